@@ -1,17 +1,23 @@
 use std::{
+    collections::HashMap,
+    io::Write,
     ops::Deref,
     path::Path,
     process::{Command, Stdio},
+    time::SystemTime,
 };
 
-use noa_editorconfig::EditorConfig;
+use noa_editorconfig::{EditorConfig, EndOfLine};
 use noa_languages::{get_language_by_name, tree_sitter, Language};
 
 use crate::{
     cursor::{Cursor, CursorId, CursorSet, Position, Range},
+    encoding::Encoding,
+    extras::{folding::Folds, snippets::SnippetSession},
     mut_raw_buffer::{Change, MutRawBuffer},
     raw_buffer::RawBuffer,
-    syntax::{ParserError, Syntax},
+    syntax::{ParserError, Syntax, SyntaxTrees},
+    undo_tree::{UndoHistory, UndoPath, UndoTree},
 };
 
 #[derive(Clone, PartialEq, Debug)]
@@ -20,6 +26,16 @@ pub struct TextEdit {
     pub new_text: String,
 }
 
+/// A replacement applied by [`Buffer::edit_ranges`].
+#[derive(Clone, PartialEq, Debug)]
+pub struct RangeEdit {
+    pub range: Range,
+    pub new_text: String,
+    /// Whether positions at the beginning of `range` stay there. Otherwise,
+    /// they move to the end of `new_text`.
+    pub stick_left: bool,
+}
+
 impl From<lsp_types::TextEdit> for TextEdit {
     fn from(edit: lsp_types::TextEdit) -> Self {
         Self {
@@ -29,19 +45,28 @@ impl From<lsp_types::TextEdit> for TextEdit {
     }
 }
 
-struct UndoState {
-    buf: RawBuffer,
-    cursors: CursorSet,
-}
-
 pub struct Buffer {
     lang: &'static Language,
-    syntax: Option<Syntax>,
+    pub(crate) syntax: Option<Syntax>,
     pub(crate) buf: MutRawBuffer,
     pub(crate) cursors: CursorSet,
     pub(crate) config: EditorConfig,
-    undo_stack: Vec<UndoState>,
-    redo_stack: Vec<UndoState>,
+    /// The line ending used when writing into a file. The buffer itself
+    /// always uses LF.
+    end_of_line: EndOfLine,
+    /// `true` if the line ending is set explicitly by `set_end_of_line`. The
+    /// config no longer overrides it.
+    end_of_line_overridden: bool,
+    undo_tree: UndoTree,
+    /// The buffer state at the last `save_undo`.
+    last_undo_buf: RawBuffer,
+    /// The selections before and after each `expand_selections` for each
+    /// cursor.
+    pub(crate) expansion_history: HashMap<CursorId, Vec<(Range, Range)>>,
+    /// The folded regions.
+    pub(crate) folds: Folds,
+    /// The snippet being edited.
+    pub(crate) snippet_session: Option<SnippetSession>,
 }
 
 impl Buffer {
@@ -52,8 +77,13 @@ impl Buffer {
             buf: MutRawBuffer::new(),
             cursors: CursorSet::new(),
             config: EditorConfig::default(),
-            undo_stack: Vec::new(),
-            redo_stack: Vec::new(),
+            end_of_line: EndOfLine::Lf,
+            end_of_line_overridden: false,
+            undo_tree: UndoTree::new(),
+            last_undo_buf: RawBuffer::new(),
+            expansion_history: HashMap::new(),
+            folds: Folds::default(),
+            snippet_session: None,
         }
     }
 
@@ -69,8 +99,10 @@ impl Buffer {
     }
 
     pub fn from_reader<T: std::io::Read>(reader: T) -> std::io::Result<Buffer> {
+        let (raw_buffer, eol) = RawBuffer::from_reader_with_end_of_line(reader)?;
         Ok(Buffer {
-            buf: MutRawBuffer::from_reader(reader)?,
+            buf: MutRawBuffer::from_raw_buffer(raw_buffer),
+            end_of_line: eol.unwrap_or(EndOfLine::Lf),
             ..Default::default()
         })
     }
@@ -82,7 +114,12 @@ impl Buffer {
     }
 
     pub fn set_from_reader<T: std::io::Read>(&mut self, reader: T) -> std::io::Result<()> {
-        self.set_raw_buffer(RawBuffer::from_reader(reader)?);
+        let (raw_buffer, eol) = RawBuffer::from_reader_with_end_of_line(reader)?;
+        self.set_raw_buffer(raw_buffer);
+        if let Some(eol) = self.config.end_of_line.or(eol) {
+            self.end_of_line = eol;
+        }
+        self.end_of_line_overridden = false;
         Ok(())
     }
 
@@ -90,17 +127,51 @@ impl Buffer {
         &self.config
     }
 
+    /// Sets the config. `end_of_line` in the config, if any, takes precedence
+    /// over the line ending detected in the file unless it's set by
+    /// `set_end_of_line`.
     pub fn set_editorconfig(&mut self, config: EditorConfig) {
+        if let Some(eol) = config.end_of_line {
+            if !self.end_of_line_overridden {
+                self.end_of_line = eol;
+            }
+        }
+
         self.config = config;
     }
 
+    pub fn end_of_line(&self) -> EndOfLine {
+        self.end_of_line
+    }
+
+    /// Changes the line ending used when saving the buffer.
+    pub fn set_end_of_line(&mut self, eol: EndOfLine) {
+        self.end_of_line = eol;
+        self.end_of_line_overridden = true;
+    }
+
     pub fn syntax(&self) -> Option<&Syntax> {
         self.syntax.as_ref()
     }
 
+    /// Returns `true` if the syntax tree reflects the current text. It's not
+    /// while the buffer is being parsed in background after edits.
+    pub fn is_syntax_up_to_date(&self) -> bool {
+        matches!(&self.syntax, Some(syntax) if syntax.is_up_to_date(&self.buf))
+    }
+
+    /// Replaces the syntax tree with the one parsed from the current text.
     pub fn set_syntax_tree(&mut self, new_tree: tree_sitter::Tree) {
         if let Some(syntax) = self.syntax.as_mut() {
-            syntax.set_tree(new_tree);
+            syntax.set_tree(new_tree, Some(self.buf.revision()));
+        }
+    }
+
+    /// Updates the syntax tree and the ones of injected languages.
+    pub fn set_syntax_trees(&mut self, new_trees: SyntaxTrees) {
+        if let Some(syntax) = self.syntax.as_mut() {
+            syntax.set_tree(new_trees.tree, new_trees.revision);
+            syntax.set_injected_trees(new_trees.injected_trees);
         }
     }
 
@@ -153,7 +224,9 @@ impl Buffer {
             "tried to add a cursors with a out-of-buffer range",
         );
 
-        self.cursors.add_cursor(selection)
+        let id = self.cursors.add_cursor(selection);
+        self.unfold_at_cursors();
+        id
     }
 
     pub fn clear_secondary_cursors(&mut self) {
@@ -188,6 +261,9 @@ impl Buffer {
                 *c.selection_mut() = self.buf.clamp_range(c.selection());
             }
         });
+
+        // Reveal the cursor jumped into folded lines (e.g. by a search).
+        self.unfold_at_cursors();
     }
 
     pub fn update_cursors_with<F>(&mut self, mut f: F)
@@ -231,6 +307,38 @@ impl Buffer {
         });
     }
 
+    /// Applies edits from the bottom of the buffer so that the remaining
+    /// ranges are not affected. The ranges must not overlap. Returns the
+    /// changes in the applied order with their `stick_left`, to adjust
+    /// positions computed before the edits.
+    pub(crate) fn apply_range_edits(&mut self, mut edits: Vec<RangeEdit>) -> Vec<(Change, bool)> {
+        edits.sort_by_key(|edit| edit.range.front());
+        let mut changes = Vec::new();
+        for edit in edits.iter().rev() {
+            let change = self.buf.edit(edit.range, &edit.new_text).clone();
+            changes.push((change, edit.stick_left));
+        }
+
+        changes
+    }
+
+    /// Applies edits as [`Buffer::apply_range_edits`] does and moves the
+    /// cursors along with the text around them.
+    pub fn edit_ranges(&mut self, edits: Vec<RangeEdit>) {
+        let changes = self.apply_range_edits(edits);
+        let mut new_cursors = self.cursors().to_vec();
+        for c in &mut new_cursors {
+            let [start, end] = [c.selection().start, c.selection().end].map(|pos| {
+                changes.iter().fold(pos, |pos, (change, stick_left)| {
+                    change.adjust_position(pos, *stick_left)
+                })
+            });
+            c.select(start.y, start.x, end.y, end.x);
+        }
+
+        self.cursors.update_cursors(&new_cursors);
+    }
+
     pub fn deselect_cursors(&mut self) {
         self.cursors.foreach(|c, _past_cursors| {
             c.move_to(c.moving_position().y, c.moving_position().x);
@@ -238,23 +346,25 @@ impl Buffer {
     }
 
     pub fn save_to_file_without_formatting(&mut self, path: &Path) -> std::io::Result<()> {
-        // Write into a temporary file and then (hopefully atomically) move it
-        // to `path`.
-        let mut f = tempfile::NamedTempFile::new()?;
-        self.buf.write_to(&mut f)?;
-        f.persist(path)?;
-
-        Ok(())
+        self.write_to_file(path, Encoding::UTF_8)
     }
 
-    pub fn save_to_file(&mut self, path: &Path) -> std::io::Result<()> {
+    pub fn save_to_file(&mut self, path: &Path, encoding: Encoding) -> std::io::Result<()> {
         self.ensure_insert_final_newline();
-        self.save_to_file_without_formatting(path)
+        self.write_to_file(path, encoding)
     }
 
-    pub fn save_to_file_with_sudo(&mut self, path: &Path) -> std::io::Result<()> {
+    pub fn save_to_file_with_sudo(
+        &mut self,
+        path: &Path,
+        encoding: Encoding,
+    ) -> std::io::Result<()> {
         self.ensure_insert_final_newline();
 
+        // Encode the text first not to leave the file truncated if it fails.
+        let mut encoded = Vec::new();
+        self.write_encoded(&mut encoded, encoding)?;
+
         let magic = "sudo is available without password";
         let check_sudo_output = Command::new("sudo")
             .args(&["echo", magic])
@@ -287,12 +397,35 @@ impl Buffer {
             .stderr(Stdio::null())
             .spawn()?;
 
-        let stdin = use_sudo.stdin.take().unwrap();
-        self.buf.write_to(stdin)?;
+        let mut stdin = use_sudo.stdin.take().unwrap();
+        stdin.write_all(&encoded)?;
 
         Ok(())
     }
 
+    fn write_to_file(&mut self, path: &Path, encoding: Encoding) -> std::io::Result<()> {
+        // Write into a temporary file and then (hopefully atomically) move it
+        // to `path`.
+        let mut f = tempfile::NamedTempFile::new()?;
+        self.write_encoded(&mut f, encoding)?;
+        f.persist(path)?;
+
+        Ok(())
+    }
+
+    fn write_encoded(&self, mut writer: impl Write, encoding: Encoding) -> std::io::Result<()> {
+        if encoding == Encoding::UTF_8 {
+            return self.buf.write_to_with_end_of_line(writer, self.end_of_line);
+        }
+
+        let mut text = Vec::new();
+        self.buf
+            .write_to_with_end_of_line(&mut text, self.end_of_line)?;
+        // The buffer always holds a valid UTF-8 text.
+        let text = String::from_utf8(text).unwrap();
+        writer.write_all(&encoding.encode(&text)?)
+    }
+
     /// Inserts a newline if the buffer doesn't end with a newline.
     fn ensure_insert_final_newline(&mut self) {
         let last_y = self.num_lines() - 1;
@@ -404,52 +537,99 @@ impl Buffer {
 
     /// Returns true if the buffer was modified since the last undo save.
     pub fn save_undo(&mut self) -> bool {
-        if let Some(last_undo) = self.undo_stack.last() {
-            if last_undo.buf == *self.buf.raw_buffer() {
+        if !self.undo_tree.is_empty() {
+            if self.buf.undo_log().is_empty() {
                 // No changes.
                 return false;
             }
-        }
 
-        self.redo_stack.clear();
-        self.undo_stack.push(UndoState {
-            buf: self.buf.raw_buffer().clone(),
-            cursors: self.cursors.clone(),
-        });
+            // Edits which cancel each other out (e.g. typing then backspacing).
+            if self.last_undo_buf.len_chars() == self.buf.len_chars()
+                && self.last_undo_buf == *self.buf.raw_buffer()
+            {
+                self.buf.clear_undo_log();
+                return false;
+            }
+        }
 
+        let changes = self.buf.clear_undo_log();
+        self.undo_tree.push(changes, self.cursors.clone());
+        self.last_undo_buf = self.buf.raw_buffer().clone();
         true
     }
 
-    /// Returns `false` if the undo stack is empty.
+    /// Returns `false` if there's no parent state to go back to.
     pub fn undo(&mut self) -> bool {
-        while let Some(state) = self.undo_stack.pop() {
-            if *self.raw_buffer() == state.buf {
-                continue;
-            }
-
-            self.set_raw_buffer(state.buf.clone());
-            self.cursors = state.cursors.clone();
-            self.redo_stack.push(state);
-            return true;
-        }
-
-        false
+        // Keep unsaved modifications so that we can redo them.
+        self.save_undo();
+        let path = self.undo_tree.undo();
+        apply_undo_path(&mut self.buf, &mut self.cursors, path) && self.after_undo_path()
     }
 
-    /// Returns `false` if the redo stack is empty.
+    /// Returns `false` if there's no child state to go forward to.
     pub fn redo(&mut self) -> bool {
-        while let Some(state) = self.redo_stack.pop() {
-            if *self.raw_buffer() == state.buf {
-                continue;
+        self.save_undo();
+        let path = self.undo_tree.redo();
+        apply_undo_path(&mut self.buf, &mut self.cursors, path) && self.after_undo_path()
+    }
+
+    /// Switches to the next (or previous) branch forked from the same state.
+    /// Returns `false` if there's no other branch.
+    pub fn switch_undo_branch(&mut self, forward: bool) -> bool {
+        self.save_undo();
+        let path = self.undo_tree.switch_branch(forward);
+        apply_undo_path(&mut self.buf, &mut self.cursors, path) && self.after_undo_path()
+    }
+
+    /// Goes back to the previously saved state in chronological order, across
+    /// branches.
+    pub fn undo_chronologically(&mut self) -> bool {
+        self.save_undo();
+        let path = self.undo_tree.earlier();
+        apply_undo_path(&mut self.buf, &mut self.cursors, path) && self.after_undo_path()
+    }
+
+    /// Goes forward to the next saved state in chronological order, across
+    /// branches.
+    pub fn redo_chronologically(&mut self) -> bool {
+        self.save_undo();
+        let path = self.undo_tree.later();
+        apply_undo_path(&mut self.buf, &mut self.cursors, path) && self.after_undo_path()
+    }
+
+    /// Restores the state as it was at `time`.
+    pub fn undo_to_time(&mut self, time: SystemTime) -> bool {
+        self.save_undo();
+        let path = self.undo_tree.travel_to(time);
+        apply_undo_path(&mut self.buf, &mut self.cursors, path) && self.after_undo_path()
+    }
+
+    /// Returns the undo history to be persisted. The current state in the
+    /// history is the one at the last `save_undo`.
+    pub fn undo_history(&self) -> Option<UndoHistory> {
+        self.undo_tree.to_history()
+    }
+
+    /// Replaces the undo history. The current buffer must be identical to the
+    /// current state in the history. Returns `false` if the history is
+    /// malformed or does not match the buffer.
+    pub fn restore_undo_history(&mut self, history: UndoHistory) -> bool {
+        match UndoTree::from_history(history, self.buf.raw_buffer()) {
+            Some(tree) => {
+                self.undo_tree = tree;
+                self.buf.clear_undo_log();
+                self.last_undo_buf = self.buf.raw_buffer().clone();
+                true
             }
-
-            self.set_raw_buffer(state.buf.clone());
-            self.cursors = state.cursors.clone();
-            self.redo_stack.push(state);
-            return true;
+            None => false,
         }
+    }
 
-        false
+    fn after_undo_path(&mut self) -> bool {
+        // Changes made by undo/redo are already in the history.
+        self.buf.clear_undo_log();
+        self.last_undo_buf = self.buf.raw_buffer().clone();
+        true
     }
 
     pub fn undo_cursor_movements(&mut self) {
@@ -465,7 +645,28 @@ impl Buffer {
     }
 
     pub fn clear_recorded_changes(&mut self) -> Vec<Change> {
-        self.buf.clear_changes()
+        let changes = self.buf.clear_changes();
+        self.update_folds(&changes);
+        self.update_snippet_session(&changes);
+        changes
+    }
+}
+
+/// Returns `false` if `path` is `None`.
+fn apply_undo_path(
+    buf: &mut MutRawBuffer,
+    cursors: &mut CursorSet,
+    path: Option<UndoPath>,
+) -> bool {
+    match path {
+        Some(path) => {
+            for change in path.changes {
+                buf.edit(change.range, &change.insert_text);
+            }
+            *cursors = path.cursors.clone();
+            true
+        }
+        None => false,
     }
 }
 
@@ -511,6 +712,37 @@ mod tests {
         assert_eq!(b.text(), "Hello World!");
     }
 
+    #[test]
+    fn edit_ranges() {
+        let mut b = Buffer::from_text("ab cd\nef gh");
+        b.set_cursors_for_test(&[
+            Cursor::new(0, 0),
+            Cursor::new_selection(1, 2, 0, 4),
+            Cursor::new(1, 3),
+        ]);
+        b.edit_ranges(vec![
+            RangeEdit {
+                range: Range::new(1, 3, 1, 5),
+                new_text: "GHGH".to_owned(),
+                stick_left: false,
+            },
+            RangeEdit {
+                range: Range::new(0, 0, 0, 2),
+                new_text: "ABAB".to_owned(),
+                stick_left: true,
+            },
+        ]);
+        assert_eq!(b.text(), "ABAB cd\nef GHGH");
+        assert_eq!(
+            b.cursors(),
+            &[
+                Cursor::new(0, 0),
+                Cursor::new_selection(1, 2, 0, 6),
+                Cursor::new(1, 7),
+            ]
+        );
+    }
+
     #[test]
     fn deletion() {
         // a|bc
@@ -945,4 +1177,111 @@ mod tests {
         assert_eq!(b.text(), "XYZ");
         assert_eq!(b.cursors(), &[Cursor::new(0, 3)]);
     }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut b = Buffer::new();
+        b.save_undo();
+        b.insert("abc");
+        b.save_undo();
+        // A newline is not merged into the previous typing.
+        b.insert("\nd");
+        assert_eq!(b.text(), "abc\nd");
+
+        assert!(b.undo());
+        assert_eq!(b.text(), "abc");
+        assert!(b.undo());
+        assert_eq!(b.text(), "");
+        assert!(!b.undo());
+
+        assert!(b.redo());
+        assert_eq!(b.text(), "abc");
+        assert!(b.redo());
+        assert_eq!(b.text(), "abc\nd");
+        assert_eq!(b.cursors(), &[Cursor::new(1, 1)]);
+        assert!(!b.redo());
+    }
+
+    #[test]
+    fn save_undo_without_changes() {
+        let mut b = Buffer::new();
+        assert!(b.save_undo());
+        assert!(!b.save_undo());
+        b.insert("a");
+        b.backspace();
+        assert!(!b.save_undo());
+        b.insert("a");
+        assert!(b.save_undo());
+    }
+
+    #[test]
+    fn undo_branches() {
+        let mut b = Buffer::new();
+        b.save_undo();
+        b.insert("abc");
+        b.save_undo();
+
+        // Editing after undo forks a new branch instead of discarding "abc".
+        b.undo();
+        b.insert("xyz");
+        b.save_undo();
+        assert!(b.undo());
+        assert_eq!(b.text(), "");
+        assert!(b.redo());
+        assert_eq!(b.text(), "xyz");
+
+        assert!(b.switch_undo_branch(true));
+        assert_eq!(b.text(), "abc");
+        assert!(b.undo_chronologically());
+        assert_eq!(b.text(), "");
+        assert!(b.redo_chronologically());
+        assert_eq!(b.text(), "abc");
+        assert!(b.redo_chronologically());
+        assert_eq!(b.text(), "xyz");
+    }
+
+    #[test]
+    fn preserve_line_endings() {
+        let mut b = Buffer::from_reader("a\r\nb\r\n".as_bytes()).unwrap();
+        assert_eq!(b.text(), "a\nb\n");
+        assert_eq!(b.end_of_line(), EndOfLine::CrLf);
+
+        b.set_editorconfig(EditorConfig {
+            end_of_line: Some(EndOfLine::Lf),
+            ..Default::default()
+        });
+        assert_eq!(b.end_of_line(), EndOfLine::Lf);
+
+        let mut b = Buffer::from_reader("a\r\nb\r\n".as_bytes()).unwrap();
+        b.set_editorconfig(EditorConfig::default());
+        assert_eq!(b.end_of_line(), EndOfLine::CrLf);
+
+        // An explicit conversion is kept.
+        b.set_end_of_line(EndOfLine::Cr);
+        b.set_editorconfig(EditorConfig {
+            end_of_line: Some(EndOfLine::Lf),
+            ..Default::default()
+        });
+        assert_eq!(b.end_of_line(), EndOfLine::Cr);
+    }
+
+    #[test]
+    fn save_with_encoding() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("utf16.txt");
+
+        let mut b = Buffer::from_reader("a\r\nb\r\n".as_bytes()).unwrap();
+        b.save_to_file(&path, Encoding::from_label("utf-16le-bom").unwrap())
+            .unwrap();
+        assert_eq!(
+            std::fs::read(&path).unwrap(),
+            b"\xff\xfea\x00\r\x00\n\x00b\x00\r\x00\n\x00"
+        );
+
+        let path = dir.path().join("latin1.txt");
+        let mut b = Buffer::from_text("あ");
+        assert!(b
+            .save_to_file(&path, Encoding::from_label("latin1").unwrap())
+            .is_err());
+    }
 }
@@ -5,6 +5,8 @@ use std::{
     sync::atomic::{self, AtomicUsize},
 };
 
+use serde::{Deserialize, Serialize};
+
 use crate::raw_buffer::RawBuffer;
 
 /// The zero-based position in the buffer.
@@ -18,7 +20,7 @@ use crate::raw_buffer::RawBuffer;
 /// ^       ^      ^
 /// x=0     x=1    x=2
 /// ```
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Position {
     /// The line number. 0-origin.
     pub y: usize,
@@ -148,7 +150,7 @@ impl From<lsp_types::Position> for Position {
 ///
 /// Note that `start` don't have to be less (in respect to its `Ord` implementation)
 /// than `end`.
-#[derive(Clone, Copy, PartialEq, Hash)]
+#[derive(Clone, Copy, PartialEq, Hash, Serialize, Deserialize)]
 pub struct Range {
     /// The start position.
     pub(crate) start: Position,
@@ -489,6 +491,8 @@ pub struct CursorSet {
     cursors: Vec<Cursor>,
     undo_stack: Vec<CursorUndoState>,
     redo_stack: Vec<CursorUndoState>,
+    /// The previous main cursors replaced by `add_main_cursor`.
+    prev_main_cursors: Vec<CursorId>,
 }
 
 impl CursorSet {
@@ -497,6 +501,7 @@ impl CursorSet {
             cursors: vec![Cursor::new_main_cursor(0, 0)],
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
+            prev_main_cursors: Vec::new(),
         }
     }
 
@@ -517,6 +522,41 @@ impl CursorSet {
         self.cursors.iter().find(|c| c.id == id)
     }
 
+    /// Creates a cursor set from selections. The first one becomes the main
+    /// cursor.
+    pub fn from_selections(selections: &[Range]) -> CursorSet {
+        debug_assert!(!selections.is_empty());
+        let mut cursors: Vec<Cursor> = selections.iter().copied().map(Cursor::from_range).collect();
+        cursors[0].id = MAIN_CURSOR_ID;
+
+        let mut set = CursorSet::new();
+        set.update_cursors(&cursors);
+        set
+    }
+
+    /// Replaces all cursors with new ones at the selections. The first one
+    /// becomes the main cursor.
+    pub fn set_selections(&mut self, selections: &[Range]) {
+        debug_assert!(!selections.is_empty());
+        let mut new_cursors: Vec<Cursor> =
+            selections.iter().copied().map(Cursor::from_range).collect();
+        new_cursors[0].id = MAIN_CURSOR_ID;
+        self.save_undo_state();
+        self.update_cursors(&new_cursors);
+    }
+
+    /// Returns the selections of cursors. The main cursor comes first.
+    pub fn selections(&self) -> Vec<Range> {
+        let mut selections = vec![self.main_cursor().selection()];
+        selections.extend(
+            self.cursors
+                .iter()
+                .filter(|c| !c.is_main_cursor())
+                .map(|c| c.selection()),
+        );
+        selections
+    }
+
     pub fn as_slice(&self) -> &[Cursor] {
         &self.cursors
     }
@@ -536,6 +576,57 @@ impl CursorSet {
         id
     }
 
+    /// Adds a cursor and makes it the main cursor. The previous main cursor
+    /// becomes a secondary one.
+    pub fn add_main_cursor(&mut self, selection: Range) {
+        let mut new_cursors = self.cursors.to_vec();
+        for c in &mut new_cursors {
+            if c.is_main_cursor() {
+                c.id = CursorId(NEXT_CURSOR_ID.fetch_add(1, atomic::Ordering::SeqCst));
+                self.prev_main_cursors.push(c.id);
+            }
+        }
+
+        new_cursors.push(Cursor {
+            id: MAIN_CURSOR_ID,
+            selection,
+        });
+        self.save_undo_state();
+        self.update_cursors(&new_cursors);
+    }
+
+    /// Removes the main cursor added by the last `add_main_cursor` and makes
+    /// the previous main cursor the main one again.
+    ///
+    /// Returns false if there are no such cursors.
+    pub fn undo_main_cursor_addition(&mut self) -> bool {
+        while let Some(prev_id) = self.prev_main_cursors.pop() {
+            if self.get_cursor_by_id(prev_id).is_none() {
+                // The cursor has been removed or merged.
+                continue;
+            }
+
+            let new_cursors: Vec<Cursor> = self
+                .cursors
+                .iter()
+                .filter(|c| !c.is_main_cursor())
+                .map(|c| {
+                    let mut c = c.clone();
+                    if c.id == prev_id {
+                        c.id = MAIN_CURSOR_ID;
+                    }
+                    c
+                })
+                .collect();
+
+            self.save_undo_state();
+            self.update_cursors(&new_cursors);
+            return true;
+        }
+
+        false
+    }
+
     pub fn remove_cursor(&mut self, id: CursorId) {
         let mut new_cursors = self.cursors.to_vec();
         new_cursors.retain(|c| c.id != id);
@@ -0,0 +1,367 @@
+use std::{borrow::Cow, fmt, io::Read};
+
+use noa_editorconfig::Charset;
+
+/// The character encoding of a file. The buffer itself always holds UTF-8.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Encoding {
+    encoding: &'static encoding_rs::Encoding,
+    bom: bool,
+}
+
+impl Encoding {
+    pub const UTF_8: Encoding = Encoding {
+        encoding: encoding_rs::UTF_8,
+        bom: false,
+    };
+
+    /// Looks for an encoding by its label (e.g. `shift_jis`, `latin1`, and
+    /// `utf-8-bom`). The BOM is written only if the label ends with `-bom`.
+    pub fn from_label(label: &str) -> Option<Encoding> {
+        let label = label.trim().to_ascii_lowercase();
+        let (label, bom) = match label.strip_suffix("-bom") {
+            Some(label) => (label, true),
+            None => (label.as_str(), false),
+        };
+
+        let encoding = encoding_rs::Encoding::for_label(label.as_bytes())?;
+        if encoding == encoding_rs::REPLACEMENT {
+            return None;
+        }
+
+        // Only Unicode encodings have a BOM.
+        if bom && encoding != encoding_rs::UTF_8 && !is_utf16(encoding) {
+            return None;
+        }
+
+        Some(Encoding { encoding, bom })
+    }
+
+    /// Returns the encoding with the BOM enabled if `bytes` starts with the
+    /// BOM of the encoding.
+    pub fn with_bom_in(self, bytes: &[u8]) -> Encoding {
+        let has_bom = matches!(
+            encoding_rs::Encoding::for_bom(bytes),
+            Some((encoding, _)) if encoding == self.encoding
+        );
+
+        Encoding {
+            bom: self.bom || has_bom,
+            ..self
+        }
+    }
+
+    /// Determines the encoding of `bytes`. A BOM takes precedence over
+    /// `preferred` (e.g. `charset` in .editorconfig). Otherwise, it guesses
+    /// the encoding from the contents.
+    ///
+    /// `bytes` can be the beginning of a file: a UTF-8 character cut off at
+    /// the end is ignored.
+    pub fn detect(bytes: &[u8], preferred: Option<Encoding>) -> Encoding {
+        if let Some((encoding, _)) = encoding_rs::Encoding::for_bom(bytes) {
+            return Encoding {
+                encoding,
+                bom: true,
+            };
+        }
+
+        if let Some(preferred) = preferred {
+            return preferred;
+        }
+
+        match std::str::from_utf8(bytes) {
+            Ok(_) => return Encoding::UTF_8,
+            Err(err) if err.error_len().is_none() => return Encoding::UTF_8,
+            Err(_) => {}
+        }
+
+        let mut detector = chardetng::EncodingDetector::new();
+        detector.feed(bytes, true);
+        Encoding {
+            encoding: detector.guess(None, true),
+            bom: false,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.encoding.name()
+    }
+
+    pub fn has_bom(&self) -> bool {
+        self.bom
+    }
+
+    /// Decodes `bytes` into UTF-8. The BOM, if any, is removed. Malformed
+    /// sequences are replaced with U+FFFD.
+    pub fn decode<'a>(&self, bytes: &'a [u8]) -> Cow<'a, str> {
+        self.encoding.decode_with_bom_removal(bytes).0
+    }
+
+    /// Returns a reader which decodes `reader` into UTF-8 on the fly. The BOM,
+    /// if any, is removed. Malformed sequences are replaced with U+FFFD.
+    pub fn decode_reader<R: Read>(&self, reader: R) -> DecodeReader<R> {
+        DecodeReader {
+            inner: reader,
+            decoder: self.encoding.new_decoder_with_bom_removal(),
+            input: Vec::new(),
+            input_pos: 0,
+            output: Vec::new(),
+            output_pos: 0,
+            eof: false,
+            finished: false,
+        }
+    }
+
+    /// Encodes `text` prepending the BOM if needed. Returns an error if
+    /// `text` contains characters not representable in the encoding.
+    pub fn encode<'a>(&self, text: &'a str) -> std::io::Result<Cow<'a, [u8]>> {
+        let bom: &[u8] = match (self.bom, self.encoding.name()) {
+            (false, _) => &[],
+            (true, "UTF-16LE") => &[0xff, 0xfe],
+            (true, "UTF-16BE") => &[0xfe, 0xff],
+            (true, _) => &[0xef, 0xbb, 0xbf],
+        };
+
+        // encoding_rs does not support encoding into UTF-16.
+        let encoded = if self.encoding == encoding_rs::UTF_16LE {
+            Cow::Owned(text.encode_utf16().flat_map(u16::to_le_bytes).collect())
+        } else if self.encoding == encoding_rs::UTF_16BE {
+            Cow::Owned(text.encode_utf16().flat_map(u16::to_be_bytes).collect())
+        } else {
+            let (encoded, _, unmappable) = self.encoding.encode(text);
+            if unmappable {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("the text contains characters not representable in {}", self),
+                ));
+            }
+
+            encoded
+        };
+
+        if bom.is_empty() {
+            Ok(encoded)
+        } else {
+            let mut bytes = bom.to_vec();
+            bytes.extend_from_slice(&encoded);
+            Ok(Cow::Owned(bytes))
+        }
+    }
+}
+
+impl Default for Encoding {
+    fn default() -> Encoding {
+        Encoding::UTF_8
+    }
+}
+
+impl From<Charset> for Encoding {
+    fn from(charset: Charset) -> Encoding {
+        let (encoding, bom) = match charset {
+            // encoding_rs treats latin1 as windows-1252, its superset.
+            Charset::Latin1 => (encoding_rs::WINDOWS_1252, false),
+            Charset::Utf8 => (encoding_rs::UTF_8, false),
+            Charset::Utf8Bom => (encoding_rs::UTF_8, true),
+            // The BOM comes from the file or an explicit `-bom` label.
+            Charset::Utf16Be => (encoding_rs::UTF_16BE, false),
+            Charset::Utf16Le => (encoding_rs::UTF_16LE, false),
+        };
+
+        Encoding { encoding, bom }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.bom && !is_utf16(self.encoding) {
+            write!(f, "{} BOM", self.name())
+        } else {
+            write!(f, "{}", self.name())
+        }
+    }
+}
+
+/// A reader returned by `Encoding::decode_reader`.
+pub struct DecodeReader<R: Read> {
+    inner: R,
+    decoder: encoding_rs::Decoder,
+    /// The bytes read from `inner`. Bytes before `input_pos` are already
+    /// decoded.
+    input: Vec<u8>,
+    input_pos: usize,
+    /// The decoded bytes. Bytes before `output_pos` are already returned.
+    output: Vec<u8>,
+    output_pos: usize,
+    /// `true` if `inner` reached EOF.
+    eof: bool,
+    /// `true` if all bytes are decoded.
+    finished: bool,
+}
+
+impl<R: Read> Read for DecodeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        const BUF_SIZE: usize = 64 * 1024;
+
+        while self.output_pos == self.output.len() && !self.finished {
+            if self.input_pos == self.input.len() && !self.eof {
+                self.input.resize(BUF_SIZE, 0);
+                let len = self.inner.read(&mut self.input)?;
+                self.input.truncate(len);
+                self.input_pos = 0;
+                self.eof = len == 0;
+            }
+
+            self.output.resize(BUF_SIZE, 0);
+            let (result, read_len, written_len, _) = self.decoder.decode_to_utf8(
+                &self.input[self.input_pos..],
+                &mut self.output,
+                self.eof,
+            );
+            self.input_pos += read_len;
+            self.output.truncate(written_len);
+            self.output_pos = 0;
+            if self.eof && result == encoding_rs::CoderResult::InputEmpty {
+                self.finished = true;
+            }
+        }
+
+        let len = buf.len().min(self.output.len() - self.output_pos);
+        buf[..len].copy_from_slice(&self.output[self.output_pos..self.output_pos + len]);
+        self.output_pos += len;
+        Ok(len)
+    }
+}
+
+fn is_utf16(encoding: &'static encoding_rs::Encoding) -> bool {
+    encoding == encoding_rs::UTF_16LE || encoding == encoding_rs::UTF_16BE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn detect_and_decode() {
+        let utf8 = "こんにちは".as_bytes();
+        assert_eq!(Encoding::detect(utf8, None), Encoding::UTF_8);
+        assert_eq!(Encoding::detect(&utf8[..4], None), Encoding::UTF_8);
+
+        let utf8_bom = b"\xef\xbb\xbfabc";
+        let encoding = Encoding::detect(utf8_bom, None);
+        assert_eq!(encoding, Encoding::from(Charset::Utf8Bom));
+        assert_eq!(encoding.decode(utf8_bom), "abc");
+
+        let utf16le = b"\xff\xfea\x00b\x00";
+        let encoding = Encoding::detect(utf16le, Some(Encoding::UTF_8));
+        assert_eq!(encoding, Encoding::from_label("utf-16le-bom").unwrap());
+        assert_eq!(encoding.decode(utf16le), "ab");
+        assert!(!Encoding::from(Charset::Utf16Le).has_bom());
+
+        let latin1 = b"caf\xe9";
+        assert_eq!(
+            Encoding::detect(latin1, Some(Encoding::from(Charset::Latin1))).decode(latin1),
+            "café"
+        );
+
+        let shift_jis = b"\x82\xb1\x82\xf1\x82\xc9\x82\xbf\x82\xcd\x81\x41\x90\xa2\x8a\x45";
+        let encoding = Encoding::detect(shift_jis, None);
+        assert_eq!(encoding.name(), "Shift_JIS");
+        assert_eq!(encoding.decode(shift_jis), "こんにちは、世界");
+    }
+
+    #[test]
+    fn decode_reader() {
+        // Read one byte at a time to split characters across reads.
+        struct OneByteReader<'a>(&'a [u8]);
+
+        impl Read for OneByteReader<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let len = buf.len().min(self.0.len()).min(1);
+                buf[..len].copy_from_slice(&self.0[..len]);
+                self.0 = &self.0[len..];
+                Ok(len)
+            }
+        }
+
+        let utf16le = b"\xff\xfea\x00B0\x0a\x00";
+        let mut text = String::new();
+        Encoding::from(Charset::Utf16Le)
+            .decode_reader(OneByteReader(utf16le))
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text, "aあ\n");
+
+        let shift_jis = b"\x82\xb1\x82\xf1\x82";
+        let mut text = String::new();
+        Encoding::from_label("shift_jis")
+            .unwrap()
+            .decode_reader(&shift_jis[..])
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text, "こん\u{fffd}");
+    }
+
+    #[test]
+    fn encode() {
+        assert_eq!(Encoding::UTF_8.encode("abc").unwrap(), &b"abc"[..]);
+        assert_eq!(
+            Encoding::from(Charset::Utf8Bom).encode("abc").unwrap(),
+            &b"\xef\xbb\xbfabc"[..]
+        );
+        assert_eq!(
+            Encoding::from(Charset::Utf16Be).encode("ab").unwrap(),
+            &b"\x00a\x00b"[..]
+        );
+        assert_eq!(
+            Encoding::from_label("utf-16be-bom")
+                .unwrap()
+                .encode("ab")
+                .unwrap(),
+            &b"\xfe\xff\x00a\x00b"[..]
+        );
+        assert_eq!(
+            Encoding::from_label("shift_jis")
+                .unwrap()
+                .encode("こんにちは")
+                .unwrap(),
+            &b"\x82\xb1\x82\xf1\x82\xc9\x82\xbf\x82\xcd"[..]
+        );
+        assert!(Encoding::from(Charset::Latin1)
+            .encode("こんにちは")
+            .is_err());
+    }
+
+    #[test]
+    fn from_label() {
+        assert_eq!(Encoding::from_label("UTF-8"), Some(Encoding::UTF_8));
+        assert_eq!(
+            Encoding::from_label("utf-8-bom"),
+            Some(Encoding::from(Charset::Utf8Bom))
+        );
+        assert_eq!(
+            Encoding::from_label("latin1"),
+            Some(Encoding::from(Charset::Latin1))
+        );
+        assert_eq!(
+            Encoding::from_label("utf-16le"),
+            Some(Encoding::from(Charset::Utf16Le))
+        );
+        assert!(Encoding::from_label("utf-16le-bom").unwrap().has_bom());
+        assert!(!Encoding::from_label("utf-16le").unwrap().has_bom());
+        assert_eq!(Encoding::from_label("latin1-bom"), None);
+        assert_eq!(Encoding::from_label("no-such-encoding"), None);
+    }
+
+    #[test]
+    fn with_bom_in() {
+        let utf16le = Encoding::from_label("utf-16le").unwrap();
+        assert!(utf16le.with_bom_in(b"\xff\xfea\x00").has_bom());
+        assert!(!utf16le.with_bom_in(b"a\x00").has_bom());
+        assert!(!utf16le.with_bom_in(b"\xef\xbb\xbfa").has_bom());
+        assert!(Encoding::from_label("utf-8-bom")
+            .unwrap()
+            .with_bom_in(b"a")
+            .has_bom());
+    }
+}
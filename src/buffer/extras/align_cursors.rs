@@ -0,0 +1,164 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    buffer::{Buffer, RangeEdit},
+    cursor::{Position, Range},
+    display_width::DisplayWidth,
+};
+
+/// Returns the display column after `chars`.
+fn display_column(chars: &[char], tab_width: usize) -> usize {
+    chars.iter().fold(0, |col, ch| {
+        if *ch == '\t' {
+            col + tab_width - col % tab_width
+        } else {
+            col + ch.display_width()
+        }
+    })
+}
+
+/// A line containing cursors to be aligned.
+struct CursorLine {
+    y: usize,
+    /// The x of cursors in ascending order.
+    xs: Vec<usize>,
+    /// The line text with spaces inserted so far.
+    chars: Vec<char>,
+    /// The number of spaces to be inserted before each cursor.
+    num_spaces: Vec<usize>,
+}
+
+impl CursorLine {
+    /// Returns the current index of the `i`-th cursor in `chars`.
+    fn index_of(&self, i: usize) -> usize {
+        self.xs[i] + self.num_spaces[..i].iter().sum::<usize>()
+    }
+}
+
+impl Buffer {
+    /// Inserts spaces before cursors so that they are at the same display
+    /// column. If a line has multiple cursors, the first cursors in lines are
+    /// aligned first, then the second ones, and so on.
+    pub fn align_cursors(&mut self) {
+        let tab_width = self.config.tab_width.max(1);
+
+        // The x of cursors in each line.
+        let mut xs_in_lines: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for c in self.cursors() {
+            let pos = c.front();
+            xs_in_lines.entry(pos.y).or_default().push(pos.x);
+        }
+
+        let mut lines: Vec<CursorLine> = xs_in_lines
+            .into_iter()
+            .map(|(y, mut xs)| {
+                xs.sort_unstable();
+                xs.dedup();
+                CursorLine {
+                    y,
+                    num_spaces: vec![0; xs.len()],
+                    xs,
+                    chars: self.line_text(y).chars().collect(),
+                }
+            })
+            .collect();
+
+        let max_cursors_in_line = lines.iter().map(|line| line.xs.len()).max();
+        for i in 0..max_cursors_in_line.unwrap_or(0) {
+            let target_column = lines
+                .iter()
+                .filter(|line| i < line.xs.len())
+                .map(|line| display_column(&line.chars[..line.index_of(i)], tab_width))
+                .max()
+                .unwrap_or(0);
+
+            for line in &mut lines {
+                if i >= line.xs.len() {
+                    continue;
+                }
+
+                let index = line.index_of(i);
+                let n = target_column - display_column(&line.chars[..index], tab_width);
+                line.chars.splice(index..index, vec![' '; n]);
+                line.num_spaces[i] = n;
+            }
+        }
+
+        // Cursors at the insertion points move after the spaces.
+        let mut edits = Vec::new();
+        for line in &lines {
+            for (x, n) in line.xs.iter().zip(line.num_spaces.iter()) {
+                if *n > 0 {
+                    edits.push(RangeEdit {
+                        range: Range::from_single_position(Position::new(line.y, *x)),
+                        new_text: " ".repeat(*n),
+                        stick_left: false,
+                    });
+                }
+            }
+        }
+
+        self.edit_ranges(edits);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::cursor::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn align_cursors() {
+        let mut b = Buffer::from_text("a = 1\nbcd = 2\n\tx = 3");
+        b.set_cursors_for_test(&[Cursor::new(0, 2), Cursor::new(1, 4), Cursor::new(2, 3)]);
+        b.align_cursors();
+        assert_eq!(b.text(), "a         = 1\nbcd       = 2\n\tx = 3");
+        assert_eq!(
+            b.cursors(),
+            &[Cursor::new(0, 10), Cursor::new(1, 10), Cursor::new(2, 3)]
+        );
+    }
+
+    #[test]
+    fn align_cursors_with_wide_chars() {
+        let mut b = Buffer::from_text("あい = 1\nb = 2");
+        b.set_cursors_for_test(&[
+            Cursor::new_selection(0, 3, 0, 4),
+            Cursor::new_selection(1, 2, 1, 3),
+        ]);
+        b.align_cursors();
+        assert_eq!(b.text(), "あい = 1\nb    = 2");
+        assert_eq!(
+            b.cursors(),
+            &[
+                Cursor::new_selection(0, 3, 0, 4),
+                Cursor::new_selection(1, 5, 1, 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn align_multiple_cursors_in_a_line() {
+        let mut b = Buffer::from_text("a = 1, bb = 2\nccc = 3, d = 4");
+        b.set_cursors_for_test(&[
+            Cursor::new(0, 2),
+            Cursor::new(0, 10),
+            Cursor::new(1, 4),
+            Cursor::new(1, 11),
+        ]);
+        b.align_cursors();
+        assert_eq!(b.text(), "a   = 1, bb = 2\nccc = 3, d  = 4");
+        assert_eq!(
+            b.cursors(),
+            &[
+                Cursor::new(0, 4),
+                Cursor::new(0, 12),
+                Cursor::new(1, 4),
+                Cursor::new(1, 12),
+            ]
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use std::cmp::{max, min};
+
+use crate::{
+    buffer::Buffer,
+    cursor::{Position, Range},
+    display_width::DisplayWidth,
+};
+
+/// A position in a line counted in display columns instead of characters.
+/// The column may be beyond the end of the line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DisplayPosition {
+    pub y: usize,
+    pub column: usize,
+}
+
+impl DisplayPosition {
+    pub fn new(y: usize, column: usize) -> DisplayPosition {
+        DisplayPosition { y, column }
+    }
+}
+
+/// Returns the character index at `column` in `line`. If `column` points to
+/// the middle of a wide character, it returns the index after the character
+/// if `round_up` is true, or the index of the character otherwise.
+fn column_to_x(line: &str, column: usize, round_up: bool) -> usize {
+    let mut width = 0;
+    for (x, ch) in line.chars().enumerate() {
+        if width >= column {
+            return x;
+        }
+
+        width += ch.display_width();
+        if width > column && !round_up {
+            return x;
+        }
+    }
+
+    line.chars().count()
+}
+
+impl Buffer {
+    pub fn display_position(&self, pos: Position) -> DisplayPosition {
+        let column = self
+            .substr(Range::new(pos.y, 0, pos.y, pos.x))
+            .display_width();
+        DisplayPosition::new(pos.y, column)
+    }
+
+    /// Selects the rectangle between `anchor` and `corner` with one
+    /// selection per line. Lines shorter than the left edge are skipped
+    /// except the line of `corner`, where the main cursor is placed.
+    pub fn select_block(&mut self, anchor: DisplayPosition, corner: DisplayPosition) {
+        let left = min(anchor.column, corner.column);
+        let right = max(anchor.column, corner.column);
+        let mut selections = Vec::new();
+        for y in min(anchor.y, corner.y)..=max(anchor.y, corner.y) {
+            let line = self.line_text(y);
+            if line.display_width() < left && y != corner.y {
+                continue;
+            }
+
+            let start_x = column_to_x(&line, left, false);
+            let end_x = column_to_x(&line, right, true);
+            // Keep the moving position on the corner side.
+            let selection = if corner.column < anchor.column {
+                Range::new(y, end_x, y, start_x)
+            } else {
+                Range::new(y, start_x, y, end_x)
+            };
+
+            if y == corner.y {
+                selections.insert(0, selection);
+            } else {
+                selections.push(selection);
+            }
+        }
+
+        self.cursors.set_selections(&selections);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn selections(b: &Buffer) -> Vec<Range> {
+        b.cursors().iter().map(|c| c.selection()).collect()
+    }
+
+    #[test]
+    fn display_position() {
+        let b = Buffer::from_text("aあb");
+        assert_eq!(b.display_position(Position::new(0, 0)).column, 0);
+        assert_eq!(b.display_position(Position::new(0, 2)).column, 3);
+        assert_eq!(b.display_position(Position::new(0, 3)).column, 4);
+    }
+
+    #[test]
+    fn select_block() {
+        let mut b = Buffer::from_text("abcd\nab\n\nabcd");
+        b.select_block(DisplayPosition::new(0, 1), DisplayPosition::new(3, 3));
+        assert_eq!(
+            selections(&b),
+            vec![
+                Range::new(0, 1, 0, 3),
+                Range::new(1, 1, 1, 2),
+                Range::new(3, 1, 3, 3),
+            ]
+        );
+        assert_eq!(b.main_cursor().selection(), Range::new(3, 1, 3, 3));
+
+        // Towards the top-left corner.
+        b.select_block(DisplayPosition::new(3, 3), DisplayPosition::new(2, 1));
+        assert_eq!(
+            selections(&b),
+            vec![Range::new(2, 0, 2, 0), Range::new(3, 3, 3, 1)]
+        );
+        assert_eq!(b.main_cursor().selection(), Range::new(2, 0, 2, 0));
+    }
+
+    #[test]
+    fn select_block_with_wide_chars() {
+        let mut b = Buffer::from_text("abcd\nあいう");
+        b.select_block(DisplayPosition::new(0, 1), DisplayPosition::new(1, 3));
+        assert_eq!(
+            selections(&b),
+            vec![Range::new(0, 1, 0, 3), Range::new(1, 0, 1, 2)]
+        );
+    }
+}
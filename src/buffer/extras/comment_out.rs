@@ -9,7 +9,11 @@ impl Buffer {
     pub fn toggle_line_comment_out(&mut self) {
         let keyword_without_whitespace = match self.language().line_comment.as_ref() {
             Some(keyword) => *keyword,
-            None => return,
+            None => {
+                // Fall back to block comments (e.g. CSS).
+                self.toggle_block_comment_with(true);
+                return;
+            }
         };
         let keyword_with_whitespace = format!("{} ", keyword_without_whitespace);
         let keyword_without_whitespace_len = keyword_without_whitespace.chars().count();
@@ -80,6 +84,153 @@ impl Buffer {
             }
         });
     }
+
+    /// Wraps each selection (or the current line if it's empty) in a block
+    /// comment, or unwraps it if it's already commented out.
+    pub fn toggle_block_comment(&mut self) {
+        self.toggle_block_comment_with(false);
+    }
+
+    fn toggle_block_comment_with(&mut self, linewise: bool) {
+        let (start_keyword, end_keyword) = match self.language().block_comment {
+            Some(keywords) => keywords,
+            None => return,
+        };
+
+        // Determine the regions to be edited before editing the buffer.
+        // Regions overlapping with another one are ignored.
+        let mut targets = Vec::new();
+        let mut last_region: Option<Range> = None;
+        for c in self.cursors() {
+            let mut selection = c.selection();
+            let target = if linewise || selection.is_empty() {
+                let ys = selection.overlapped_lines();
+                let first_y = selection.front().y;
+                let last_y = if ys.is_empty() { first_y } else { ys.end - 1 };
+                Range::new(
+                    first_y,
+                    self.buf.line_indent_len(first_y),
+                    last_y,
+                    self.buf.line_len(last_y),
+                )
+            } else {
+                selection
+            };
+
+            let target = match self.enclosing_block_comment(target, start_keyword, end_keyword) {
+                Some(comment) => (comment, true),
+                None => (target, false),
+            };
+
+            let overlapped = matches!(last_region, Some(last) if last.overlaps_with(target.0));
+            if overlapped {
+                targets.push(None);
+            } else {
+                last_region = Some(target.0);
+                targets.push(Some(target));
+            }
+        }
+
+        let mut targets = targets.into_iter().rev();
+        self.cursors.foreach(|c, past_cursors| {
+            let (target, commented_out) = match targets.next().flatten() {
+                Some(target) => target,
+                None => return,
+            };
+
+            let text = self.buf.substr(target);
+            let text_len = text.chars().count();
+            let (new_text, prefix_len, inner_len) = if commented_out {
+                let inner = &text[start_keyword.len()..text.len() - end_keyword.len()];
+                let without_prefix = inner.strip_prefix(' ').unwrap_or(inner);
+                let prefix_len =
+                    start_keyword.chars().count() + (inner.len() - without_prefix.len());
+                let inner = without_prefix.strip_suffix(' ').unwrap_or(without_prefix);
+                (inner.to_owned(), prefix_len, inner.chars().count())
+            } else {
+                (
+                    format!("{} {} {}", start_keyword, text, end_keyword),
+                    start_keyword.chars().count() + 1,
+                    text_len,
+                )
+            };
+            let new_len = new_text.chars().count();
+
+            // Map a position in the original text to the edited one.
+            let target_start = self.buf.pos_to_char_index(target.front());
+            let offsets = [c.selection().start, c.selection().end].map(|pos| {
+                if pos < target.front() {
+                    return Err(pos);
+                }
+
+                let offset = self.buf.pos_to_char_index(pos) - target_start;
+                Ok(if offset > text_len {
+                    offset - text_len + new_len
+                } else if commented_out {
+                    min(offset.saturating_sub(prefix_len), inner_len)
+                } else {
+                    offset + prefix_len
+                })
+            });
+
+            c.select_range(target);
+            self.buf.edit_at_cursor(c, past_cursors, &new_text);
+
+            let [start, end] = offsets.map(|offset| match offset {
+                Ok(offset) => self.buf.char_index_to_pos(target_start + offset),
+                Err(pos) => pos,
+            });
+            c.select_range(Range::from_positions(start, end));
+        });
+    }
+
+    /// Returns the range of the block comment which is `range` itself or
+    /// surrounds `range` on the same lines.
+    fn enclosing_block_comment(
+        &self,
+        range: Range,
+        start_keyword: &str,
+        end_keyword: &str,
+    ) -> Option<Range> {
+        // The first end keyword after the opening one must be the last one:
+        // `/* a */ b /* c */` is not a single comment.
+        let text = self.buf.substr(range);
+        if let Some(body) = text.strip_prefix(start_keyword) {
+            if body.find(end_keyword) == Some(body.len().saturating_sub(end_keyword.len())) {
+                return Some(range);
+            }
+        }
+
+        if text.contains(start_keyword) || text.contains(end_keyword) {
+            return None;
+        }
+
+        let front = range.front();
+        let back = range.back();
+        let before = self.buf.substr(Range::new(front.y, 0, front.y, front.x));
+        let after = self.buf.substr(Range::from_positions(
+            back,
+            Position::new(back.y, self.buf.line_len(back.y)),
+        ));
+
+        let rest = before
+            .strip_suffix(' ')
+            .unwrap_or(&before)
+            .strip_suffix(start_keyword)?;
+        let before_len = before.chars().count() - rest.chars().count();
+        let rest = after
+            .strip_prefix(' ')
+            .unwrap_or(&after)
+            .strip_prefix(end_keyword)?;
+        let after_len = after.chars().count() - rest.chars().count();
+
+        Some(Range::new(
+            front.y,
+            front.x - before_len,
+            back.y,
+            back.x + after_len,
+        ))
+    }
 }
 
 #[cfg(test)]
@@ -155,4 +306,50 @@ mod tests {
         buffer.toggle_line_comment_out();
         assert_eq!(buffer.text(), "  abc\n  def");
     }
+
+    #[test]
+    fn test_toggle_block_comment() {
+        let lang = get_language_by_name("rust").unwrap();
+
+        let mut buffer = Buffer::from_text("foo(abc);");
+        buffer.set_language(lang).unwrap();
+        buffer.set_cursors_for_test(&[Cursor::new_selection(0, 4, 0, 7)]);
+        buffer.toggle_block_comment();
+        assert_eq!(buffer.text(), "foo(/* abc */);");
+        assert_eq!(buffer.cursors(), &[Cursor::new_selection(0, 7, 0, 10)]);
+        buffer.toggle_block_comment();
+        assert_eq!(buffer.text(), "foo(abc);");
+        assert_eq!(buffer.cursors(), &[Cursor::new_selection(0, 4, 0, 7)]);
+
+        let mut buffer = Buffer::from_text("/* a */ b /* c */");
+        buffer.set_language(lang).unwrap();
+        buffer.set_cursors_for_test(&[Cursor::new_selection(0, 0, 0, 17)]);
+        buffer.toggle_block_comment();
+        assert_eq!(buffer.text(), "/* /* a */ b /* c */ */");
+
+        let mut buffer = Buffer::from_text("  abc\n  def");
+        buffer.set_language(lang).unwrap();
+        buffer.set_cursors_for_test(&[Cursor::new(0, 3), Cursor::new(1, 2)]);
+        buffer.toggle_block_comment();
+        assert_eq!(buffer.text(), "  /* abc */\n  /* def */");
+        assert_eq!(buffer.cursors(), &[Cursor::new(0, 6), Cursor::new(1, 5)]);
+        buffer.toggle_block_comment();
+        assert_eq!(buffer.text(), "  abc\n  def");
+        assert_eq!(buffer.cursors(), &[Cursor::new(0, 3), Cursor::new(1, 2)]);
+    }
+
+    #[test]
+    fn test_comment_out_with_block_comment() {
+        let lang = get_language_by_name("css").unwrap();
+
+        let mut buffer = Buffer::from_text("a {\n  color: red;\n}");
+        buffer.set_language(lang).unwrap();
+        buffer.set_cursors_for_test(&[Cursor::new_selection(0, 0, 2, 1)]);
+        buffer.toggle_line_comment_out();
+        assert_eq!(buffer.text(), "/* a {\n  color: red;\n} */");
+        assert_eq!(buffer.cursors(), &[Cursor::new_selection(0, 3, 2, 1)]);
+        buffer.toggle_line_comment_out();
+        assert_eq!(buffer.text(), "a {\n  color: red;\n}");
+        assert_eq!(buffer.cursors(), &[Cursor::new_selection(0, 0, 2, 1)]);
+    }
 }
@@ -4,14 +4,86 @@ use crate::{buffer::Buffer, cursor::Range, syntax::TsNodeExt};
 
 impl Buffer {
     pub fn expand_selections(&mut self) {
+        let mut history = std::mem::take(&mut self.expansion_history);
         self.update_cursors_with(|c, buf| {
             if let Some(syntax) = buf.syntax() {
                 let root = syntax.tree().root_node();
-                let new_selection = walk_ts_node(root, &mut root.walk(), c.selection());
+                let prev_selection = c.selection();
+                let new_selection = walk_ts_node(root, &mut root.walk(), prev_selection);
                 c.select_range(new_selection);
+
+                // Forget the history if the cursor has been moved since the
+                // last expansion.
+                let entries = history.entry(c.id()).or_default();
+                if !matches!(entries.last(), Some((_, after)) if *after == prev_selection) {
+                    entries.clear();
+                }
+
+                if new_selection != prev_selection {
+                    entries.push((prev_selection, new_selection));
+                }
+            }
+        });
+
+        history.retain(|id, entries| {
+            !entries.is_empty() && self.cursors.get_cursor_by_id(*id).is_some()
+        });
+        self.expansion_history = history;
+    }
+
+    /// Restores each selection to the one before the last
+    /// `expand_selections`. If the cursor has been moved since then, it
+    /// selects the first named child node in the selection instead.
+    pub fn shrink_selections(&mut self) {
+        let mut history = std::mem::take(&mut self.expansion_history);
+        self.update_cursors_with(|c, buf| {
+            let selection = c.selection();
+            let entries = history.entry(c.id()).or_default();
+            match entries.pop() {
+                Some((before, after)) if after == selection => {
+                    c.select_range(before);
+                }
+                _ => {
+                    entries.clear();
+                    if let Some(syntax) = buf.syntax() {
+                        let root = syntax.tree().root_node();
+                        if let Some(child) = first_named_child(root, selection) {
+                            c.select_range(child);
+                        }
+                    }
+                }
             }
         });
+
+        history.retain(|id, entries| {
+            !entries.is_empty() && self.cursors.get_cursor_by_id(*id).is_some()
+        });
+        self.expansion_history = history;
+    }
+}
+
+/// Returns the range of the first named child of the smallest node
+/// containing `selection`.
+fn first_named_child(root: tree_sitter::Node<'_>, selection: Range) -> Option<Range> {
+    let mut node = root;
+    'outer: loop {
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            if child.buffer_range().contains_range(selection) {
+                node = child;
+                continue 'outer;
+            }
+        }
+
+        break;
     }
+
+    let mut cursor = node.walk();
+    let child = node
+        .named_children(&mut cursor)
+        .map(|child| child.buffer_range())
+        .find(|range| selection.contains_range(*range) && *range != selection);
+    child
 }
 
 fn walk_ts_node<'tree>(
@@ -34,10 +106,9 @@ fn walk_ts_node<'tree>(
 mod tests {
     use std::borrow::Cow;
 
-    use crate::{cursor::Cursor, syntax::SyntaxParser};
+    use crate::{cursor::Cursor, syntax::parsed_buffer};
 
     use super::*;
-    use noa_languages::get_language_by_name;
     use pretty_assertions::assert_eq;
 
     fn selected_str(buf: &Buffer) -> Cow<'_, str> {
@@ -46,12 +117,7 @@ mod tests {
 
     #[test]
     fn expand_selections() {
-        let mut b = Buffer::from_text("");
-        let lang = get_language_by_name("rust").unwrap();
-        b.set_language(lang).unwrap();
-        let mut parser = SyntaxParser::new(lang).unwrap();
-        parser.parse_fully(b.raw_buffer());
-        b.set_syntax_tree(parser.tree().clone());
+        let mut b = parsed_buffer("rust", "");
         b.set_cursors_for_test(&[Cursor::new(0, 0)]);
         b.expand_selections();
         assert_eq!(selected_str(&b), "");
@@ -73,18 +139,16 @@ mod tests {
         //                 integer_literal [2, 18] - [2, 21]
         //                 integer_literal [2, 24] - [2, 25]
         //                 integer_literal [2, 27] - [2, 30]
-        let mut b = Buffer::from_text(concat!(
-            "pub fn main() {\n",
-            "    if true {\n",
-            "        dbg!(vec![123 + 0, 456]);\n",
-            "    }\n",
-            "}\n",
-        ));
-        let lang = get_language_by_name("rust").unwrap();
-        b.set_language(lang).unwrap();
-        let mut parser = SyntaxParser::new(lang).unwrap();
-        parser.parse_fully(b.raw_buffer());
-        b.set_syntax_tree(parser.tree().clone());
+        let mut b = parsed_buffer(
+            "rust",
+            concat!(
+                "pub fn main() {\n",
+                "    if true {\n",
+                "        dbg!(vec![123 + 0, 456]);\n",
+                "    }\n",
+                "}\n",
+            ),
+        );
 
         // The cursor is located in "123".
         b.set_cursors_for_test(&[Cursor::new(2, 21)]);
@@ -125,4 +189,34 @@ mod tests {
             "pub fn main() {\n    if true {\n        dbg!(vec![123 + 0, 456]);\n    }\n}\n"
         );
     }
+
+    #[test]
+    fn shrink_selections() {
+        let mut b = parsed_buffer("rust", "fn main() {\n    foo(123, bar);\n}\n");
+        b.set_cursors_for_test(&[Cursor::new(1, 9)]);
+        b.expand_selections();
+        b.expand_selections();
+        b.expand_selections();
+        assert_eq!(selected_str(&b), "foo(123, bar)");
+
+        b.shrink_selections();
+        assert_eq!(selected_str(&b), "(123, bar)");
+        b.shrink_selections();
+        assert_eq!(selected_str(&b), "123");
+        b.shrink_selections();
+        assert_eq!(b.cursors(), &[Cursor::new(1, 9)]);
+
+        // No history: select the first named child node.
+        b.set_cursors_for_test(&[Cursor::new_selection(1, 4, 1, 17)]);
+        b.shrink_selections();
+        assert_eq!(selected_str(&b), "foo");
+
+        // The history is reset by other movements.
+        b.set_cursors_for_test(&[Cursor::new(1, 9)]);
+        b.expand_selections();
+        b.expand_selections();
+        b.set_cursors_for_test(&[Cursor::new_selection(1, 8, 1, 17)]);
+        b.shrink_selections();
+        assert_eq!(selected_str(&b), "123");
+    }
 }
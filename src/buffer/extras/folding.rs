@@ -0,0 +1,409 @@
+use std::cmp::Reverse;
+
+use crate::{buffer::Buffer, cursor::Position, mut_raw_buffer::Change};
+
+/// A region of lines which can be folded. The line `start` is kept visible
+/// and the lines `start + 1..=end` are hidden when it's folded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FoldRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl FoldRange {
+    pub fn new(start: usize, end: usize) -> FoldRange {
+        FoldRange { start, end }
+    }
+
+    /// Returns true if the line is hidden when folded.
+    pub fn hides(&self, y: usize) -> bool {
+        self.start < y && y <= self.end
+    }
+}
+
+/// The folded regions with an index of hidden lines for lookups from the
+/// layout, which is done for every visible line.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Folds {
+    folds: Vec<FoldRange>,
+    /// The sorted start lines of `folds`.
+    starts: Vec<usize>,
+    /// The sorted, non-overlapping ranges of hidden lines (inclusive).
+    hidden: Vec<(usize, usize)>,
+}
+
+impl Folds {
+    pub fn as_slice(&self) -> &[FoldRange] {
+        &self.folds
+    }
+
+    pub fn set(&mut self, folds: Vec<FoldRange>) {
+        self.starts = folds.iter().map(|fold| fold.start).collect();
+        self.starts.sort_unstable();
+
+        let mut hidden: Vec<(usize, usize)> = folds
+            .iter()
+            .filter(|fold| fold.start < fold.end)
+            .map(|fold| (fold.start + 1, fold.end))
+            .collect();
+        hidden.sort_unstable();
+        self.hidden.clear();
+        for (first, last) in hidden {
+            match self.hidden.last_mut() {
+                Some(prev) if first <= prev.1 + 1 => prev.1 = prev.1.max(last),
+                _ => self.hidden.push((first, last)),
+            }
+        }
+
+        self.folds = folds;
+    }
+
+    pub fn starts_at(&self, y: usize) -> bool {
+        self.starts.binary_search(&y).is_ok()
+    }
+
+    pub fn hides(&self, y: usize) -> bool {
+        let i = self.hidden.partition_point(|(_, last)| *last < y);
+        matches!(self.hidden.get(i), Some((first, _)) if *first <= y)
+    }
+}
+
+impl Buffer {
+    /// Returns the foldable regions from `@fold` nodes in the syntax tree, or
+    /// from indentation if the language does not support it. They're sorted
+    /// by their start lines, outer ones first.
+    pub fn fold_ranges(&self) -> Vec<FoldRange> {
+        let mut ranges = Vec::new();
+        let queried = match self.syntax() {
+            Some(syntax) => syntax.query_folds(self.raw_buffer(), |range| {
+                // Ignore the line where the node ends at its beginning.
+                let end = if range.back().x == 0 {
+                    range.back().y.saturating_sub(1)
+                } else {
+                    range.back().y
+                };
+
+                if range.front().y < end {
+                    ranges.push(FoldRange::new(range.front().y, end));
+                }
+            }),
+            None => false,
+        };
+
+        if !queried {
+            ranges = self.indent_fold_ranges();
+        }
+
+        ranges.sort_by_key(|range| (range.start, Reverse(range.end)));
+        ranges.dedup();
+        ranges
+    }
+
+    /// Computes foldable regions from indentation: a line followed by more
+    /// indented lines. Blank lines are ignored.
+    fn indent_fold_ranges(&self) -> Vec<FoldRange> {
+        let mut ranges = Vec::new();
+        // The start lines of regions and their indentation.
+        let mut stack: Vec<(usize, usize)> = Vec::new();
+        let mut last_non_blank_y = 0;
+        for y in 0..self.num_lines() {
+            let indent_len = self.buf.line_indent_len(y);
+            if indent_len == self.buf.line_len(y) {
+                continue;
+            }
+
+            while let Some((start, start_indent_len)) = stack.last().copied() {
+                if indent_len > start_indent_len {
+                    break;
+                }
+
+                stack.pop();
+                if last_non_blank_y > start {
+                    ranges.push(FoldRange::new(start, last_non_blank_y));
+                }
+            }
+
+            stack.push((y, indent_len));
+            last_non_blank_y = y;
+        }
+
+        for (start, _) in stack {
+            if last_non_blank_y > start {
+                ranges.push(FoldRange::new(start, last_non_blank_y));
+            }
+        }
+
+        ranges
+    }
+
+    /// Returns the folded regions.
+    pub fn folds(&self) -> &[FoldRange] {
+        self.folds.as_slice()
+    }
+
+    /// Returns true if the line is the visible first line of a folded region.
+    pub fn is_folded(&self, y: usize) -> bool {
+        self.folds.starts_at(y) && !self.is_line_hidden(y)
+    }
+
+    pub fn is_line_hidden(&self, y: usize) -> bool {
+        self.folds.hides(y)
+    }
+
+    /// Returns the outermost folded region which hides the line.
+    pub fn hidden_fold_at(&self, y: usize) -> Option<FoldRange> {
+        if !self.is_line_hidden(y) {
+            return None;
+        }
+
+        self.folds()
+            .iter()
+            .filter(|fold| fold.hides(y))
+            .min_by_key(|fold| (fold.start, Reverse(fold.end)))
+            .copied()
+    }
+
+    /// Folds the innermost region containing each cursor which is not folded
+    /// yet.
+    pub fn fold(&mut self) {
+        let ranges = self.fold_ranges();
+        let mut new_folds = Vec::new();
+        for c in self.cursors() {
+            let y = c.moving_position().y;
+            let innermost = ranges
+                .iter()
+                .filter(|range| range.start <= y && y <= range.end && !self.folds().contains(range))
+                .max_by_key(|range| (range.start, Reverse(range.end)));
+            if let Some(range) = innermost {
+                if !new_folds.contains(range) {
+                    new_folds.push(*range);
+                }
+            }
+        }
+
+        let mut folds = self.folds().to_vec();
+        folds.extend(new_folds);
+        self.folds.set(folds);
+        self.move_cursors_out_of_folds();
+    }
+
+    /// Unfolds the regions starting at the cursor lines.
+    pub fn unfold(&mut self) {
+        let ys: Vec<usize> = self
+            .cursors()
+            .iter()
+            .map(|c| c.moving_position().y)
+            .collect();
+        let mut folds = self.folds().to_vec();
+        folds.retain(|fold| !ys.contains(&fold.start));
+        self.folds.set(folds);
+    }
+
+    pub fn fold_all(&mut self) {
+        self.folds.set(self.fold_ranges());
+        self.move_cursors_out_of_folds();
+    }
+
+    pub fn unfold_all(&mut self) {
+        self.folds.set(Vec::new());
+    }
+
+    /// Unfolds the regions hiding cursors, e.g. after jumping to a line.
+    pub fn unfold_at_cursors(&mut self) {
+        let ys: Vec<usize> = self
+            .cursors()
+            .iter()
+            .map(|c| c.moving_position().y)
+            .filter(|y| self.is_line_hidden(*y))
+            .collect();
+        if ys.is_empty() {
+            return;
+        }
+
+        let mut folds = self.folds().to_vec();
+        folds.retain(|fold| !ys.iter().any(|y| fold.hides(*y)));
+        self.folds.set(folds);
+    }
+
+    /// Moves the cursors in hidden lines to the end of the visible first line
+    /// of the folded region.
+    fn move_cursors_out_of_folds(&mut self) {
+        self.update_cursors_with(|c, buffer| {
+            if let Some(fold) = buffer.hidden_fold_at(c.moving_position().y) {
+                c.move_to_pos(Position::new(fold.start, buffer.line_len(fold.start)));
+            }
+        });
+    }
+
+    /// Updates the folded regions after the edits: shifts ones after the
+    /// edits and unfolds ones modified.
+    pub(crate) fn update_folds(&mut self, changes: &[Change]) {
+        if self.folds().is_empty() {
+            return;
+        }
+
+        let mut folds = self.folds().to_vec();
+        for change in changes {
+            let front_y = change.range.front().y;
+            let back_y = change.range.back().y;
+            let new_y = change.new_pos.y;
+            folds = folds
+                .iter()
+                .filter_map(|fold| {
+                    if back_y < fold.start {
+                        let shift = |y: usize| {
+                            if new_y >= back_y {
+                                y + (new_y - back_y)
+                            } else {
+                                y - (back_y - new_y)
+                            }
+                        };
+                        Some(FoldRange::new(shift(fold.start), shift(fold.end)))
+                    } else if front_y > fold.end
+                        || (front_y == fold.start && back_y == fold.start && new_y == fold.start)
+                    {
+                        Some(*fold)
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+        }
+
+        self.folds.set(folds);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::{
+        cursor::{Cursor, Range},
+        syntax::parsed_buffer,
+    };
+
+    use super::*;
+
+    #[test]
+    fn fold_ranges_by_syntax() {
+        let b = parsed_buffer(
+            "rust",
+            concat!(
+                "impl A {\n",
+                "    fn a() {\n",
+                "        1\n",
+                "    }\n",
+                "\n",
+                "    fn b() {}\n",
+                "}\n",
+            ),
+        );
+        assert_eq!(
+            b.fold_ranges(),
+            vec![FoldRange::new(0, 6), FoldRange::new(1, 3)]
+        );
+    }
+
+    #[test]
+    fn fold_ranges_by_indent() {
+        let b = Buffer::from_text("a\n  b\n    c\n\n  d\ne\n  f\n\n");
+        assert_eq!(
+            b.fold_ranges(),
+            vec![
+                FoldRange::new(0, 4),
+                FoldRange::new(1, 2),
+                FoldRange::new(5, 6)
+            ]
+        );
+    }
+
+    #[test]
+    fn fold_and_unfold() {
+        let mut b = Buffer::from_text("a\n  b\n    c\n  d\ne");
+        b.set_cursors_for_test(&[Cursor::new(2, 2)]);
+        b.fold();
+        assert_eq!(b.folds(), &[FoldRange::new(1, 2)]);
+        assert_eq!(b.cursors(), &[Cursor::new(1, 3)]);
+
+        // Fold the outer one.
+        b.fold();
+        assert_eq!(b.folds(), &[FoldRange::new(1, 2), FoldRange::new(0, 3)]);
+        assert_eq!(b.cursors(), &[Cursor::new(0, 1)]);
+        assert!(b.is_folded(0));
+        assert!(!b.is_folded(1));
+        assert!(b.is_line_hidden(3));
+        assert!(!b.is_line_hidden(4));
+        assert_eq!(b.hidden_fold_at(2), Some(FoldRange::new(0, 3)));
+
+        b.unfold();
+        assert_eq!(b.folds(), &[FoldRange::new(1, 2)]);
+
+        b.unfold_all();
+        assert!(b.folds().is_empty());
+        b.fold_all();
+        assert_eq!(b.folds(), &[FoldRange::new(0, 3), FoldRange::new(1, 2)]);
+    }
+
+    #[test]
+    fn hidden_lines() {
+        let mut folds = Folds::default();
+        folds.set(vec![
+            FoldRange::new(10, 12),
+            FoldRange::new(0, 3),
+            FoldRange::new(1, 2),
+            FoldRange::new(3, 5),
+        ]);
+        let hidden: Vec<usize> = (0..15).filter(|y| folds.hides(*y)).collect();
+        assert_eq!(hidden, vec![1, 2, 3, 4, 5, 11, 12]);
+        assert!(folds.starts_at(3));
+        assert!(!folds.starts_at(4));
+    }
+
+    #[test]
+    fn unfold_on_jump() {
+        let mut b = Buffer::from_text("a\n  b\n  c\nd");
+        b.set_cursors_for_test(&[Cursor::new(0, 0)]);
+        b.fold();
+        assert_eq!(b.folds(), &[FoldRange::new(0, 2)]);
+
+        b.move_main_cursor_to_pos(Position::new(3, 0));
+        assert_eq!(b.folds(), &[FoldRange::new(0, 2)]);
+        b.move_main_cursor_to_pos(Position::new(2, 0));
+        assert!(b.folds().is_empty());
+    }
+
+    #[test]
+    fn update_folds() {
+        let mut b = Buffer::from_text("x\na\n  b\n  c\nd\n  e");
+        b.set_cursors_for_test(&[Cursor::new(1, 0), Cursor::new(4, 0)]);
+        b.fold();
+        assert_eq!(b.folds(), &[FoldRange::new(1, 3), FoldRange::new(4, 5)]);
+
+        // Shift the folds.
+        b.set_cursors_for_test(&[Cursor::new(0, 1)]);
+        b.insert_char('\n');
+        b.clear_recorded_changes();
+        assert_eq!(b.folds(), &[FoldRange::new(2, 4), FoldRange::new(5, 6)]);
+
+        // Edits in the first line keep the fold.
+        b.set_cursors_for_test(&[Cursor::new(2, 1)]);
+        b.insert_char('!');
+        b.clear_recorded_changes();
+        assert_eq!(b.folds(), &[FoldRange::new(2, 4), FoldRange::new(5, 6)]);
+
+        // Edits in the hidden lines unfold it.
+        b.set_cursors_for_test(&[Cursor::new_selection(3, 0, 4, 0)]);
+        b.backspace();
+        b.clear_recorded_changes();
+        assert_eq!(b.folds(), &[FoldRange::new(4, 5)]);
+
+        b.set_cursors_for_test(&[Cursor::new(0, 0)]);
+        b.unfold_at_cursors();
+        assert_eq!(b.folds(), &[FoldRange::new(4, 5)]);
+        b.set_cursors_for_test(&[Cursor::new(5, 0)]);
+        b.unfold_at_cursors();
+        assert!(b.folds().is_empty());
+        assert_eq!(b.substr(Range::new(0, 0, 1, 0)), "x\n");
+    }
+}
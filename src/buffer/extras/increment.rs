@@ -0,0 +1,293 @@
+use crate::{
+    buffer::{Buffer, RangeEdit},
+    cursor::{Position, Range},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum NumberKind {
+    /// A decimal number with an optional sign and fraction part, e.g. `-1.5`.
+    Decimal,
+    /// `0x1f`
+    Hex,
+    /// `0b101`
+    Binary,
+}
+
+fn is_word_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+/// Returns the range of the number (in chars) at or after `x`.
+fn find_number(line: &str, x: usize) -> Option<(std::ops::Range<usize>, NumberKind)> {
+    let chars: Vec<char> = line.chars().collect();
+    let count_while = |start: usize, f: fn(&char) -> bool| {
+        chars[start.min(chars.len())..]
+            .iter()
+            .take_while(|ch| f(ch))
+            .count()
+    };
+
+    let mut i = 0;
+    while i < chars.len() {
+        if !chars[i].is_ascii_digit() {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        let prefix = chars.get(i + 1).map(|ch| ch.to_ascii_lowercase());
+        let num_hex_digits = count_while(i + 2, char::is_ascii_hexdigit);
+        let num_bin_digits = count_while(i + 2, |ch| *ch == '0' || *ch == '1');
+        let (range, kind) = if chars[i] == '0' && prefix == Some('x') && num_hex_digits > 0 {
+            (start..i + 2 + num_hex_digits, NumberKind::Hex)
+        } else if chars[i] == '0' && prefix == Some('b') && num_bin_digits > 0 {
+            (start..i + 2 + num_bin_digits, NumberKind::Binary)
+        } else {
+            let mut end = start + count_while(start, char::is_ascii_digit);
+            if chars.get(end) == Some(&'.') {
+                let num_frac_digits = count_while(end + 1, char::is_ascii_digit);
+                if num_frac_digits > 0 {
+                    end += 1 + num_frac_digits;
+                }
+            }
+
+            // `-` in `foo-1` is not a sign.
+            let negative = start > 0
+                && chars[start - 1] == '-'
+                && (start < 2 || !is_word_char(chars[start - 2]));
+            if negative {
+                (start - 1..end, NumberKind::Decimal)
+            } else {
+                (start..end, NumberKind::Decimal)
+            }
+        };
+
+        if range.end > x {
+            return Some((range, kind));
+        }
+
+        i = range.end;
+    }
+
+    None
+}
+
+/// Adds `delta` to the number keeping its format, i.e. the letter case of hex
+/// digits, leading zeros, and the number of fraction digits.
+fn add_to_number(text: &str, kind: NumberKind, delta: i64) -> Option<String> {
+    match kind {
+        NumberKind::Hex | NumberKind::Binary => {
+            let (prefix, digits) = text.split_at(2);
+            let radix = if kind == NumberKind::Hex { 16 } else { 2 };
+            // Leave the number unchanged on overflow and underflow.
+            let value = i128::from(u64::from_str_radix(digits, radix).ok()?) + i128::from(delta);
+            let value = u64::try_from(value).ok()?;
+            let width = digits.len();
+            let new_digits = match kind {
+                NumberKind::Hex if digits.chars().any(|ch| ch.is_ascii_uppercase()) => {
+                    format!("{:0width$X}", value, width = width)
+                }
+                NumberKind::Hex => format!("{:0width$x}", value, width = width),
+                _ => format!("{:0width$b}", value, width = width),
+            };
+            Some(format!("{}{}", prefix, new_digits))
+        }
+        NumberKind::Decimal => {
+            let (negative, abs) = match text.strip_prefix('-') {
+                Some(abs) => (true, abs),
+                None => (false, text),
+            };
+            let (int_part, frac_part) = abs.split_once('.').unwrap_or((abs, ""));
+
+            let scale = 10i128.checked_pow(frac_part.len() as u32)?;
+            let mut value = format!("{}{}", int_part, frac_part).parse::<i128>().ok()?;
+            if negative {
+                value = -value;
+            }
+            let value = value.checked_add((delta as i128).checked_mul(scale)?)?;
+
+            let int_width = if int_part.len() > 1 && int_part.starts_with('0') {
+                int_part.len()
+            } else {
+                0
+            };
+
+            let mut new_text = String::new();
+            if value < 0 {
+                new_text.push('-');
+            }
+            let abs = value.unsigned_abs();
+            new_text.push_str(&format!(
+                "{:0width$}",
+                abs / scale as u128,
+                width = int_width
+            ));
+            if !frac_part.is_empty() {
+                new_text.push_str(&format!(
+                    ".{:0width$}",
+                    abs % scale as u128,
+                    width = frac_part.len()
+                ));
+            }
+
+            Some(new_text)
+        }
+    }
+}
+
+impl Buffer {
+    /// Adds `delta` to the number at or after each cursor.
+    pub fn increment_numbers(&mut self, delta: i64) {
+        self.add_to_numbers(|_| delta);
+    }
+
+    /// Adds `delta`, `delta * 2`, `delta * 3`, ... to the number at or after
+    /// each cursor from the top.
+    pub fn increment_numbers_in_sequence(&mut self, delta: i64) {
+        self.add_to_numbers(|nth| delta.saturating_mul(nth as i64 + 1));
+    }
+
+    /// Adds `delta_for(nth)` to the number at or after each cursor and moves
+    /// the cursors to the beginning of the numbers. `nth` is the index of the
+    /// number from the top.
+    fn add_to_numbers<F>(&mut self, delta_for: F)
+    where
+        F: Fn(usize) -> i64,
+    {
+        let mut positions: Vec<Position> = self.cursors().iter().map(|c| c.front()).collect();
+        positions.sort();
+
+        let mut edits: Vec<RangeEdit> = Vec::new();
+        // The cursor positions and the beginning of their numbers.
+        let mut targets: Vec<(Position, Position)> = Vec::new();
+        for pos in positions {
+            let (xs, kind) = match find_number(&self.line_text(pos.y), pos.x) {
+                Some(number) => number,
+                None => continue,
+            };
+
+            // Cursors on the same number.
+            let range = Range::new(pos.y, xs.start, pos.y, xs.end);
+            if edits.iter().any(|edit| edit.range == range) {
+                targets.push((pos, range.front()));
+                continue;
+            }
+
+            let text = self.substr(range);
+            if let Some(new_text) = add_to_number(&text, kind, delta_for(edits.len())) {
+                edits.push(RangeEdit {
+                    range,
+                    new_text,
+                    stick_left: true,
+                });
+                targets.push((pos, range.front()));
+            }
+        }
+
+        let mut new_cursors = self.cursors().to_vec();
+        for c in &mut new_cursors {
+            let pos = c.front();
+            let new_pos = targets
+                .iter()
+                .find(|(cursor_pos, _)| *cursor_pos == pos)
+                .map(|(_, number_pos)| *number_pos)
+                .unwrap_or(pos);
+            c.move_to_pos(new_pos);
+        }
+
+        self.cursors.update_cursors(&new_cursors);
+        self.edit_ranges(edits);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::cursor::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_find_number() {
+        assert_eq!(find_number("abc", 0), None);
+        assert_eq!(find_number("a 123 b", 0), Some((2..5, NumberKind::Decimal)));
+        assert_eq!(find_number("a 123 b", 4), Some((2..5, NumberKind::Decimal)));
+        assert_eq!(find_number("a 123 b", 5), None);
+        assert_eq!(
+            find_number("x = -1.5;", 0),
+            Some((4..8, NumberKind::Decimal))
+        );
+        assert_eq!(find_number("foo-1", 0), Some((4..5, NumberKind::Decimal)));
+        assert_eq!(find_number("0x1F, 0b10", 3), Some((0..4, NumberKind::Hex)));
+        assert_eq!(
+            find_number("0x1F, 0b10", 4),
+            Some((6..10, NumberKind::Binary))
+        );
+        assert_eq!(find_number("1 2", 1), Some((2..3, NumberKind::Decimal)));
+    }
+
+    #[test]
+    fn test_add_to_number() {
+        use NumberKind::*;
+        assert_eq!(add_to_number("9", Decimal, 1), Some("10".to_owned()));
+        assert_eq!(add_to_number("1", Decimal, -2), Some("-1".to_owned()));
+        assert_eq!(add_to_number("-1", Decimal, 3), Some("2".to_owned()));
+        assert_eq!(add_to_number("007", Decimal, 1), Some("008".to_owned()));
+        assert_eq!(add_to_number("099", Decimal, 1), Some("100".to_owned()));
+        assert_eq!(add_to_number("1.50", Decimal, 1), Some("2.50".to_owned()));
+        assert_eq!(add_to_number("-0.5", Decimal, 1), Some("0.5".to_owned()));
+        assert_eq!(add_to_number("0.5", Decimal, -1), Some("-0.5".to_owned()));
+        assert_eq!(add_to_number("0xff", Hex, 1), Some("0x100".to_owned()));
+        assert_eq!(add_to_number("0x0A", Hex, 1), Some("0x0B".to_owned()));
+        assert_eq!(add_to_number("0x0", Hex, -1), None);
+        assert_eq!(add_to_number("0xffffffffffffffff", Hex, 1), None);
+        assert_eq!(add_to_number("0b0", Binary, -1), None);
+        assert_eq!(
+            add_to_number("0b0011", Binary, 1),
+            Some("0b0100".to_owned())
+        );
+    }
+
+    #[test]
+    fn increment_numbers() {
+        let mut b = Buffer::from_text("a = 9;\nb = 0x0f;\nc = 1, 2");
+        b.set_cursors_for_test(&[Cursor::new(0, 0), Cursor::new(1, 0), Cursor::new(2, 4)]);
+        b.increment_numbers(1);
+        assert_eq!(b.text(), "a = 10;\nb = 0x10;\nc = 2, 2");
+        assert_eq!(
+            b.cursors(),
+            &[Cursor::new(0, 4), Cursor::new(1, 4), Cursor::new(2, 4)]
+        );
+
+        b.increment_numbers(-11);
+        assert_eq!(b.text(), "a = -1;\nb = 0x05;\nc = -9, 2");
+        assert_eq!(
+            b.cursors(),
+            &[Cursor::new(0, 4), Cursor::new(1, 4), Cursor::new(2, 4)]
+        );
+
+        // Multiple cursors on a line.
+        let mut b = Buffer::from_text("1 1 x");
+        b.set_cursors_for_test(&[Cursor::new(0, 0), Cursor::new(0, 2), Cursor::new(0, 4)]);
+        b.increment_numbers(10);
+        assert_eq!(b.text(), "11 11 x");
+        assert_eq!(
+            b.cursors(),
+            &[Cursor::new(0, 0), Cursor::new(0, 3), Cursor::new(0, 6)]
+        );
+    }
+
+    #[test]
+    fn increment_numbers_in_sequence() {
+        let mut b = Buffer::from_text("a[0]\na[0]\na[0]\nb");
+        b.set_cursors_for_test(&[
+            Cursor::new(2, 0),
+            Cursor::new(0, 0),
+            Cursor::new(1, 0),
+            Cursor::new(3, 0),
+        ]);
+        b.increment_numbers_in_sequence(1);
+        assert_eq!(b.text(), "a[1]\na[2]\na[3]\nb");
+    }
+}
@@ -2,29 +2,115 @@ use std::{cmp::min, collections::HashMap};
 
 use noa_editorconfig::{EditorConfig, IndentStyle};
 
-use crate::{buffer::Buffer, cursor::Range, raw_buffer::RawBuffer};
-
-fn compute_desired_indent_len(buf: &RawBuffer, config: &EditorConfig, y: usize) -> usize {
-    let current_line = buf.substr(Range::new(y, 0, y, buf.line_len(y)));
-    for prev_y in (0..y).rev() {
-        let prev_line = buf.substr(Range::new(prev_y, 0, prev_y, buf.line_len(prev_y)));
-        if prev_line.trim().is_empty() {
-            continue;
-        }
+use crate::{
+    buffer::Buffer,
+    cursor::{Position, Range},
+    raw_buffer::RawBuffer,
+    syntax::Syntax,
+};
+
+fn apply_indent_level_change(indent_len: usize, change: isize, config: &EditorConfig) -> usize {
+    let size = config.indent_size * change.unsigned_abs();
+    if change < 0 {
+        indent_len.saturating_sub(size)
+    } else {
+        indent_len + size
+    }
+}
 
-        let mut desired_len = buf.line_indent_len(prev_y);
-        if prev_line.trim_end().ends_with('{') {
-            desired_len += config.indent_size;
-        }
+/// Computes the indentation of the line `y` from the previous non-blank line.
+/// It uses the indents query of the language if available, or just looks at
+/// curly braces otherwise.
+fn compute_desired_indent_len(
+    buf: &RawBuffer,
+    config: &EditorConfig,
+    syntax: Option<&Syntax>,
+    y: usize,
+) -> usize {
+    let change = syntax.and_then(|syntax| indent_level_change_from_prev_line(buf, syntax, y));
+    desired_indent_len(buf, config, change, y)
+}
 
-        if current_line.trim_start().starts_with('}') {
-            desired_len = desired_len.saturating_sub(config.indent_size);
-        }
+/// Returns the indent level change of the line `y` from the previous non-blank
+/// line computed from the indents query.
+fn indent_level_change_from_prev_line(buf: &RawBuffer, syntax: &Syntax, y: usize) -> Option<isize> {
+    let prev_y = prev_non_blank_line(buf, y)?;
+    let break_pos = Position::new(prev_y, buf.line_len(prev_y));
+    let first_pos = Position::new(y, buf.line_indent_len(y));
+    syntax.indent_level_change(buf, break_pos, first_pos)
+}
+
+/// Computes the indentation of the line `y` by applying `change` to the
+/// previous non-blank line, or by looking at curly braces if it's `None`.
+fn desired_indent_len(
+    buf: &RawBuffer,
+    config: &EditorConfig,
+    change: Option<isize>,
+    y: usize,
+) -> usize {
+    let prev_y = match prev_non_blank_line(buf, y) {
+        Some(prev_y) => prev_y,
+        None => return 0,
+    };
+
+    let prev_indent_len = buf.line_indent_len(prev_y);
+    if let Some(change) = change {
+        return apply_indent_level_change(prev_indent_len, change, config);
+    }
 
-        return desired_len;
+    let mut desired_len = prev_indent_len;
+    if buf.line_text(prev_y).trim_end().ends_with('{') {
+        desired_len += config.indent_size;
     }
 
-    0
+    if buf.line_text(y).trim_start().starts_with('}') {
+        desired_len = desired_len.saturating_sub(config.indent_size);
+    }
+
+    desired_len
+}
+
+/// Computes the indentation of a new line inserted at `pos` from the indents
+/// query. Returns `None` if it's not available or the text before `pos` is
+/// blank.
+fn compute_indent_len_after_break(
+    buf: &RawBuffer,
+    config: &EditorConfig,
+    syntax: Option<&Syntax>,
+    pos: Position,
+) -> Option<usize> {
+    let indent_len = buf.line_indent_len(pos.y);
+    if pos.x <= indent_len {
+        return None;
+    }
+
+    let num_spaces = buf
+        .char_iter(pos)
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .count();
+    let first_pos = Position::new(pos.y, pos.x + num_spaces);
+    let change = syntax?.indent_level_change(buf, pos, first_pos)?;
+    Some(apply_indent_level_change(indent_len, change, config))
+}
+
+/// Returns the syntax only if its tree is parsed from the current text:
+/// positions in a stale tree may point to wrong nodes.
+fn up_to_date_syntax<'a>(syntax: &'a Option<Syntax>, buf: &RawBuffer) -> Option<&'a Syntax> {
+    syntax.as_ref().filter(|syntax| syntax.is_up_to_date(buf))
+}
+
+/// Returns the non-blank line before `y`.
+fn prev_non_blank_line(buf: &RawBuffer, y: usize) -> Option<usize> {
+    (0..y)
+        .rev()
+        .find(|&prev_y| !buf.line_text(prev_y).trim().is_empty())
+}
+
+/// Returns the indentation of the non-blank line before `y`.
+fn prev_non_blank_line_indent_len(buf: &RawBuffer, y: usize) -> usize {
+    prev_non_blank_line(buf, y)
+        .map(|prev_y| buf.line_indent_len(prev_y))
+        .unwrap_or(0)
 }
 
 impl Buffer {
@@ -32,8 +118,20 @@ impl Buffer {
         if let Some(cursor) = self.cursors.single_selection_cursor() {
             let ys = cursor.selection().overlapped_lines();
             if !ys.is_empty() {
-                for y in ys {
-                    let desired_len = compute_desired_indent_len(&self.buf, &self.config, y);
+                // The syntax tree does not know the indentation we're going to
+                // insert into the previous lines.
+                let syntax = up_to_date_syntax(&self.syntax, &self.buf);
+                let changes: Vec<Option<isize>> = ys
+                    .clone()
+                    .map(|y| {
+                        syntax.and_then(|syntax| {
+                            indent_level_change_from_prev_line(&self.buf, syntax, y)
+                        })
+                    })
+                    .collect();
+
+                for (y, change) in ys.zip(changes) {
+                    let desired_len = desired_indent_len(&self.buf, &self.config, change, y);
                     let current_indent_len = self.buf.line_indent_len(y);
 
                     let indent_size = if desired_len <= current_indent_len {
@@ -59,7 +157,12 @@ impl Buffer {
         for c in &self.cursors {
             let pos = c.front();
 
-            let desired_len = compute_desired_indent_len(&self.buf, &self.config, pos.y);
+            let desired_len = compute_desired_indent_len(
+                &self.buf,
+                &self.config,
+                up_to_date_syntax(&self.syntax, &self.buf),
+                pos.y,
+            );
             let current_indent_len = self.buf.line_indent_len(pos.y);
             let n = if pos.x < desired_len && pos.x == current_indent_len {
                 desired_len - pos.x
@@ -138,11 +241,30 @@ impl Buffer {
     }
 
     pub fn smart_insert_char(&mut self, c: char) {
+        // Look for the enclosing indent nodes before inserting `}` since the
+        // syntax tree does not know about it.
+        let mut enclosing_indent_lines: Vec<Option<Option<usize>>> = if c == '}' {
+            let syntax = up_to_date_syntax(&self.syntax, &self.buf);
+            self.cursors
+                .as_slice()
+                .iter()
+                .map(|c| {
+                    syntax.and_then(|syntax| {
+                        syntax.enclosing_indent_line(&self.buf, c.moving_position())
+                    })
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
         self.insert_char(c);
 
         // Smart dedent.
         if c == '}' {
+            // `foreach` visits cursors from the last one.
             self.cursors.foreach(|c, past_cursors| {
+                let enclosing_indent_line = enclosing_indent_lines.pop().flatten();
                 if c.is_selection() {
                     return;
                 }
@@ -153,8 +275,18 @@ impl Buffer {
                     return;
                 }
 
-                let desired_indent_size =
-                    compute_desired_indent_len(&self.buf, &self.config, c.front().y);
+                let desired_indent_size = match enclosing_indent_line {
+                    Some(Some(y)) => self.buf.line_indent_len(y),
+                    // Not in any indented block. Keep the indentation of the
+                    // previous line.
+                    Some(None) => prev_non_blank_line_indent_len(&self.buf, pos.y),
+                    None => compute_desired_indent_len(
+                        &self.buf,
+                        &self.config,
+                        up_to_date_syntax(&self.syntax, &self.buf),
+                        c.front().y,
+                    ),
+                };
                 c.select(pos.y, 0, pos.y, 0);
                 self.buf.edit_at_cursor(
                     c,
@@ -210,7 +342,19 @@ impl Buffer {
     }
 
     pub fn insert_newline_and_indent(&mut self) {
+        // Compute indentations from the syntax tree before inserting newlines
+        // since it does not know about them.
+        let syntax = up_to_date_syntax(&self.syntax, &self.buf);
+        let mut indent_lens_from_syntax: Vec<Option<usize>> = self
+            .cursors
+            .as_slice()
+            .iter()
+            .map(|c| compute_indent_len_after_break(&self.buf, &self.config, syntax, c.front()))
+            .collect();
+
+        // `foreach` visits cursors from the last one.
         self.cursors.foreach(|c, past_cursors| {
+            let indent_len_from_syntax = indent_lens_from_syntax.pop().flatten();
             if !c.is_selection() {
                 let pos = c.front();
                 let line_text = self.buf.line_text(pos.y);
@@ -222,10 +366,13 @@ impl Buffer {
                 {
                     self.buf.edit_at_cursor(c, past_cursors, "\n");
 
+                    // The indentation of the closing bracket.
+                    let closing_indent_size = indent_len_from_syntax.unwrap_or_else(|| {
+                        compute_desired_indent_len(&self.buf, &self.config, None, c.front().y)
+                    });
+
                     // Add indentation.
-                    let indent_size =
-                        compute_desired_indent_len(&self.buf, &self.config, c.front().y)
-                            + self.config.indent_size;
+                    let indent_size = closing_indent_size + self.config.indent_size;
                     self.buf.edit_at_cursor(
                         c,
                         past_cursors,
@@ -239,14 +386,12 @@ impl Buffer {
                     self.buf.edit_at_cursor(c, past_cursors, "\n");
 
                     // Add indentation.
-                    let indent_size =
-                        compute_desired_indent_len(&self.buf, &self.config, c.front().y);
                     self.buf.edit_at_cursor(
                         c,
                         past_cursors,
                         &match self.config.indent_style {
-                            IndentStyle::Tab => "\t".repeat(indent_size),
-                            IndentStyle::Space => " ".repeat(indent_size),
+                            IndentStyle::Tab => "\t".repeat(closing_indent_size),
+                            IndentStyle::Space => " ".repeat(closing_indent_size),
                         },
                     );
 
@@ -259,7 +404,9 @@ impl Buffer {
             self.buf.edit_at_cursor(c, past_cursors, "\n");
 
             // Add indentation.
-            let indent_size = compute_desired_indent_len(&self.buf, &self.config, c.front().y);
+            let indent_size = indent_len_from_syntax.unwrap_or_else(|| {
+                compute_desired_indent_len(&self.buf, &self.config, None, c.front().y)
+            });
             self.buf.edit_at_cursor(
                 c,
                 past_cursors,
@@ -274,7 +421,7 @@ impl Buffer {
 
 #[cfg(test)]
 mod tests {
-    use crate::cursor::Cursor;
+    use crate::{cursor::Cursor, syntax::parsed_buffer};
     use noa_languages::get_language_by_name;
     use pretty_assertions::assert_eq;
 
@@ -351,7 +498,12 @@ mod tests {
             .set_language(get_language_by_name("rust").unwrap())
             .unwrap();
         assert_eq!(
-            compute_desired_indent_len(buffer.raw_buffer(), buffer.editorconfig(), 1),
+            compute_desired_indent_len(
+                buffer.raw_buffer(),
+                buffer.editorconfig(),
+                buffer.syntax(),
+                1
+            ),
             4
         );
 
@@ -360,7 +512,12 @@ mod tests {
             .set_language(get_language_by_name("rust").unwrap())
             .unwrap();
         assert_eq!(
-            compute_desired_indent_len(buffer.raw_buffer(), buffer.editorconfig(), 2),
+            compute_desired_indent_len(
+                buffer.raw_buffer(),
+                buffer.editorconfig(),
+                buffer.syntax(),
+                2
+            ),
             8
         );
     }
@@ -372,7 +529,12 @@ mod tests {
             .set_language(get_language_by_name("rust").unwrap())
             .unwrap();
         assert_eq!(
-            compute_desired_indent_len(buffer.raw_buffer(), buffer.editorconfig(), 1),
+            compute_desired_indent_len(
+                buffer.raw_buffer(),
+                buffer.editorconfig(),
+                buffer.syntax(),
+                1
+            ),
             4
         );
     }
@@ -423,4 +585,85 @@ mod tests {
         assert_eq!(b.text(), "    if foo {\n    }");
         assert_eq!(b.cursors(), &[Cursor::new(1, 5)]);
     }
+
+    #[test]
+    fn indent_by_syntax() {
+        // The heuristic does not know about parentheses.
+        let b = parsed_buffer("rust", "fn main() {\n    foo(a,\nb)\n}");
+        assert_eq!(
+            compute_desired_indent_len(b.raw_buffer(), b.editorconfig(), b.syntax(), 2),
+            8
+        );
+
+        let mut b = parsed_buffer("rust", "fn main() {\n    foo(a, b)\n}");
+        b.set_cursors_for_test(&[Cursor::new(1, 11)]);
+        b.insert_newline_and_indent();
+        assert_eq!(b.text(), "fn main() {\n    foo(a, \n        b)\n}");
+        assert_eq!(b.cursors(), &[Cursor::new(2, 8)]);
+    }
+
+    #[test]
+    fn indent_selection_by_syntax() {
+        let mut b = parsed_buffer("rust", "fn main() {\n    foo(\nb\n)\n}");
+        b.set_cursors_for_test(&[Cursor::new_selection(2, 0, 3, 1)]);
+        b.indent();
+        assert_eq!(b.text(), "fn main() {\n    foo(\n        b\n    )\n}");
+    }
+
+    #[test]
+    fn insert_newline_and_indent_by_syntax_with_multiple_cursors() {
+        let mut b = parsed_buffer("rust", "fn main() {\n    foo(a, b)\n    bar(c, d)\n}");
+        b.set_cursors_for_test(&[Cursor::new(1, 11), Cursor::new(2, 11)]);
+        b.insert_newline_and_indent();
+        assert_eq!(
+            b.text(),
+            "fn main() {\n    foo(a, \n        b)\n    bar(c, \n        d)\n}"
+        );
+        assert_eq!(b.cursors(), &[Cursor::new(2, 8), Cursor::new(4, 8)]);
+    }
+
+    #[test]
+    fn insert_newline_and_indent_in_braces_by_syntax() {
+        let mut b = parsed_buffer("rust", "fn main() {\n    foo(a, b);\n    bar({});\n}");
+        b.set_cursors_for_test(&[Cursor::new(1, 11), Cursor::new(2, 9)]);
+        b.insert_newline_and_indent();
+        assert_eq!(
+            b.text(),
+            "fn main() {\n    foo(a, \n        b);\n    bar({\n        \n    });\n}"
+        );
+        assert_eq!(b.cursors(), &[Cursor::new(2, 8), Cursor::new(4, 8)]);
+    }
+
+    #[test]
+    fn smart_dedent_by_syntax() {
+        let mut b = parsed_buffer(
+            "rust",
+            "fn main() {\n    if x {\n        foo();\n        \n}",
+        );
+        b.set_cursors_for_test(&[Cursor::new(3, 8)]);
+        b.smart_insert_char('}');
+        assert_eq!(
+            b.text(),
+            "fn main() {\n    if x {\n        foo();\n    }\n}"
+        );
+
+        // Not in any block: keep the indentation of the previous line.
+        let mut b = parsed_buffer("rust", "    foo();\n    ");
+        b.set_cursors_for_test(&[Cursor::new(1, 4)]);
+        b.smart_insert_char('}');
+        assert_eq!(b.text(), "    foo();\n    }");
+    }
+
+    #[test]
+    fn ignore_stale_syntax_tree() {
+        let mut b = parsed_buffer("rust", "fn main() {\n    foo(a, b)\n}");
+        b.set_cursors_for_test(&[Cursor::new(0, 0)]);
+        b.insert("\n");
+        assert!(!b.is_syntax_up_to_date());
+
+        // The stale tree thinks `foo(` is in line 1.
+        b.set_cursors_for_test(&[Cursor::new(2, 11)]);
+        b.insert_newline_and_indent();
+        assert_eq!(b.text(), "\nfn main() {\n    foo(a, \n    b)\n}");
+    }
 }
@@ -0,0 +1,409 @@
+use std::{cmp::Ordering, collections::HashSet, iter::Peekable, str::Chars};
+
+use noa_languages::Language;
+
+use crate::{
+    buffer::{Buffer, RangeEdit},
+    cursor::{Position, Range},
+};
+
+/// Compares strings in the "natural" order, that is, `a2` comes before `a10`.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    fn take_digits(chars: &mut Peekable<Chars>) -> String {
+        let mut digits = String::new();
+        while let Some(ch) = chars.next_if(char::is_ascii_digit) {
+            digits.push(ch);
+        }
+        digits
+    }
+
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x = take_digits(&mut a);
+                let y = take_digits(&mut b);
+                let x_trimmed = x.trim_start_matches('0');
+                let y_trimmed = y.trim_start_matches('0');
+                let ord = x_trimmed
+                    .len()
+                    .cmp(&y_trimmed.len())
+                    .then_with(|| x_trimmed.cmp(y_trimmed))
+                    .then_with(|| x.len().cmp(&y.len()));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+/// Returns the comment leader of a line (e.g. `///` in `/// foo`) if it's a
+/// line comment.
+fn comment_leader<'a>(line: &'a str, line_comment: Option<&str>) -> Option<&'a str> {
+    let prefix = line_comment?;
+    let rest = line.strip_prefix(prefix)?;
+    let last = prefix.chars().last()?;
+    let extra_len: usize = rest
+        .chars()
+        .take_while(|&ch| ch == last || ch == '!')
+        .map(char::len_utf8)
+        .sum();
+    Some(&line[..prefix.len() + extra_len])
+}
+
+/// Returns true if the language is written in prose rather than code.
+fn is_prose(lang: &Language) -> bool {
+    matches!(lang.name, "plain" | "markdown")
+}
+
+/// Returns the text to be inserted between joined lines. In code, no space is
+/// inserted inside brackets and before punctuation (e.g. `foo(a, b)`). In
+/// prose (including comments), words are simply separated by a space.
+fn join_separator(left: &str, right: &str, prose: bool) -> &'static str {
+    match (left.chars().last(), right.chars().next()) {
+        (None, _) | (_, None) => "",
+        _ if prose => " ",
+        (Some('(' | '['), _) | (_, Some(')' | ']' | ',' | ';' | '.')) => "",
+        _ => " ",
+    }
+}
+
+/// Joins lines into a line: removes the indentation of following lines and
+/// their comment leaders if the first line is a line comment.
+fn join_lines(lines: Vec<String>, lang: &Language) -> Vec<String> {
+    let mut lines = lines.iter();
+    let mut joined = match lines.next() {
+        Some(first) => first.trim_end().to_owned(),
+        None => return Vec::new(),
+    };
+
+    let leader = comment_leader(joined.trim_start(), lang.line_comment).map(str::to_owned);
+    let prose = is_prose(lang) || leader.is_some();
+    for line in lines {
+        let mut line = line.trim();
+        if let Some(leader) = &leader {
+            if let Some(rest) = line.strip_prefix(leader.as_str()) {
+                line = rest.trim_start();
+            }
+        }
+
+        joined.push_str(join_separator(&joined, line, prose));
+        joined.push_str(line);
+    }
+
+    vec![joined]
+}
+
+impl Buffer {
+    /// Sorts the lines in the natural order (e.g. `a2` comes before `a10`).
+    pub fn sort_lines_naturally(&mut self) {
+        self.transform_lines(false, |mut lines| {
+            lines.sort_by(|a, b| natural_cmp(a, b));
+            lines
+        });
+    }
+
+    /// Sorts the lines ignoring case.
+    pub fn sort_lines_case_insensitively(&mut self) {
+        self.transform_lines(false, |mut lines| {
+            lines.sort_by_cached_key(|line| line.to_lowercase());
+            lines
+        });
+    }
+
+    /// Removes duplicated lines except the first occurrence.
+    pub fn unique_lines(&mut self) {
+        self.transform_lines(false, |mut lines| {
+            let mut seen = HashSet::new();
+            lines.retain(|line| seen.insert(line.clone()));
+            lines
+        });
+    }
+
+    pub fn reverse_lines(&mut self) {
+        self.transform_lines(false, |mut lines| {
+            lines.reverse();
+            lines
+        });
+    }
+
+    pub fn shuffle_lines(&mut self) {
+        self.transform_lines(false, |mut lines| {
+            fastrand::shuffle(&mut lines);
+            lines
+        });
+    }
+
+    /// Joins the lines covered by each selection into one. A cursor or a
+    /// selection within a line is joined with the next line.
+    pub fn join_lines(&mut self) {
+        let lang = self.language();
+        self.transform_lines(true, |lines| join_lines(lines, lang))
+    }
+
+    /// Returns the ranges of lines covered by the selections, or the whole
+    /// buffer if nothing is selected. When joining, cursors without
+    /// selections are also targeted. Overlapping ranges are merged.
+    fn target_lines(&self, whole_buffer: bool, join: bool) -> Vec<std::ops::Range<usize>> {
+        let mut num_lines = self.num_lines();
+        if num_lines > 1 && self.line_len(num_lines - 1) == 0 {
+            // Ignore the empty line after the trailing newline.
+            num_lines -= 1;
+        }
+
+        if whole_buffer {
+            return std::iter::once(0..num_lines).collect();
+        }
+
+        let mut ranges: Vec<std::ops::Range<usize>> = self
+            .cursors()
+            .iter()
+            .filter(|c| join || c.is_selection())
+            .map(|c| {
+                let front = c.front();
+                let back = c.back();
+                let mut end_y = if back.x == 0 && back.y > front.y {
+                    back.y
+                } else {
+                    back.y + 1
+                };
+
+                if join && end_y - front.y == 1 && end_y < num_lines {
+                    end_y += 1;
+                }
+
+                front.y..end_y
+            })
+            .collect();
+
+        ranges.sort_by_key(|ys| ys.start);
+        let mut merged: Vec<std::ops::Range<usize>> = Vec::new();
+        for ys in ranges {
+            match merged.last_mut() {
+                Some(last) if ys.start < last.end => last.end = last.end.max(ys.end),
+                _ => merged.push(ys),
+            }
+        }
+
+        merged
+    }
+
+    /// Replaces the lines covered by the selections (or the whole buffer if
+    /// nothing is selected and not joining) with the ones returned by `f` as
+    /// a single undo step, and then restores the selections.
+    fn transform_lines<F>(&mut self, join: bool, mut f: F)
+    where
+        F: FnMut(Vec<String>) -> Vec<String>,
+    {
+        let whole_buffer = !join && !self.cursors().iter().any(|c| c.is_selection());
+        let targets = self.target_lines(whole_buffer, join);
+
+        // The original lines and new ones for each target.
+        let mut blocks = Vec::with_capacity(targets.len());
+        let mut modified = false;
+        for ys in targets {
+            let lines: Vec<String> = ys.clone().map(|y| self.line_text(y)).collect();
+            let new_lines = f(lines.clone());
+            modified |= new_lines != lines;
+            blocks.push((ys, new_lines));
+        }
+
+        if !modified {
+            return;
+        }
+
+        self.save_undo();
+
+        let edits = blocks
+            .iter()
+            .map(|(ys, new_lines)| {
+                let last_y = ys.end - 1;
+                RangeEdit {
+                    range: Range::new(ys.start, 0, last_y, self.line_len(last_y)),
+                    new_text: new_lines.join("\n"),
+                    stick_left: true,
+                }
+            })
+            .collect();
+        self.apply_range_edits(edits);
+
+        // The number of lines inserted (or removed if negative) above `y`.
+        let shift = |y: usize| -> isize {
+            blocks
+                .iter()
+                .take_while(|(ys, _)| ys.end <= y)
+                .map(|(ys, new_lines)| new_lines.len() as isize - ys.len() as isize)
+                .sum()
+        };
+
+        let mut new_cursors = self.cursors().to_vec();
+        for c in &mut new_cursors {
+            let selection = c.selection();
+            let block = blocks
+                .iter()
+                .find(|(ys, _)| ys.contains(&selection.front().y));
+            match block {
+                Some((ys, new_lines)) if c.is_selection() => {
+                    let start_y = (ys.start as isize + shift(ys.start)) as usize;
+                    let front = Position::new(start_y, 0);
+                    let back = if selection.back().x == 0 && !join {
+                        Position::new(start_y + new_lines.len(), 0)
+                    } else {
+                        let end_y = start_y + new_lines.len().max(1) - 1;
+                        Position::new(end_y, self.line_len(end_y))
+                    };
+
+                    if selection.front() == selection.start {
+                        c.select_range(Range::from_positions(front, back));
+                    } else {
+                        c.select_range(Range::from_positions(back, front));
+                    }
+                }
+                _ => {
+                    let mut pos = c.moving_position();
+                    pos.y = (pos.y as isize + shift(pos.y)).max(0) as usize;
+                    c.move_to_pos(self.clamp_range(Range::from_single_position(pos)).front());
+                }
+            }
+        }
+
+        self.cursors.update_cursors(&new_cursors);
+        self.save_undo();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use noa_languages::get_language_by_name;
+    use pretty_assertions::assert_eq;
+
+    use crate::cursor::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_natural_cmp() {
+        assert_eq!(natural_cmp("a2", "a10"), Ordering::Less);
+        assert_eq!(natural_cmp("a10", "a2"), Ordering::Greater);
+        assert_eq!(natural_cmp("a02", "a2"), Ordering::Greater);
+        assert_eq!(natural_cmp("a2b", "a2c"), Ordering::Less);
+        assert_eq!(natural_cmp("abc", "abc"), Ordering::Equal);
+        assert_eq!(natural_cmp("ab", "abc"), Ordering::Less);
+    }
+
+    #[test]
+    fn sort_lines() {
+        // The whole buffer.
+        let mut b = Buffer::from_text("x10\nx2\nx1\n");
+        b.set_cursors_for_test(&[Cursor::new(1, 1)]);
+        b.sort_lines_naturally();
+        assert_eq!(b.text(), "x1\nx2\nx10\n");
+        assert_eq!(b.cursors(), &[Cursor::new(1, 1)]);
+
+        // Lines covered by a selection.
+        let mut b = Buffer::from_text("b\nC\na\nB\nz");
+        b.set_cursors_for_test(&[Cursor::new_selection(1, 0, 4, 0)]);
+        b.sort_lines_case_insensitively();
+        assert_eq!(b.text(), "b\na\nB\nC\nz");
+        assert_eq!(b.cursors(), &[Cursor::new_selection(1, 0, 4, 0)]);
+
+        // Multiple selections.
+        let mut b = Buffer::from_text("3\n1\n2\n---\nb\na");
+        b.set_cursors_for_test(&[
+            Cursor::new_selection(0, 0, 2, 1),
+            Cursor::new_selection(5, 1, 4, 0),
+        ]);
+        b.sort_lines_naturally();
+        assert_eq!(b.text(), "1\n2\n3\n---\na\nb");
+        assert_eq!(
+            b.cursors(),
+            &[
+                Cursor::new_selection(0, 0, 2, 1),
+                Cursor::new_selection(5, 1, 4, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn unique_and_reverse_lines() {
+        let mut b = Buffer::from_text("top\na\nb\na\nb\nc\nbottom");
+        b.set_cursors_for_test(&[Cursor::new_selection(1, 0, 5, 1), Cursor::new(6, 3)]);
+        b.unique_lines();
+        assert_eq!(b.text(), "top\na\nb\nc\nbottom");
+        assert_eq!(
+            b.cursors(),
+            &[Cursor::new_selection(1, 0, 3, 1), Cursor::new(4, 3)]
+        );
+
+        b.reverse_lines();
+        assert_eq!(b.text(), "top\nc\nb\na\nbottom");
+        assert_eq!(
+            b.cursors(),
+            &[Cursor::new_selection(1, 0, 3, 1), Cursor::new(4, 3)]
+        );
+
+        // Undo restores the lines at once.
+        b.undo();
+        assert_eq!(b.text(), "top\na\nb\nc\nbottom");
+    }
+
+    #[test]
+    fn shuffle_lines() {
+        let mut b = Buffer::from_text("1\n2\n3\n4\n5\n6\n7\n8\n");
+        b.shuffle_lines();
+        let mut lines: Vec<String> = b.text().lines().map(str::to_owned).collect();
+        lines.sort();
+        assert_eq!(lines, vec!["1", "2", "3", "4", "5", "6", "7", "8"]);
+    }
+
+    #[test]
+    fn test_join_lines() {
+        let mut b = Buffer::from_text("    foo(\n        a,\n        b\n    )\nbar");
+        b.set_language(get_language_by_name("rust").unwrap())
+            .unwrap();
+        b.set_cursors_for_test(&[Cursor::new_selection(0, 0, 3, 5)]);
+        b.join_lines();
+        assert_eq!(b.text(), "    foo(a, b)\nbar");
+        assert_eq!(b.cursors(), &[Cursor::new_selection(0, 0, 0, 13)]);
+
+        // A selection within a line is joined with the next line.
+        let mut b = Buffer::from_text("if x {\n    y();\n}");
+        b.set_cursors_for_test(&[Cursor::new_selection(0, 0, 0, 2)]);
+        b.join_lines();
+        assert_eq!(b.text(), "if x { y();\n}");
+
+        // A cursor is joined with the next line, not the whole buffer.
+        let mut b = Buffer::from_text("a\n  b\nc\n  d\ne");
+        b.set_cursors_for_test(&[Cursor::new(0, 1), Cursor::new(2, 0)]);
+        b.join_lines();
+        assert_eq!(b.text(), "a b\nc d\ne");
+        assert_eq!(b.cursors(), &[Cursor::new(0, 1), Cursor::new(1, 0)]);
+
+        // Comment leaders. Comments are joined as prose.
+        let mut b = Buffer::from_text("  /// foo(\n  /// bar\n  // baz\nx\n");
+        b.set_language(get_language_by_name("rust").unwrap())
+            .unwrap();
+        b.set_cursors_for_test(&[Cursor::new_selection(0, 0, 2, 8)]);
+        b.join_lines();
+        assert_eq!(b.text(), "  /// foo( bar // baz\nx\n");
+
+        // Plain text is joined as prose.
+        let mut b = Buffer::from_text("see (\nthis).");
+        b.set_cursors_for_test(&[Cursor::new(0, 0)]);
+        b.join_lines();
+        assert_eq!(b.text(), "see ( this).");
+    }
+}
@@ -1,9 +1,79 @@
+use noa_languages::tree_sitter;
+
 use crate::{
     buffer::Buffer,
     char_iter::CharIter,
     cursor::{Position, Range},
+    syntax::{Syntax, TsNodeExt},
 };
 
+pub const BRACKETS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}'), ('<', '>')];
+pub const QUOTES: &[char] = &['"', '\'', '`'];
+
+/// The node kinds of string and character literals in the supported
+/// languages.
+const LITERAL_KINDS: &[&str] = &[
+    "string",
+    "string_literal",
+    "raw_string_literal",
+    "interpreted_string_literal",
+    "raw_string",
+    "template_string",
+    "char_literal",
+    "character_literal",
+    "rune_literal",
+];
+
+/// Returns whether the character is an opening bracket and its counterpart.
+pub fn corresponding_bracket(c: char) -> Option<(bool, char)> {
+    match c {
+        '(' => Some((true, ')')),
+        '{' => Some((true, '}')),
+        '[' => Some((true, ']')),
+        '<' => Some((true, '>')),
+        ')' => Some((false, '(')),
+        '}' => Some((false, '{')),
+        ']' => Some((false, '[')),
+        '>' => Some((false, '<')),
+        _ => None,
+    }
+}
+
+/// Returns the ranges of the first and last children if they're a bracket
+/// pair like `(` and `)`.
+fn bracket_children(node: tree_sitter::Node<'_>) -> Option<(Range, Range)> {
+    let first = node.child(0)?;
+    let last = node.child(node.child_count().checked_sub(1)?)?;
+    if first.id() == last.id() {
+        return None;
+    }
+
+    let is_pair = BRACKETS
+        .iter()
+        .any(|(open, close)| first.kind() == open.to_string() && last.kind() == close.to_string());
+    if !is_pair {
+        return None;
+    }
+
+    Some((first.buffer_range(), last.buffer_range()))
+}
+
+/// Returns the ranges of the first and last children if they're anonymous
+/// nodes like `"` (or `b"` and `"`) in a string literal.
+fn literal_delimiter_children(node: tree_sitter::Node<'_>) -> Option<(Range, Range)> {
+    let first = node.child(0)?;
+    let last = node.child(node.child_count().checked_sub(1)?)?;
+    if first.id() == last.id() || first.is_named() || last.is_named() {
+        return None;
+    }
+
+    Some((first.buffer_range(), last.buffer_range()))
+}
+
+fn char_range(pos: Position) -> Range {
+    Range::from_positions(pos, Position::new(pos.y, pos.x + 1))
+}
+
 impl Buffer {
     pub fn matching_bracket(&mut self, pos: Position) -> Option<Range> {
         let (mut char_iter, opening, start_ch, end_ch) = self.find_bracket_nearby(pos)?;
@@ -39,22 +109,10 @@ impl Buffer {
     }
 
     pub fn find_bracket_nearby(&mut self, pos: Position) -> Option<(CharIter, bool, char, char)> {
-        let get_corresponding_char = |c: char| match c {
-            '(' => Some((true, ')')),
-            '{' => Some((true, '}')),
-            '[' => Some((true, ']')),
-            '<' => Some((true, '>')),
-            ')' => Some((false, '(')),
-            '}' => Some((false, '{')),
-            ']' => Some((false, '[')),
-            '>' => Some((false, '<')),
-            _ => None,
-        };
-
         // Try the next character.
         let mut char_iter = self.char_iter(pos);
         if let Some(c) = char_iter.next() {
-            if let Some((opening, correspond_ch)) = get_corresponding_char(c) {
+            if let Some((opening, correspond_ch)) = corresponding_bracket(c) {
                 return Some((char_iter, opening, c, correspond_ch));
             }
         }
@@ -62,18 +120,180 @@ impl Buffer {
         // Try the previous character.
         let mut char_iter = self.char_iter(pos);
         if let Some(c) = char_iter.prev() {
-            if let Some((opening, correspond_ch)) = get_corresponding_char(c) {
+            if let Some((opening, correspond_ch)) = corresponding_bracket(c) {
                 return Some((char_iter, opening, c, correspond_ch));
             }
         }
 
         None
     }
+
+    /// Returns the ranges of the nearest `open` before `range` and `close`
+    /// after it, skipping balanced pairs in between.
+    ///
+    /// If `open` and `close` are the same character (e.g. quotes), they're
+    /// searched only in the lines of `range`.
+    pub fn find_enclosing_pair(
+        &self,
+        range: Range,
+        open: char,
+        close: char,
+    ) -> Option<(Range, Range)> {
+        if open == close {
+            return self.find_enclosing_quotes(range, open);
+        }
+
+        let mut char_iter = self.char_iter(range.front());
+        let mut nested = 0;
+        let open_range = loop {
+            match char_iter.prev()? {
+                ch if ch == close => nested += 1,
+                ch if ch == open && nested == 0 => break char_range(char_iter.last_position()),
+                ch if ch == open => nested -= 1,
+                _ => {}
+            }
+        };
+
+        let mut char_iter = self.char_iter(range.back());
+        let mut nested = 0;
+        let close_range = loop {
+            match char_iter.next()? {
+                ch if ch == open => nested += 1,
+                ch if ch == close && nested == 0 => break char_range(char_iter.last_position()),
+                ch if ch == close => nested -= 1,
+                _ => {}
+            }
+        };
+
+        Some((open_range, close_range))
+    }
+
+    /// Returns the ranges of the innermost bracket or quote pair enclosing
+    /// `range`. Pairs are taken from the syntax tree if available so that
+    /// brackets in strings or escaped quotes don't matter.
+    pub fn find_nearest_enclosing_pair(&self, range: Range) -> Option<(Range, Range)> {
+        if let Some(pair) = self
+            .syntax()
+            .and_then(|syntax| self.find_enclosing_pair_node(syntax, range))
+        {
+            return Some(pair);
+        }
+
+        let brackets = BRACKETS.iter().copied();
+        let quotes = QUOTES.iter().map(|quote| (*quote, *quote));
+        brackets
+            .chain(quotes)
+            .filter_map(|(open, close)| self.find_enclosing_pair(range, open, close))
+            .max_by_key(|(open, _)| open.front())
+    }
+
+    /// Returns the delimiters of the innermost node enclosing `range` which is
+    /// a string or starts and ends with a bracket pair.
+    fn find_enclosing_pair_node(&self, syntax: &Syntax, range: Range) -> Option<(Range, Range)> {
+        let mut innermost = None;
+        let mut node = syntax.tree().root_node();
+        'outer: loop {
+            let mut cursor = node.walk();
+            for child in node.children(&mut cursor) {
+                if !child.buffer_range().contains_range(range) {
+                    continue;
+                }
+
+                let pair = if LITERAL_KINDS.contains(&child.kind()) {
+                    // Literals parsed as a single token (e.g. `r#"..."#` in
+                    // Rust) have no children for the delimiters.
+                    literal_delimiter_children(child)
+                        .or_else(|| self.string_quotes(child.buffer_range()))
+                } else {
+                    bracket_children(child)
+                };
+
+                match pair {
+                    Some((open, close))
+                        if open.back() <= range.front() && range.back() <= close.front() =>
+                    {
+                        innermost = Some((open, close));
+                    }
+                    _ => {}
+                }
+
+                node = child;
+                continue 'outer;
+            }
+
+            break;
+        }
+
+        innermost
+    }
+
+    /// Returns the opening and closing quotes of a string literal including
+    /// prefixes and suffixes like `r#"` and `"#`.
+    fn string_quotes(&self, range: Range) -> Option<(Range, Range)> {
+        let text = self.substr(range);
+        let is_affix = |c: char| c.is_ascii_alphanumeric() || matches!(c, '#' | '@' | '$');
+
+        let prefix_len = text.chars().take_while(|c| is_affix(*c)).count();
+        let quote = text
+            .chars()
+            .nth(prefix_len)
+            .filter(|c| QUOTES.contains(c))?;
+        let suffix_len = text.chars().rev().take_while(|c| *c == '#').count();
+        if text.chars().rev().nth(suffix_len) != Some(quote)
+            || text.chars().count() < prefix_len + suffix_len + 2
+        {
+            return None;
+        }
+
+        let front = range.front();
+        let back = range.back();
+        let open = Range::new(front.y, front.x, front.y, front.x + prefix_len + 1);
+        let close = Range::new(back.y, back.x.checked_sub(suffix_len + 1)?, back.y, back.x);
+        Some((open, close))
+    }
+
+    fn find_enclosing_quotes(&self, range: Range, quote: char) -> Option<(Range, Range)> {
+        let mut char_iter = self.char_iter(range.front());
+        let open_range = loop {
+            match char_iter.prev()? {
+                '\n' => return None,
+                ch if ch == quote && !self.is_escaped(char_iter.last_position()) => {
+                    break char_range(char_iter.last_position());
+                }
+                _ => {}
+            }
+        };
+
+        let mut char_iter = self.char_iter(range.back());
+        let close_range = loop {
+            match char_iter.next()? {
+                '\n' => return None,
+                ch if ch == quote && !self.is_escaped(char_iter.last_position()) => {
+                    break char_range(char_iter.last_position());
+                }
+                _ => {}
+            }
+        };
+
+        Some((open_range, close_range))
+    }
+
+    /// Returns true if the character at `pos` is preceded by a backslash.
+    fn is_escaped(&self, pos: Position) -> bool {
+        let mut char_iter = self.char_iter(pos);
+        let mut num_backslashes = 0;
+        while char_iter.prev() == Some('\\') {
+            num_backslashes += 1;
+        }
+
+        num_backslashes % 2 == 1
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::syntax::parsed_buffer;
     use pretty_assertions::assert_eq;
 
     #[test]
@@ -119,4 +339,75 @@ mod tests {
             Some(Range::new(0, 0, 0, 1))
         );
     }
+
+    #[test]
+    fn find_enclosing_pair() {
+        let b = Buffer::from_text("f(a, (b), [c\n]) \"x \\\" y\"");
+        assert_eq!(
+            b.find_enclosing_pair(Range::new(0, 2, 0, 3), '(', ')'),
+            Some((Range::new(0, 1, 0, 2), Range::new(1, 1, 1, 2)))
+        );
+        assert_eq!(
+            b.find_enclosing_pair(Range::new(0, 6, 0, 6), '(', ')'),
+            Some((Range::new(0, 5, 0, 6), Range::new(0, 7, 0, 8)))
+        );
+        assert_eq!(
+            b.find_enclosing_pair(Range::new(0, 11, 1, 0), '[', ']'),
+            Some((Range::new(0, 10, 0, 11), Range::new(1, 0, 1, 1)))
+        );
+        assert_eq!(
+            b.find_enclosing_pair(Range::new(0, 6, 0, 6), '[', ']'),
+            None
+        );
+
+        // Quotes.
+        assert_eq!(
+            b.find_enclosing_pair(Range::new(1, 5, 1, 5), '"', '"'),
+            Some((Range::new(1, 3, 1, 4), Range::new(1, 10, 1, 11)))
+        );
+        assert_eq!(
+            b.find_enclosing_pair(Range::new(0, 6, 0, 6), '"', '"'),
+            None
+        );
+
+        // The innermost one of any kind.
+        assert_eq!(
+            b.find_nearest_enclosing_pair(Range::new(0, 2, 0, 2)),
+            Some((Range::new(0, 1, 0, 2), Range::new(1, 1, 1, 2)))
+        );
+        assert_eq!(
+            b.find_nearest_enclosing_pair(Range::new(1, 8, 1, 8)),
+            Some((Range::new(1, 3, 1, 4), Range::new(1, 10, 1, 11)))
+        );
+    }
+
+    #[test]
+    fn find_nearest_enclosing_pair_by_syntax() {
+        let b = parsed_buffer("rust", "f(\"(\", 'a', r#\"\"\"#);");
+
+        // Between strings.
+        assert_eq!(
+            b.find_nearest_enclosing_pair(Range::new(0, 6, 0, 6)),
+            Some((Range::new(0, 1, 0, 2), Range::new(0, 18, 0, 19)))
+        );
+        assert_eq!(
+            b.find_nearest_enclosing_pair(Range::new(0, 3, 0, 3)),
+            Some((Range::new(0, 2, 0, 3), Range::new(0, 4, 0, 5)))
+        );
+        assert_eq!(
+            b.find_nearest_enclosing_pair(Range::new(0, 8, 0, 8)),
+            Some((Range::new(0, 7, 0, 8), Range::new(0, 9, 0, 10)))
+        );
+        assert_eq!(
+            b.find_nearest_enclosing_pair(Range::new(0, 15, 0, 16)),
+            Some((Range::new(0, 12, 0, 15), Range::new(0, 16, 0, 18)))
+        );
+
+        // The delimiters of a string literal are taken from its children.
+        let b = parsed_buffer("rust", "g(b\"x\\\"y\");");
+        assert_eq!(
+            b.find_nearest_enclosing_pair(Range::new(0, 4, 0, 4)),
+            Some((Range::new(0, 2, 0, 4), Range::new(0, 8, 0, 9)))
+        );
+    }
 }
@@ -1,9 +1,21 @@
+pub mod align_cursors;
+pub mod block_selection;
 pub mod comment_out;
 pub mod duplicate_lines;
 pub mod edit_words;
 pub mod expand_selections;
+pub mod folding;
+pub mod increment;
 pub mod indent;
+pub mod line_ops;
 pub mod matching_brackets;
 pub mod move_lines;
+pub mod occurrences;
+pub mod replace;
 pub mod select_lines;
+pub mod select_pairs;
+pub mod snippets;
+pub mod surround;
+pub mod swap_siblings;
+pub mod textobjects;
 pub mod truncate;
@@ -0,0 +1,154 @@
+use crate::{
+    buffer::Buffer,
+    cursor::{Position, Range},
+};
+
+impl Buffer {
+    /// Adds a cursor at the next occurrence of the main selection (wrapping
+    /// around at EOF). The new cursor becomes the main cursor so that the
+    /// view follows it.
+    ///
+    /// If the main cursor is not a selection, it selects the current word
+    /// instead.
+    ///
+    /// Returns false if there are no more occurrences.
+    pub fn add_next_occurrence(&mut self) -> bool {
+        if !self.main_cursor().is_selection() {
+            self.select_current_word();
+            return self.main_cursor().is_selection();
+        }
+
+        match self.next_occurrence() {
+            Some(range) => {
+                self.cursors.add_main_cursor(range);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves the main selection to the next occurrence, that is, skips the
+    /// current one.
+    ///
+    /// Returns false if there are no more occurrences.
+    pub fn skip_occurrence(&mut self) -> bool {
+        if !self.main_cursor().is_selection() {
+            self.select_current_word();
+            return self.main_cursor().is_selection();
+        }
+
+        match self.next_occurrence() {
+            Some(range) => {
+                self.cursors.save_undo_state();
+                self.select_main_cursor_range(range);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the cursor added by the last `add_next_occurrence`.
+    ///
+    /// Returns false if there are no such cursors.
+    pub fn undo_last_occurrence(&mut self) -> bool {
+        self.cursors.undo_main_cursor_addition()
+    }
+
+    /// Looks for the next occurrence of the main selection not selected by
+    /// other cursors.
+    fn next_occurrence(&self) -> Option<Range> {
+        let selection = self.main_cursor().selection();
+        let text = self.substr(selection);
+        self.find_iter(&text, selection.back())
+            .chain(
+                self.find_iter(&text, Position::new(0, 0))
+                    .take_while(|range| range.front() < selection.front()),
+            )
+            .find(|range| {
+                !self
+                    .cursors()
+                    .iter()
+                    .any(|c| c.selection().overlaps_with(*range))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::cursor::Cursor;
+
+    use super::*;
+
+    fn selections(b: &Buffer) -> Vec<Range> {
+        b.cursors().iter().map(|c| c.selection()).collect()
+    }
+
+    #[test]
+    fn add_next_occurrence() {
+        let mut b = Buffer::from_text("foo bar foo\nfoo");
+        b.set_cursors_for_test(&[Cursor::new(0, 9)]);
+
+        // Select the current word first.
+        assert!(b.add_next_occurrence());
+        assert_eq!(selections(&b), vec![Range::new(0, 8, 0, 11)]);
+
+        assert!(b.add_next_occurrence());
+        assert_eq!(b.main_cursor().selection(), Range::new(1, 0, 1, 3));
+
+        // Wrap around.
+        assert!(b.add_next_occurrence());
+        assert_eq!(b.main_cursor().selection(), Range::new(0, 0, 0, 3));
+        assert_eq!(
+            selections(&b),
+            vec![
+                Range::new(0, 0, 0, 3),
+                Range::new(0, 8, 0, 11),
+                Range::new(1, 0, 1, 3),
+            ]
+        );
+
+        // All occurrences are already selected.
+        assert!(!b.add_next_occurrence());
+    }
+
+    #[test]
+    fn skip_occurrence() {
+        let mut b = Buffer::from_text("foo foo foo");
+        b.set_cursors_for_test(&[Cursor::new_selection(0, 0, 0, 3)]);
+        assert!(b.skip_occurrence());
+        assert_eq!(selections(&b), vec![Range::new(0, 4, 0, 7)]);
+
+        assert!(b.add_next_occurrence());
+        assert!(b.skip_occurrence());
+        assert_eq!(
+            selections(&b),
+            vec![Range::new(0, 0, 0, 3), Range::new(0, 4, 0, 7)]
+        );
+        assert_eq!(b.main_cursor().selection(), Range::new(0, 0, 0, 3));
+
+        let mut b = Buffer::from_text("foo bar");
+        b.set_cursors_for_test(&[Cursor::new_selection(0, 0, 0, 3)]);
+        assert!(!b.skip_occurrence());
+    }
+
+    #[test]
+    fn undo_last_occurrence() {
+        let mut b = Buffer::from_text("foo foo foo");
+        b.set_cursors_for_test(&[Cursor::new_selection(0, 0, 0, 3)]);
+        b.add_next_occurrence();
+        b.add_next_occurrence();
+
+        assert!(b.undo_last_occurrence());
+        assert_eq!(
+            selections(&b),
+            vec![Range::new(0, 0, 0, 3), Range::new(0, 4, 0, 7)]
+        );
+        assert_eq!(b.main_cursor().selection(), Range::new(0, 4, 0, 7));
+
+        assert!(b.undo_last_occurrence());
+        assert_eq!(selections(&b), vec![Range::new(0, 0, 0, 3)]);
+        assert!(!b.undo_last_occurrence());
+    }
+}
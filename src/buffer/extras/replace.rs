@@ -0,0 +1,175 @@
+use crate::{
+    buffer::{Buffer, TextEdit},
+    cursor::{Position, Range},
+    find::FindQuery,
+};
+
+impl Buffer {
+    /// Replaces the occurrence selected by the main cursor and selects the
+    /// next one (wrapping around at EOF). If the main cursor does not select
+    /// an occurrence, it just selects the next one.
+    ///
+    /// Returns false if there are no occurrences.
+    pub fn replace_next(&mut self, query: &FindQuery, replacement: &str) -> bool {
+        let selection = self.main_cursor().selection();
+        let mut next_pos = selection.front();
+        let mut replaced = false;
+        if !selection.is_empty() {
+            if let Some((range, new_text)) = query
+                .replacements(&self.buf, selection, replacement)
+                .into_iter()
+                .find(|(range, _)| *range == selection)
+            {
+                next_pos = Position::position_after_edit(
+                    Range::from_single_position(range.front()),
+                    &new_text,
+                );
+                self.save_undo();
+                self.apply_text_edit(&TextEdit { range, new_text });
+                self.save_undo();
+                replaced = true;
+            }
+        }
+
+        let next = query
+            .find_iter(&self.buf, next_pos)
+            .next()
+            .or_else(|| query.find_iter(&self.buf, Position::new(0, 0)).next());
+
+        match next {
+            Some(range) => {
+                self.select_main_cursor_range(range);
+                true
+            }
+            None => {
+                if replaced {
+                    self.move_main_cursor_to_pos(next_pos);
+                }
+
+                replaced
+            }
+        }
+    }
+
+    /// Replaces all occurrences in the buffer, or only ones in the selections
+    /// if `in_selection` is true, as a single undo step.
+    ///
+    /// Returns the number of replaced occurrences.
+    pub fn replace_all(
+        &mut self,
+        query: &FindQuery,
+        replacement: &str,
+        in_selection: bool,
+    ) -> usize {
+        let ranges: Vec<Range> = if in_selection {
+            self.cursors()
+                .iter()
+                .filter(|c| c.is_selection())
+                .map(|c| c.selection())
+                .collect()
+        } else {
+            let end_y = self.num_lines() - 1;
+            vec![Range::new(0, 0, end_y, self.line_len(end_y))]
+        };
+
+        let edits: Vec<TextEdit> = ranges
+            .into_iter()
+            .flat_map(|range| query.replacements(&self.buf, range, replacement))
+            .map(|(range, new_text)| TextEdit { range, new_text })
+            .collect();
+
+        let num_replaced = edits.len();
+        if num_replaced > 0 {
+            self.save_undo();
+            self.apply_text_edits(edits);
+            self.save_undo();
+        }
+
+        num_replaced
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::{cursor::Cursor, find::FindOptions};
+
+    use super::*;
+
+    fn query(query: &str, regex: bool) -> FindQuery {
+        FindQuery::new(
+            query,
+            FindOptions {
+                regex,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn replace_all() {
+        let mut b = Buffer::from_text("foo bar\nfoo baz foo");
+        assert_eq!(b.replace_all(&query("foo", false), "x", false), 3);
+        assert_eq!(b.text(), "x bar\nx baz x");
+
+        // A single undo step.
+        b.undo();
+        assert_eq!(b.text(), "foo bar\nfoo baz foo");
+
+        assert_eq!(b.replace_all(&query("nothing", false), "x", false), 0);
+        assert_eq!(b.text(), "foo bar\nfoo baz foo");
+    }
+
+    #[test]
+    fn replace_all_with_captures() {
+        let mut b = Buffer::from_text("let a = 1;\nlet bc = 23;");
+        assert_eq!(
+            b.replace_all(
+                &query(r"let (\w+) = (\d+)", true),
+                "const $1: i32 = $2",
+                false
+            ),
+            2
+        );
+        assert_eq!(b.text(), "const a: i32 = 1;\nconst bc: i32 = 23;");
+
+        // `$1` is not expanded in the plain text mode.
+        let mut b = Buffer::from_text("abc");
+        b.replace_all(&query("b", false), "$1", false);
+        assert_eq!(b.text(), "a$1c");
+    }
+
+    #[test]
+    fn replace_in_selection() {
+        let mut b = Buffer::from_text("aaa\naaa\naaa");
+        b.set_cursors_for_test(&[Cursor::new_selection(0, 1, 1, 2)]);
+        assert_eq!(b.replace_all(&query("a", false), "b", true), 4);
+        assert_eq!(b.text(), "abb\nbba\naaa");
+    }
+
+    #[test]
+    fn replace_next() {
+        let mut b = Buffer::from_text("foo foo\nfoo");
+        b.set_cursors_for_test(&[Cursor::new(0, 1)]);
+
+        // Select the next occurrence first.
+        assert!(b.replace_next(&query("foo", false), "barbaz"));
+        assert_eq!(b.text(), "foo foo\nfoo");
+        assert_eq!(b.main_cursor().selection(), Range::new(0, 4, 0, 7));
+
+        assert!(b.replace_next(&query("foo", false), "barbaz"));
+        assert_eq!(b.text(), "foo barbaz\nfoo");
+        assert_eq!(b.main_cursor().selection(), Range::new(1, 0, 1, 3));
+
+        // Wrap around.
+        assert!(b.replace_next(&query("foo", false), "barbaz"));
+        assert_eq!(b.text(), "foo barbaz\nbarbaz");
+        assert_eq!(b.main_cursor().selection(), Range::new(0, 0, 0, 3));
+
+        assert!(b.replace_next(&query("foo", false), "barbaz"));
+        assert_eq!(b.text(), "barbaz barbaz\nbarbaz");
+        assert!(!b.replace_next(&query("foo", false), "barbaz"));
+    }
+}
@@ -0,0 +1,90 @@
+use crate::{buffer::Buffer, cursor::Range};
+
+impl Buffer {
+    /// Selects the text inside the innermost bracket or quote pair enclosing
+    /// each selection. If it's already selected, selects inside the next
+    /// enclosing pair.
+    pub fn select_inside_pairs(&mut self) {
+        self.update_cursors_with(|c, buffer| {
+            let selection = c.selection();
+            let mut pair = buffer.find_nearest_enclosing_pair(selection);
+            if let Some((open, close)) = pair {
+                if Range::from_positions(open.back(), close.front()) == selection {
+                    let around = Range::from_positions(open.front(), close.back());
+                    pair = buffer.find_nearest_enclosing_pair(around);
+                }
+            }
+
+            if let Some((open, close)) = pair {
+                c.select_range(Range::from_positions(open.back(), close.front()));
+            }
+        });
+    }
+
+    /// Selects the innermost bracket or quote pair enclosing each selection
+    /// including the brackets or quotes.
+    pub fn select_around_pairs(&mut self) {
+        self.update_cursors_with(|c, buffer| {
+            if let Some((open, close)) = buffer.find_nearest_enclosing_pair(c.selection()) {
+                c.select_range(Range::from_positions(open.front(), close.back()));
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::{cursor::Cursor, syntax::parsed_buffer};
+
+    use super::*;
+
+    #[test]
+    fn select_inside_pairs() {
+        let mut b = Buffer::from_text("f(a, [b], `c`, {\n  d\n})");
+        b.set_cursors_for_test(&[Cursor::new(0, 6), Cursor::new(0, 11), Cursor::new(1, 2)]);
+        b.select_inside_pairs();
+        assert_eq!(
+            b.cursors(),
+            &[
+                Cursor::new_selection(0, 6, 0, 7),
+                Cursor::new_selection(0, 11, 0, 12),
+                Cursor::new_selection(0, 16, 2, 0),
+            ]
+        );
+
+        // Grow outward.
+        b.select_inside_pairs();
+        assert_eq!(b.cursors(), &[Cursor::new_selection(0, 2, 2, 1)]);
+    }
+
+    #[test]
+    fn select_around_pairs() {
+        let mut b = Buffer::from_text("x = [(1, \"a\\\"b\"), 2]");
+        b.set_cursors_for_test(&[Cursor::new(0, 11)]);
+        b.select_around_pairs();
+        assert_eq!(b.cursors(), &[Cursor::new_selection(0, 9, 0, 15)]);
+
+        // Grow outward.
+        b.select_around_pairs();
+        assert_eq!(b.cursors(), &[Cursor::new_selection(0, 5, 0, 16)]);
+        b.select_around_pairs();
+        assert_eq!(b.cursors(), &[Cursor::new_selection(0, 4, 0, 20)]);
+        b.select_around_pairs();
+        assert_eq!(b.cursors(), &[Cursor::new_selection(0, 4, 0, 20)]);
+    }
+
+    #[test]
+    fn select_pairs_by_syntax() {
+        let mut b = parsed_buffer("rust", "f(\")\", '(');");
+
+        b.set_cursors_for_test(&[Cursor::new(0, 5)]);
+        b.select_inside_pairs();
+        assert_eq!(b.cursors(), &[Cursor::new_selection(0, 2, 0, 10)]);
+
+        b.set_cursors_for_test(&[Cursor::new(0, 3)]);
+        b.select_around_pairs();
+        assert_eq!(b.cursors(), &[Cursor::new_selection(0, 2, 0, 5)]);
+    }
+}
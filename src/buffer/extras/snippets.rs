@@ -0,0 +1,255 @@
+use noa_editorconfig::IndentStyle;
+
+use crate::{buffer::Buffer, cursor::Range, mut_raw_buffer::Change, snippet::Snippet};
+
+/// The tabstops of the snippet being edited.
+#[derive(Clone, Debug)]
+pub struct SnippetSession {
+    /// The ranges of each tabstop (from all cursors) in the order to be
+    /// visited.
+    tabstops: Vec<Vec<Range>>,
+    choices: Vec<Vec<String>>,
+    /// The index of the tabstop being edited.
+    current: usize,
+    /// The number of recorded changes already reflected in `tabstops`, i.e.
+    /// the insertion of the snippet itself.
+    num_applied_changes: usize,
+}
+
+impl Buffer {
+    /// Inserts the snippet at each cursor and selects its first tabstop.
+    ///
+    /// `resolve_variable` returns the value of a variable which is not
+    /// specific to cursors such as `TM_FILENAME`.
+    pub fn insert_snippet<F>(&mut self, snippet: &Snippet, resolve_variable: F)
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let tab = match self.config.indent_style {
+            IndentStyle::Tab => "\t".to_owned(),
+            IndentStyle::Space => " ".repeat(self.config.indent_size),
+        };
+
+        // Expand the snippet for each cursor and compute where it will be
+        // inserted after all edits.
+        let mut expanded_snippets = Vec::new();
+        let mut start_offsets = Vec::new();
+        let mut diff: isize = 0;
+        for c in self.cursors() {
+            let selection = c.selection();
+            let pos = c.moving_position();
+            let indent = self.substr(Range::new(
+                selection.front().y,
+                0,
+                selection.front().y,
+                self.buf.line_indent_len(selection.front().y),
+            ));
+
+            let expanded = snippet.expand(&indent, &tab, |name| match name {
+                "TM_SELECTED_TEXT" => Some(self.substr(selection)),
+                "TM_CURRENT_LINE" => Some(self.buf.line_text(pos.y)),
+                "TM_CURRENT_WORD" => self.current_word(pos).map(|range| self.substr(range)),
+                "TM_LINE_INDEX" => Some(pos.y.to_string()),
+                "TM_LINE_NUMBER" => Some((pos.y + 1).to_string()),
+                _ => resolve_variable(name),
+            });
+
+            let front = self.buf.pos_to_char_index(selection.front());
+            let back = self.buf.pos_to_char_index(selection.back());
+            start_offsets.push((front as isize + diff) as usize);
+            diff += expanded.text.chars().count() as isize - (back - front) as isize;
+            expanded_snippets.push(expanded);
+        }
+
+        let mut texts = expanded_snippets.iter().rev();
+        self.cursors.foreach(|c, past_cursors| {
+            self.buf
+                .edit_at_cursor(c, past_cursors, &texts.next().unwrap().text);
+        });
+
+        let mut tabstops = vec![Vec::new(); expanded_snippets[0].tabstops.len()];
+        for (expanded, start) in expanded_snippets.iter().zip(start_offsets) {
+            for (i, tabstop) in expanded.tabstops.iter().enumerate() {
+                for range in &tabstop.ranges {
+                    tabstops[i].push(Range::from_positions(
+                        self.buf.char_index_to_pos(start + range.start),
+                        self.buf.char_index_to_pos(start + range.end),
+                    ));
+                }
+            }
+        }
+
+        let choices = expanded_snippets[0]
+            .tabstops
+            .iter()
+            .map(|tabstop| tabstop.choices.clone())
+            .collect();
+
+        self.snippet_session = Some(SnippetSession {
+            tabstops,
+            choices,
+            current: 0,
+            num_applied_changes: self.buf.changes().len(),
+        });
+        self.select_current_tabstop();
+    }
+
+    /// Returns true if a snippet is being edited.
+    pub fn in_snippet(&self) -> bool {
+        self.snippet_session.is_some()
+    }
+
+    /// Returns the choices of the current tabstop if any.
+    pub fn current_snippet_choices(&self) -> Option<&[String]> {
+        self.snippet_session
+            .as_ref()
+            .map(|session| session.choices[session.current].as_slice())
+            .filter(|choices| !choices.is_empty())
+    }
+
+    /// Selects the next tabstop. Returns false if no snippet is being edited
+    /// or the main cursor has left the current tabstop.
+    pub fn move_to_next_tabstop(&mut self) -> bool {
+        if !self.is_main_cursor_in_tabstop() {
+            self.snippet_session = None;
+            return false;
+        }
+
+        let session = self.snippet_session.as_mut().unwrap();
+        session.current += 1;
+        self.select_current_tabstop();
+        true
+    }
+
+    /// Selects the previous tabstop. Returns false if no snippet is being
+    /// edited or the main cursor has left the current tabstop.
+    pub fn move_to_prev_tabstop(&mut self) -> bool {
+        if !self.is_main_cursor_in_tabstop() {
+            self.snippet_session = None;
+            return false;
+        }
+
+        let session = self.snippet_session.as_mut().unwrap();
+        session.current = session.current.saturating_sub(1);
+        self.select_current_tabstop();
+        true
+    }
+
+    fn is_main_cursor_in_tabstop(&self) -> bool {
+        let session = match &self.snippet_session {
+            Some(session) => session,
+            None => return false,
+        };
+
+        let selection = self.main_cursor().selection();
+        session.tabstops[session.current].iter().any(|range| {
+            range.contains_or_contacts_with(selection.front())
+                && range.contains_or_contacts_with(selection.back())
+        })
+    }
+
+    /// Selects the ranges of the current tabstop. Ends the session when it
+    /// reaches the last one (`$0`).
+    fn select_current_tabstop(&mut self) {
+        let session = self.snippet_session.as_ref().unwrap();
+        let ranges = session.tabstops[session.current].clone();
+        if session.current == session.tabstops.len() - 1 {
+            self.snippet_session = None;
+        }
+
+        self.cursors.set_selections(&ranges);
+    }
+
+    /// Updates the tabstop ranges after the edits: the current tabstop grows
+    /// as you type into it.
+    pub(crate) fn update_snippet_session(&mut self, changes: &[Change]) {
+        let session = match &mut self.snippet_session {
+            Some(session) => session,
+            None => return,
+        };
+
+        let num_applied_changes = std::mem::take(&mut session.num_applied_changes);
+        for change in changes.iter().skip(num_applied_changes) {
+            for (i, ranges) in session.tabstops.iter_mut().enumerate() {
+                let is_current = i == session.current;
+                for range in ranges {
+                    let front = change.adjust_position(range.front(), is_current);
+                    let back = change.adjust_position(range.back(), !is_current);
+                    *range = Range::from_positions(front, back.max(front));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::cursor::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn insert_snippet() {
+        let mut b = Buffer::from_text("  a\n  b");
+        b.set_cursors_for_test(&[Cursor::new(0, 3), Cursor::new(1, 3)]);
+        b.insert_snippet(&Snippet::parse("if ${1:x} {\n\t$0\n}"), |_| None);
+        assert_eq!(b.text(), "  aif x {\n      \n  }\n  bif x {\n      \n  }");
+        assert_eq!(
+            b.cursors(),
+            &[
+                Cursor::new_selection(0, 6, 0, 7),
+                Cursor::new_selection(3, 6, 3, 7)
+            ]
+        );
+        assert!(b.in_snippet());
+
+        // Type in the first tabstop.
+        b.insert("yz");
+        b.clear_recorded_changes();
+        assert_eq!(b.text(), "  aif yz {\n      \n  }\n  bif yz {\n      \n  }");
+
+        assert!(b.move_to_prev_tabstop());
+        assert_eq!(
+            b.cursors(),
+            &[
+                Cursor::new_selection(0, 6, 0, 8),
+                Cursor::new_selection(3, 6, 3, 8)
+            ]
+        );
+
+        assert!(b.move_to_next_tabstop());
+        assert_eq!(b.cursors(), &[Cursor::new(1, 6), Cursor::new(4, 6)]);
+        assert!(!b.in_snippet());
+        assert!(!b.move_to_next_tabstop());
+    }
+
+    #[test]
+    fn insert_snippet_with_variables() {
+        let mut b = Buffer::from_text("abc");
+        b.set_cursors_for_test(&[Cursor::new_selection(0, 0, 0, 3)]);
+        b.insert_snippet(
+            &Snippet::parse("${FOO}(${1:$TM_SELECTED_TEXT})$TM_LINE_NUMBER"),
+            |name| (name == "FOO").then(|| "foo".to_owned()),
+        );
+        assert_eq!(b.text(), "foo(abc)1");
+        assert_eq!(b.cursors(), &[Cursor::new_selection(0, 4, 0, 7)]);
+    }
+
+    #[test]
+    fn leave_tabstop() {
+        let mut b = Buffer::from_text("");
+        b.insert_snippet(&Snippet::parse("${1|a,b|} $2"), |_| None);
+        assert_eq!(
+            b.current_snippet_choices(),
+            Some(&["a".to_owned(), "b".to_owned()][..])
+        );
+
+        // The cursor is moved out of the tabstop.
+        b.set_cursors_for_test(&[Cursor::new(0, 2)]);
+        assert!(!b.move_to_next_tabstop());
+        assert!(!b.in_snippet());
+        assert_eq!(b.current_snippet_choices(), None);
+    }
+}
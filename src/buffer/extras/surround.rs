@@ -0,0 +1,382 @@
+use crate::{
+    buffer::{Buffer, RangeEdit},
+    cursor::{Position, Range},
+    extras::matching_brackets::corresponding_bracket,
+};
+
+/// A pair of delimiters surrounding a text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SurroundPair {
+    /// Brackets or quotes, e.g. `(` and `)`.
+    Chars(char, char),
+    /// An HTML tag. `open` is the whole opening tag, e.g. `<a href="#">`.
+    Tag { name: String, open: String },
+}
+
+impl SurroundPair {
+    /// Parses a bracket (either opening or closing one), a quote, any other
+    /// single character, or an opening tag like `<div>`.
+    pub fn parse(s: &str) -> Option<SurroundPair> {
+        let s = s.trim();
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(ch), None) => Some(match corresponding_bracket(ch) {
+                Some((true, close)) => SurroundPair::Chars(ch, close),
+                Some((false, open)) => SurroundPair::Chars(open, ch),
+                None => SurroundPair::Chars(ch, ch),
+            }),
+            (Some('<'), Some(_)) if s.ends_with('>') => {
+                let name = tag_name(&s[1..])?;
+                Some(SurroundPair::Tag {
+                    name,
+                    open: s.to_owned(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn open_str(&self) -> String {
+        match self {
+            SurroundPair::Chars(open, _) => open.to_string(),
+            SurroundPair::Tag { open, .. } => open.clone(),
+        }
+    }
+
+    fn close_str(&self) -> String {
+        match self {
+            SurroundPair::Chars(_, close) => close.to_string(),
+            SurroundPair::Tag { name, .. } => format!("</{}>", name),
+        }
+    }
+}
+
+/// Returns the tag name at the beginning of `s`.
+fn tag_name(s: &str) -> Option<String> {
+    let name: String = s
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | ':' | '.'))
+        .collect();
+
+    if name.is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+/// A tag found in the buffer.
+struct Tag {
+    range: Range,
+    name: String,
+    closing: bool,
+}
+
+/// A surrounding pair to be replaced: the ranges of the current delimiters
+/// and the new ones.
+struct SurroundEdit {
+    open: Range,
+    close: Range,
+    new_open: String,
+    new_close: String,
+}
+
+impl Buffer {
+    /// Wraps each selection with the pair. If the cursor has no selection,
+    /// the current word is wrapped instead.
+    pub fn surround_add(&mut self, pair: &SurroundPair) {
+        let mut edits = Vec::new();
+        for c in self.cursors() {
+            let mut range = c.selection();
+            if range.is_empty() {
+                if let Some(word) = self.current_word(c.moving_position()) {
+                    range = word;
+                }
+            }
+
+            edits.push(SurroundEdit {
+                open: Range::from_single_position(range.front()),
+                close: Range::from_single_position(range.back()),
+                new_open: pair.open_str(),
+                new_close: pair.close_str(),
+            });
+        }
+
+        self.apply_surround_edits(edits);
+    }
+
+    /// Replaces the nearest pair enclosing each selection with `new_pair`.
+    /// If `target` is `None`, the nearest one of any kind is replaced.
+    pub fn surround_change(&mut self, target: Option<&SurroundPair>, new_pair: &SurroundPair) {
+        let edits = self.find_surround_targets(target, |open, close| SurroundEdit {
+            open,
+            close,
+            new_open: new_pair.open_str(),
+            new_close: new_pair.close_str(),
+        });
+        self.apply_surround_edits(edits);
+    }
+
+    /// Deletes the nearest pair enclosing each selection. If `target` is
+    /// `None`, the nearest one of any kind is deleted.
+    pub fn surround_delete(&mut self, target: Option<&SurroundPair>) {
+        let edits = self.find_surround_targets(target, |open, close| SurroundEdit {
+            open,
+            close,
+            new_open: String::new(),
+            new_close: String::new(),
+        });
+        self.apply_surround_edits(edits);
+    }
+
+    /// Returns the ranges of the nearest pair enclosing `range`.
+    pub fn find_surrounding_pair(
+        &self,
+        range: Range,
+        target: Option<&SurroundPair>,
+    ) -> Option<(Range, Range)> {
+        match target {
+            Some(SurroundPair::Chars(open, close)) => {
+                self.find_enclosing_pair(range, *open, *close)
+            }
+            Some(SurroundPair::Tag { name, .. }) => self.find_enclosing_tag(range, Some(name)),
+            None => self
+                .find_nearest_enclosing_pair(range)
+                .into_iter()
+                .chain(self.find_enclosing_tag(range, None))
+                .max_by_key(|(open, _)| open.front()),
+        }
+    }
+
+    fn find_surround_targets<F>(&self, target: Option<&SurroundPair>, f: F) -> Vec<SurroundEdit>
+    where
+        F: Fn(Range, Range) -> SurroundEdit,
+    {
+        self.cursors()
+            .iter()
+            .filter_map(|c| self.find_surrounding_pair(c.selection(), target))
+            .map(|(open, close)| f(open, close))
+            .collect()
+    }
+
+    /// Replaces the delimiters. Selections keep selecting the same text.
+    fn apply_surround_edits(&mut self, edits: Vec<SurroundEdit>) {
+        // Ignore pairs sharing delimiters with another one, e.g. the same
+        // pair found from multiple cursors.
+        let mut delimiter_edits: Vec<RangeEdit> = Vec::new();
+        for edit in edits {
+            let shared = delimiter_edits.iter().any(|delimiter| {
+                let range = delimiter.range;
+                !range.is_empty()
+                    && (range.overlaps_with(edit.open) || range.overlaps_with(edit.close))
+            });
+            if shared {
+                continue;
+            }
+
+            // Positions at the beginning of the pair move into it.
+            delimiter_edits.push(RangeEdit {
+                range: edit.open,
+                new_text: edit.new_open,
+                stick_left: false,
+            });
+            delimiter_edits.push(RangeEdit {
+                range: edit.close,
+                new_text: edit.new_close,
+                stick_left: true,
+            });
+        }
+
+        self.edit_ranges(delimiter_edits);
+    }
+
+    /// Returns the ranges of the nearest tag pair enclosing `range`. If
+    /// `name` is `None`, tags with any name are searched.
+    fn find_enclosing_tag(&self, range: Range, name: Option<&str>) -> Option<(Range, Range)> {
+        // Look for an opening tag without its closing tag before `range`.
+        let mut char_iter = self.char_iter(range.front());
+        let mut closed_tags = Vec::new();
+        let open_tag = loop {
+            if char_iter.prev()? != '<' {
+                continue;
+            }
+
+            let tag = match self.parse_tag_at(char_iter.last_position()) {
+                Some(tag) => tag,
+                None => continue,
+            };
+
+            if tag.closing {
+                closed_tags.push(tag.name);
+            } else if closed_tags.last() == Some(&tag.name) {
+                closed_tags.pop();
+            } else if tag.range.back() <= range.front()
+                && (name.is_none() || name == Some(tag.name.as_str()))
+            {
+                break tag;
+            }
+        };
+
+        // Look for the corresponding closing tag after `range`.
+        let mut char_iter = self.char_iter(range.back());
+        let mut nested = 0;
+        loop {
+            if char_iter.next()? != '<' {
+                continue;
+            }
+
+            let tag = match self.parse_tag_at(char_iter.last_position()) {
+                Some(tag) if tag.name == open_tag.name => tag,
+                _ => continue,
+            };
+
+            if !tag.closing {
+                nested += 1;
+            } else if nested > 0 {
+                nested -= 1;
+            } else {
+                return Some((open_tag.range, tag.range));
+            }
+        }
+    }
+
+    /// Parses an opening or closing tag at `pos`. Self-closing tags are
+    /// ignored.
+    fn parse_tag_at(&self, pos: Position) -> Option<Tag> {
+        const TAG_LEN_MAX: usize = 1024;
+
+        let mut char_iter = self.char_iter(pos);
+        let mut text = String::new();
+        for ch in char_iter.by_ref().take(TAG_LEN_MAX) {
+            text.push(ch);
+            if ch == '>' {
+                break;
+            }
+        }
+
+        if !text.ends_with('>') || text.ends_with("/>") {
+            return None;
+        }
+
+        let end = char_iter.last_position();
+        let range = Range::from_positions(pos, Position::new(end.y, end.x + 1));
+        match text.strip_prefix("</") {
+            Some(rest) => Some(Tag {
+                range,
+                name: tag_name(rest)?,
+                closing: true,
+            }),
+            None => Some(Tag {
+                range,
+                name: tag_name(&text[1..])?,
+                closing: false,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::cursor::Cursor;
+
+    use super::*;
+
+    fn pair(s: &str) -> SurroundPair {
+        SurroundPair::parse(s).unwrap()
+    }
+
+    #[test]
+    fn parse_pair() {
+        assert_eq!(pair("("), SurroundPair::Chars('(', ')'));
+        assert_eq!(pair(")"), SurroundPair::Chars('(', ')'));
+        assert_eq!(pair("'"), SurroundPair::Chars('\'', '\''));
+        assert_eq!(
+            pair("<a href=\"#\">"),
+            SurroundPair::Tag {
+                name: "a".to_owned(),
+                open: "<a href=\"#\">".to_owned()
+            }
+        );
+        assert_eq!(SurroundPair::parse("ab"), None);
+        assert_eq!(SurroundPair::parse("<>"), None);
+    }
+
+    #[test]
+    fn surround_add() {
+        let mut b = Buffer::from_text("abc def\nxyz");
+        b.set_cursors_for_test(&[
+            Cursor::new_selection(0, 0, 0, 3),
+            Cursor::new(0, 5),
+            Cursor::new_selection(0, 7, 1, 1),
+        ]);
+        b.surround_add(&pair("("));
+        assert_eq!(b.text(), "(abc) (def)(\nx)yz");
+        assert_eq!(
+            b.cursors(),
+            &[
+                Cursor::new_selection(0, 1, 0, 4),
+                Cursor::new(0, 8),
+                Cursor::new_selection(0, 12, 1, 1),
+            ]
+        );
+
+        let mut b = Buffer::from_text("abc");
+        b.set_cursors_for_test(&[Cursor::new_selection(0, 0, 0, 3)]);
+        b.surround_add(&pair("<b class=\"x\">"));
+        assert_eq!(b.text(), "<b class=\"x\">abc</b>");
+        assert_eq!(b.cursors(), &[Cursor::new_selection(0, 13, 0, 16)]);
+    }
+
+    #[test]
+    fn surround_change() {
+        let mut b = Buffer::from_text("f(a, [b]) \"c\"");
+        b.set_cursors_for_test(&[Cursor::new(0, 2), Cursor::new(0, 6), Cursor::new(0, 12)]);
+        b.surround_change(None, &pair("{"));
+        assert_eq!(b.text(), "f{a, {b}} {c}");
+        assert_eq!(
+            b.cursors(),
+            &[Cursor::new(0, 2), Cursor::new(0, 6), Cursor::new(0, 12)]
+        );
+
+        // Quotes sharing a delimiter.
+        let mut b = Buffer::from_text("\"a\" \"b\"");
+        b.set_cursors_for_test(&[Cursor::new(0, 1), Cursor::new(0, 3)]);
+        b.surround_change(None, &pair("("));
+        assert_eq!(b.text(), "(a) \"b\"");
+
+        let mut b = Buffer::from_text("(a [b] c)");
+        b.set_cursors_for_test(&[Cursor::new(0, 4)]);
+        b.surround_change(Some(&pair("(")), &pair("'"));
+        assert_eq!(b.text(), "'a [b] c'");
+        assert_eq!(b.cursors(), &[Cursor::new(0, 4)]);
+    }
+
+    #[test]
+    fn surround_delete() {
+        let mut b = Buffer::from_text("<div><p>a</p> <p>b</p></div>");
+        b.set_cursors_for_test(&[Cursor::new(0, 8)]);
+        b.surround_delete(None);
+        assert_eq!(b.text(), "<div>a <p>b</p></div>");
+        assert_eq!(b.cursors(), &[Cursor::new(0, 5)]);
+
+        b.set_cursors_for_test(&[Cursor::new(0, 5), Cursor::new(0, 7)]);
+        b.surround_delete(None);
+        assert_eq!(b.text(), "a <p>b</p>");
+        assert_eq!(b.cursors(), &[Cursor::new(0, 0), Cursor::new(0, 2)]);
+
+        // Cursors in the same pair.
+        let mut b = Buffer::from_text("x(a, b)");
+        b.set_cursors_for_test(&[Cursor::new(0, 2), Cursor::new(0, 5)]);
+        b.surround_delete(Some(&pair(")")));
+        assert_eq!(b.text(), "xa, b");
+        assert_eq!(b.cursors(), &[Cursor::new(0, 1), Cursor::new(0, 4)]);
+
+        // No pairs.
+        let mut b = Buffer::from_text("abc");
+        b.set_cursors_for_test(&[Cursor::new(0, 1)]);
+        b.surround_delete(None);
+        assert_eq!(b.text(), "abc");
+    }
+}
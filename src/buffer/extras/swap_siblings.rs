@@ -0,0 +1,156 @@
+use std::cmp::{max, min};
+
+use noa_languages::tree_sitter;
+
+use crate::{buffer::Buffer, cursor::Range, syntax::TsNodeExt};
+
+/// Looks for the smallest named node containing `selection` which has a
+/// named sibling in the direction, and returns the ranges of the node and
+/// the sibling.
+fn find_swap_pair(
+    root: tree_sitter::Node<'_>,
+    selection: Range,
+    forward: bool,
+) -> Option<(Range, Range)> {
+    let mut node =
+        root.named_descendant_for_point_range(selection.front().into(), selection.back().into())?;
+    loop {
+        let sibling = if forward {
+            node.next_named_sibling()
+        } else {
+            node.prev_named_sibling()
+        };
+
+        if let Some(sibling) = sibling {
+            return Some((node.buffer_range(), sibling.buffer_range()));
+        }
+
+        node = node.parent()?;
+    }
+}
+
+impl Buffer {
+    /// Swaps the syntax node under each cursor with the next named sibling
+    /// (e.g. a function argument with the next one).
+    ///
+    /// Returns `false` if the syntax tree is not available or not up to date
+    /// with the buffer.
+    pub fn swap_with_next_sibling(&mut self) -> bool {
+        self.swap_with_sibling(true)
+    }
+
+    /// Swaps the syntax node under each cursor with the previous named
+    /// sibling.
+    ///
+    /// Returns `false` if the syntax tree is not available or not up to date
+    /// with the buffer.
+    pub fn swap_with_prev_sibling(&mut self) -> bool {
+        self.swap_with_sibling(false)
+    }
+
+    fn swap_with_sibling(&mut self, forward: bool) -> bool {
+        // Node ranges in a stale tree may point to wrong text.
+        let root = match self.syntax() {
+            Some(syntax) if syntax.is_up_to_date(&self.buf) => syntax.tree().root_node(),
+            _ => return false,
+        };
+
+        // Look for nodes to be swapped before editing the buffer since the
+        // syntax tree won't be updated until the next parse. Pairs
+        // overlapping with another one are ignored.
+        let mut pairs = Vec::new();
+        let mut last_region: Option<Range> = None;
+        for c in self.cursors() {
+            let pair = find_swap_pair(root, c.selection(), forward).filter(|(node, sibling)| {
+                let region = Range::from_positions(
+                    min(node.front(), sibling.front()),
+                    max(node.back(), sibling.back()),
+                );
+                let overlapped = matches!(last_region, Some(last) if last.overlaps_with(region));
+                if !overlapped {
+                    last_region = Some(region);
+                }
+                !overlapped
+            });
+            pairs.push(pair);
+        }
+
+        let mut pairs = pairs.into_iter().rev();
+        self.cursors.foreach(|c, past_cursors| {
+            let (node, sibling) = match pairs.next().flatten() {
+                Some(pair) => pair,
+                None => return,
+            };
+
+            let (first, second) = if forward {
+                (node, sibling)
+            } else {
+                (sibling, node)
+            };
+
+            let first_text = self.buf.substr(first);
+            let separator = self
+                .buf
+                .substr(Range::from_positions(first.back(), second.front()));
+            let second_text = self.buf.substr(second);
+
+            // Remember where the cursor is in the node to follow the node.
+            let node_start = self.buf.pos_to_char_index(node.front());
+            let start_offset = self.buf.pos_to_char_index(c.selection().start) - node_start;
+            let end_offset = self.buf.pos_to_char_index(c.selection().end) - node_start;
+            let new_node_start = if forward {
+                self.buf.pos_to_char_index(first.front())
+                    + second_text.chars().count()
+                    + separator.chars().count()
+            } else {
+                self.buf.pos_to_char_index(first.front())
+            };
+
+            c.select_range(Range::from_positions(first.front(), second.back()));
+            self.buf.edit_at_cursor(
+                c,
+                past_cursors,
+                &format!("{}{}{}", second_text, separator, first_text),
+            );
+
+            c.select_range(Range::from_positions(
+                self.buf.char_index_to_pos(new_node_start + start_offset),
+                self.buf.char_index_to_pos(new_node_start + end_offset),
+            ));
+        });
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::{cursor::Cursor, syntax::parsed_buffer};
+
+    #[test]
+    fn swap_with_next_sibling() {
+        let mut b = parsed_buffer("rust", "foo(abc, de, f);");
+        b.set_cursors_for_test(&[Cursor::new(0, 5)]);
+        assert!(b.swap_with_next_sibling());
+        assert_eq!(b.text(), "foo(de, abc, f);");
+        assert_eq!(b.cursors(), &[Cursor::new(0, 9)]);
+
+        // The syntax tree is not updated yet.
+        assert!(!b.swap_with_next_sibling());
+        assert_eq!(b.text(), "foo(de, abc, f);");
+    }
+
+    #[test]
+    fn swap_with_prev_sibling() {
+        let mut b = parsed_buffer("rust", "let x = [1, 23, 456];\nlet y = [7, 8];");
+        b.set_cursors_for_test(&[Cursor::new_selection(0, 16, 0, 19), Cursor::new(1, 12)]);
+        b.swap_with_prev_sibling();
+        assert_eq!(b.text(), "let x = [1, 456, 23];\nlet y = [8, 7];");
+        assert_eq!(
+            b.cursors(),
+            &[Cursor::new_selection(0, 12, 0, 15), Cursor::new(1, 9)]
+        );
+    }
+}
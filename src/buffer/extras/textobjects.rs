@@ -0,0 +1,139 @@
+use std::cmp::Reverse;
+
+use crate::{buffer::Buffer, cursor::Range};
+
+/// A kind of text objects defined in `textobjects.scm`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextObjectKind {
+    Function,
+    Class,
+    Parameter,
+    Comment,
+}
+
+impl TextObjectKind {
+    fn capture_name(self) -> &'static str {
+        match self {
+            TextObjectKind::Function => "function.outer",
+            TextObjectKind::Class => "class.outer",
+            TextObjectKind::Parameter => "parameter.inner",
+            TextObjectKind::Comment => "comment.outer",
+        }
+    }
+}
+
+impl Buffer {
+    /// Returns the ranges of text objects sorted by their start positions.
+    fn textobjects(&self, kind: TextObjectKind) -> Vec<Range> {
+        let mut ranges = Vec::new();
+        if let Some(syntax) = self.syntax() {
+            syntax.query_textobjects(self.raw_buffer(), |range, capture| {
+                if capture == kind.capture_name() {
+                    ranges.push(range);
+                }
+            });
+        }
+
+        ranges.sort_by_key(|range| (range.front(), range.back()));
+        ranges.dedup();
+        ranges
+    }
+
+    /// Selects the innermost text object containing each selection. If the
+    /// selection is already a text object, selects the enclosing one.
+    pub fn select_textobjects(&mut self, kind: TextObjectKind) {
+        let textobjects = self.textobjects(kind);
+        self.update_cursors_with(|c, _| {
+            let selection = c.selection();
+            let innermost = textobjects
+                .iter()
+                .filter(|range| range.contains_range(selection) && **range != selection)
+                .max_by_key(|range| (range.front(), Reverse(range.back())));
+            if let Some(range) = innermost {
+                c.select_range(*range);
+            }
+        });
+    }
+
+    /// Moves each cursor to the beginning of the next text object.
+    pub fn move_to_next_textobjects(&mut self, kind: TextObjectKind) {
+        let textobjects = self.textobjects(kind);
+        self.update_cursors_with(|c, _| {
+            let pos = c.moving_position();
+            if let Some(range) = textobjects.iter().find(|range| range.front() > pos) {
+                c.move_to_pos(range.front());
+            }
+        });
+    }
+
+    /// Moves each cursor to the beginning of the previous text object.
+    pub fn move_to_prev_textobjects(&mut self, kind: TextObjectKind) {
+        let textobjects = self.textobjects(kind);
+        self.update_cursors_with(|c, _| {
+            let pos = c.moving_position();
+            if let Some(range) = textobjects.iter().rev().find(|range| range.front() < pos) {
+                c.move_to_pos(range.front());
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::{cursor::Cursor, syntax::parsed_buffer};
+
+    use super::*;
+
+    const TEXT: &str = concat!(
+        "// a\n",
+        "fn a(x: i32, y: i32) {}\n",
+        "\n",
+        "fn b() {\n",
+        "    a(1, 2);\n",
+        "}\n",
+    );
+
+    #[test]
+    fn select_textobjects() {
+        let mut b = parsed_buffer("rust", TEXT);
+        b.set_cursors_for_test(&[Cursor::new(1, 15), Cursor::new(4, 7)]);
+        b.select_textobjects(TextObjectKind::Parameter);
+        assert_eq!(
+            b.cursors(),
+            &[
+                Cursor::new_selection(1, 13, 1, 19),
+                Cursor::new_selection(4, 6, 4, 7),
+            ]
+        );
+
+        b.select_textobjects(TextObjectKind::Function);
+        assert_eq!(
+            b.cursors(),
+            &[
+                Cursor::new_selection(1, 0, 1, 23),
+                Cursor::new_selection(3, 0, 5, 1),
+            ]
+        );
+
+        // Nothing to select.
+        b.select_textobjects(TextObjectKind::Function);
+        assert_eq!(b.cursors()[0].selection(), Range::new(1, 0, 1, 23));
+    }
+
+    #[test]
+    fn move_to_textobjects() {
+        let mut b = parsed_buffer("rust", TEXT);
+        b.set_cursors_for_test(&[Cursor::new(0, 0)]);
+        b.move_to_next_textobjects(TextObjectKind::Function);
+        assert_eq!(b.cursors(), &[Cursor::new(1, 0)]);
+        b.move_to_next_textobjects(TextObjectKind::Function);
+        assert_eq!(b.cursors(), &[Cursor::new(3, 0)]);
+        b.move_to_next_textobjects(TextObjectKind::Function);
+        assert_eq!(b.cursors(), &[Cursor::new(3, 0)]);
+
+        b.move_to_prev_textobjects(TextObjectKind::Comment);
+        assert_eq!(b.cursors(), &[Cursor::new(0, 0)]);
+    }
+}
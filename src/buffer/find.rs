@@ -1,4 +1,217 @@
-use crate::{char_iter::CharIter, cursor::Range};
+use std::cmp::min;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+    char_iter::CharIter,
+    cursor::{Position, Range},
+    raw_buffer::RawBuffer,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct FindOptions {
+    /// Interpret the query as a regular expression.
+    pub regex: bool,
+    pub case_insensitive: bool,
+    /// Match only at word boundaries.
+    pub whole_word: bool,
+}
+
+enum Pattern {
+    Text(String),
+    Regex(Regex),
+}
+
+/// A query compiled with `FindOptions`.
+pub struct FindQuery {
+    pattern: Pattern,
+    /// Expand `$1` in replacements.
+    expand_captures: bool,
+}
+
+impl FindQuery {
+    pub fn new(query: &str, options: FindOptions) -> Result<FindQuery, regex::Error> {
+        if query.is_empty() || options == FindOptions::default() {
+            return Ok(FindQuery {
+                pattern: Pattern::Text(query.to_owned()),
+                expand_captures: false,
+            });
+        }
+
+        let mut pattern = if options.regex {
+            query.to_owned()
+        } else {
+            regex::escape(query)
+        };
+
+        if options.whole_word {
+            pattern = format!(r"\b(?:{})\b", pattern);
+        }
+
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(options.case_insensitive)
+            .build()?;
+
+        Ok(FindQuery {
+            pattern: Pattern::Regex(regex),
+            expand_captures: options.regex,
+        })
+    }
+
+    /// Returns an iterator which returns occurrences in `buf` from `pos`.
+    pub fn find_iter<'a>(&'a self, buf: &'a RawBuffer, pos: Position) -> FindQueryIter<'a> {
+        match &self.pattern {
+            Pattern::Text(text) => FindQueryIter::Text(buf.find_iter(text, pos)),
+            Pattern::Regex(regex) => FindQueryIter::Regex(RegexFindIter::new(buf, regex, pos)),
+        }
+    }
+
+    /// Returns occurrences within `range` and their replacements. In the
+    /// regex mode, capture groups (e.g. `$1` and `${name}`) in `replacement`
+    /// are expanded.
+    pub fn replacements(
+        &self,
+        buf: &RawBuffer,
+        range: Range,
+        replacement: &str,
+    ) -> Vec<(Range, String)> {
+        let regex = match &self.pattern {
+            Pattern::Regex(regex) if self.expand_captures => regex,
+            _ => {
+                return self
+                    .find_iter(buf, range.front())
+                    .take_while(|r| r.back() <= range.back())
+                    .map(|r| (r, replacement.to_owned()))
+                    .collect();
+            }
+        };
+
+        let mut replacements = Vec::new();
+        for y in range.front().y..=min(range.back().y, buf.num_lines() - 1) {
+            let line = buf.line_text(y);
+            for caps in regex.captures_iter(&line) {
+                let m = caps.get(0).unwrap();
+                if m.start() == m.end() {
+                    continue;
+                }
+
+                let start_x = line[..m.start()].chars().count();
+                let end_x = start_x + m.as_str().chars().count();
+                let r = Range::new(y, start_x, y, end_x);
+                if r.front() < range.front() || r.back() > range.back() {
+                    continue;
+                }
+
+                let mut new_text = String::new();
+                caps.expand(replacement, &mut new_text);
+                replacements.push((r, new_text));
+            }
+        }
+
+        replacements
+    }
+}
+
+impl Default for FindQuery {
+    /// A query which matches nothing.
+    fn default() -> FindQuery {
+        FindQuery {
+            pattern: Pattern::Text(String::new()),
+            expand_captures: false,
+        }
+    }
+}
+
+pub enum FindQueryIter<'a> {
+    Text(FindIter<'a, 'a>),
+    Regex(RegexFindIter<'a>),
+}
+
+impl<'a> Iterator for FindQueryIter<'a> {
+    type Item = Range;
+
+    fn next(&mut self) -> Option<Range> {
+        match self {
+            FindQueryIter::Text(iter) => iter.next(),
+            FindQueryIter::Regex(iter) => iter.next(),
+        }
+    }
+}
+
+/// Finds regex matches line by line, i.e. a match never spans multiple lines.
+pub struct RegexFindIter<'a> {
+    buf: &'a RawBuffer,
+    regex: &'a Regex,
+    y: usize,
+    line: String,
+    /// The byte offset in `line` to start the next search from.
+    offset: usize,
+}
+
+impl<'a> RegexFindIter<'a> {
+    fn new(buf: &'a RawBuffer, regex: &'a Regex, pos: Position) -> RegexFindIter<'a> {
+        let line = if pos.y < buf.num_lines() {
+            buf.line_text(pos.y)
+        } else {
+            String::new()
+        };
+
+        let offset = line
+            .char_indices()
+            .nth(pos.x)
+            .map(|(i, _)| i)
+            .unwrap_or(line.len());
+
+        RegexFindIter {
+            buf,
+            regex,
+            y: pos.y,
+            line,
+            offset,
+        }
+    }
+}
+
+impl<'a> Iterator for RegexFindIter<'a> {
+    type Item = Range;
+
+    fn next(&mut self) -> Option<Range> {
+        while self.y < self.buf.num_lines() {
+            let m = if self.offset <= self.line.len() {
+                self.regex.find_at(&self.line, self.offset)
+            } else {
+                None
+            };
+
+            match m {
+                Some(m) if m.start() == m.end() => {
+                    // Skip empty matches.
+                    self.offset = m.end()
+                        + self.line[m.end()..]
+                            .chars()
+                            .next()
+                            .map(|ch| ch.len_utf8())
+                            .unwrap_or(1);
+                }
+                Some(m) => {
+                    self.offset = m.end();
+                    let start_x = self.line[..m.start()].chars().count();
+                    let end_x = start_x + m.as_str().chars().count();
+                    return Some(Range::new(self.y, start_x, self.y, end_x));
+                }
+                None => {
+                    self.y += 1;
+                    self.offset = 0;
+                    if self.y < self.buf.num_lines() {
+                        self.line = self.buf.line_text(self.y);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+}
 
 pub struct FindIter<'a, 'b> {
     chars: CharIter<'a>,
@@ -167,4 +380,70 @@ mod tests {
         assert_eq!(iter.prev(), Some(Range::new(0, 0, 0, 1)));
         assert_eq!(iter.prev(), None);
     }
+
+    #[test]
+    fn test_find_with_options() {
+        fn find_all(text: &str, query: &str, options: FindOptions) -> Vec<Range> {
+            let b = Buffer::from_text(text);
+            let query = FindQuery::new(query, options).unwrap();
+            query.find_iter(&b, Position::new(0, 0)).collect()
+        }
+
+        let options = FindOptions::default();
+        assert_eq!(
+            find_all("a.c abc\nA.C", "a.c", options),
+            vec![Range::new(0, 0, 0, 3)]
+        );
+
+        let options = FindOptions {
+            case_insensitive: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            find_all("a.c abc\nA.C", "a.c", options),
+            vec![Range::new(0, 0, 0, 3), Range::new(1, 0, 1, 3)]
+        );
+
+        let options = FindOptions {
+            regex: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            find_all("a.c abc\nA.C", "a.c", options),
+            vec![Range::new(0, 0, 0, 3), Range::new(0, 4, 0, 7)]
+        );
+        assert_eq!(
+            find_all("あいう\nいい", "い+", options),
+            vec![Range::new(0, 1, 0, 2), Range::new(1, 0, 1, 2)]
+        );
+        assert_eq!(find_all("abc", "x*", options), vec![]);
+        assert!(FindQuery::new("(", options).is_err());
+
+        let options = FindOptions {
+            whole_word: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            find_all("foo foobar foo_ (foo)", "foo", options),
+            vec![Range::new(0, 0, 0, 3), Range::new(0, 17, 0, 20)]
+        );
+    }
+
+    #[test]
+    fn test_find_with_options_from_middle() {
+        let b = Buffer::from_text("abc abc\nabc");
+        let query = FindQuery::new(
+            "ABC",
+            FindOptions {
+                case_insensitive: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let mut iter = query.find_iter(&b, Position::new(0, 1));
+        assert_eq!(iter.next(), Some(Range::new(0, 4, 0, 7)));
+        assert_eq!(iter.next(), Some(Range::new(1, 0, 1, 3)));
+        assert_eq!(iter.next(), None);
+    }
 }
@@ -8,10 +8,13 @@ pub mod buffer;
 pub mod char_iter;
 pub mod cursor;
 pub mod display_width;
+pub mod encoding;
 pub mod extras;
 pub mod find;
 pub mod grapheme_iter;
 pub mod mut_raw_buffer;
 pub mod raw_buffer;
+pub mod snippet;
 pub mod syntax;
+pub mod undo_tree;
 pub mod word_iter;
@@ -1,11 +1,13 @@
 use std::ops::Deref;
 
+use serde::{Deserialize, Serialize};
+
 use crate::{
     cursor::{Cursor, Position, Range},
     raw_buffer::RawBuffer,
 };
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Change {
     pub range: Range,
     pub byte_range: std::ops::Range<usize>,
@@ -13,11 +15,43 @@ pub struct Change {
     pub insert_text: String,
 }
 
+impl Change {
+    /// Returns the position after the change. If `pos` is at the beginning of
+    /// the edited range, it stays there if `stick_left` is true or moves to
+    /// the end of the inserted text otherwise.
+    pub fn adjust_position(&self, pos: Position, stick_left: bool) -> Position {
+        let front = self.range.front();
+        let back = self.range.back();
+        if pos < front || (pos == front && stick_left) {
+            pos
+        } else if pos < back {
+            if stick_left {
+                front
+            } else {
+                self.new_pos
+            }
+        } else if pos.y == back.y {
+            Position::new(self.new_pos.y, self.new_pos.x + (pos.x - back.x))
+        } else {
+            Position::new(pos.y + self.new_pos.y - back.y, pos.x)
+        }
+    }
+}
+
+/// A change paired with its inverse, which reverts the change.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ReversibleChange {
+    pub redo: Change,
+    pub undo: Change,
+}
+
 /// An internal mutable buffer implementation supporting primitive operations
 /// required by the editor.
 pub struct MutRawBuffer {
     raw: RawBuffer,
     changes: Vec<Change>,
+    /// Changes since the last `clear_undo_log` for the undo history.
+    undo_log: Vec<ReversibleChange>,
 }
 
 impl MutRawBuffer {
@@ -25,6 +59,7 @@ impl MutRawBuffer {
         MutRawBuffer {
             raw: RawBuffer::new(),
             changes: Vec::new(),
+            undo_log: Vec::new(),
         }
     }
 
@@ -32,6 +67,7 @@ impl MutRawBuffer {
         MutRawBuffer {
             raw: raw_buffer,
             changes: Vec::new(),
+            undo_log: Vec::new(),
         }
     }
 
@@ -39,6 +75,7 @@ impl MutRawBuffer {
         MutRawBuffer {
             raw: RawBuffer::from_text(text),
             changes: Vec::new(),
+            undo_log: Vec::new(),
         }
     }
 
@@ -46,6 +83,7 @@ impl MutRawBuffer {
         Ok(MutRawBuffer {
             raw: RawBuffer::from_reader(reader)?,
             changes: Vec::new(),
+            undo_log: Vec::new(),
         })
     }
 
@@ -53,12 +91,25 @@ impl MutRawBuffer {
         &self.raw
     }
 
+    /// Returns the changes recorded since the last `clear_changes`.
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
     pub fn clear_changes(&mut self) -> Vec<Change> {
         let changes = self.changes.drain(..).collect();
         self.changes = Vec::new();
         changes
     }
 
+    pub fn undo_log(&self) -> &[ReversibleChange] {
+        &self.undo_log
+    }
+
+    pub fn clear_undo_log(&mut self) -> Vec<ReversibleChange> {
+        std::mem::take(&mut self.undo_log)
+    }
+
     /// Replaces the text at the `range` with `new_text`.
     ///
     /// This is the only method that modifies the buffer.
@@ -82,18 +133,36 @@ impl MutRawBuffer {
             rope.insert(start, new_text);
         }
 
+        let revision = self.raw.revision() + 1;
         self.raw = RawBuffer::from(rope);
+        self.raw.revision = revision;
     }
 
     pub fn edit(&mut self, range: Range, new_text: &str) -> &Change {
         let new_pos = Position::position_after_edit(range, new_text);
-        self.changes.push(Change {
+        let change = Change {
             range,
             insert_text: new_text.to_owned(),
             new_pos,
             byte_range: self.raw.pos_to_byte_index(range.front())
                 ..self.raw.pos_to_byte_index(range.back()),
+        };
+
+        // The change which puts the removed text back.
+        let inserted_end =
+            Position::position_after_edit(Range::from_single_position(range.front()), new_text);
+        let undo = Change {
+            range: Range::from_positions(range.front(), inserted_end),
+            insert_text: self.raw.substr(range),
+            new_pos: range.back(),
+            byte_range: change.byte_range.start..change.byte_range.start + new_text.len(),
+        };
+
+        self.undo_log.push(ReversibleChange {
+            redo: change.clone(),
+            undo,
         });
+        self.changes.push(change);
 
         self.edit_without_recording(range, new_text);
         self.changes.last().unwrap()
@@ -175,4 +244,20 @@ mod tests {
         buffer.edit(Range::new(0, 1, 0, 3), "");
         assert_eq!(buffer.text(), "ADEFG");
     }
+
+    #[test]
+    fn undo_log() {
+        let mut buffer = MutRawBuffer::from_text("ABC\nDEF");
+        buffer.edit(Range::new(0, 1, 1, 1), "XY\nZ");
+        assert_eq!(buffer.text(), "AXY\nZEF");
+
+        let log = buffer.clear_undo_log();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].undo.range, Range::new(0, 1, 1, 1));
+        assert_eq!(log[0].undo.insert_text, "BC\nD");
+        assert!(buffer.undo_log().is_empty());
+
+        buffer.edit(log[0].undo.range, &log[0].undo.insert_text);
+        assert_eq!(buffer.text(), "ABC\nDEF");
+    }
 }
@@ -1,5 +1,7 @@
 use std::{cmp::min, fmt};
 
+use noa_editorconfig::{EndOfLine, LineBreakCounts};
+
 use crate::{
     char_iter::CharIter,
     cursor::{Position, Range},
@@ -16,35 +18,117 @@ use crate::{
 pub struct RawBuffer {
     /// The inner buffer data structure.
     rope: ropey::Rope,
+    /// Incremented by every edit in `MutRawBuffer`. Tells in O(1) whether a
+    /// copy of the buffer (e.g. the one a syntax tree is parsed from) is
+    /// still up to date.
+    pub(crate) revision: u64,
 }
 
 impl RawBuffer {
     pub fn new() -> RawBuffer {
-        RawBuffer {
-            rope: ropey::Rope::new(),
-        }
+        RawBuffer::from(ropey::Rope::new())
     }
 
     pub fn from_text(text: &str) -> RawBuffer {
-        RawBuffer {
-            rope: ropey::Rope::from_str(text),
-        }
+        RawBuffer::from(ropey::Rope::from_str(text))
     }
 
+    /// Reads a text. Line endings are normalized into LF.
     pub fn from_reader<T: std::io::Read>(reader: T) -> std::io::Result<RawBuffer> {
-        Ok(RawBuffer {
-            rope: ropey::Rope::from_reader(reader)?,
-        })
+        RawBuffer::from_reader_with_end_of_line(reader).map(|(buf, _)| buf)
+    }
+
+    /// Reads a text and normalizes line endings into LF. Returns the line
+    /// ending detected in the text as well.
+    ///
+    /// CRLF is normalized while reading the text in chunks. Lone CRs are
+    /// kept unless they're the most common line ending, which is known only
+    /// after reading the whole text.
+    pub fn from_reader_with_end_of_line<T: std::io::Read>(
+        mut reader: T,
+    ) -> std::io::Result<(RawBuffer, Option<EndOfLine>)> {
+        let mut builder = ropey::RopeBuilder::new();
+        let mut normalizer = CrLfNormalizer::default();
+        let mut buf = vec![0; 64 * 1024];
+        // The length of an incomplete UTF-8 sequence at the end of `buf`.
+        let mut incomplete_len = 0;
+        loop {
+            let read_len = reader.read(&mut buf[incomplete_len..])?;
+            if read_len == 0 {
+                if incomplete_len > 0 {
+                    return Err(invalid_utf8_error());
+                }
+
+                break;
+            }
+
+            let len = incomplete_len + read_len;
+            let text = match std::str::from_utf8(&buf[..len]) {
+                Ok(text) => text,
+                Err(err) if err.error_len().is_none() => {
+                    std::str::from_utf8(&buf[..err.valid_up_to()]).unwrap()
+                }
+                Err(_) => return Err(invalid_utf8_error()),
+            };
+
+            let valid_len = text.len();
+            normalizer.push(text, |chunk| builder.append(chunk));
+
+            buf.copy_within(valid_len..len, 0);
+            incomplete_len = len - valid_len;
+        }
+
+        let counts = normalizer.finish(|chunk| builder.append(chunk));
+        let mut rope = builder.finish();
+        let eol = counts.most_common();
+        if eol == Some(EndOfLine::Cr) && counts.cr > 0 {
+            let mut builder = ropey::RopeBuilder::new();
+            for chunk in rope.chunks() {
+                builder.append(&chunk.replace('\r', "\n"));
+            }
+            rope = builder.finish();
+        }
+
+        Ok((RawBuffer::from(rope), eol))
     }
 
     pub fn rope(&self) -> &ropey::Rope {
         &self.rope
     }
 
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
     pub fn write_to(&self, writer: impl std::io::Write) -> std::io::Result<()> {
         self.rope.write_to(writer)
     }
 
+    /// Writes the text converting line endings into `eol`.
+    pub fn write_to_with_end_of_line(
+        &self,
+        mut writer: impl std::io::Write,
+        eol: EndOfLine,
+    ) -> std::io::Result<()> {
+        if eol == EndOfLine::Lf {
+            return self.write_to(writer);
+        }
+
+        for chunk in self.rope.chunks() {
+            let mut lines = chunk.split('\n');
+            if let Some(first) = lines.next() {
+                writer.write_all(first.as_bytes())?;
+            }
+
+            for line in lines {
+                writer.write_all(eol.as_str().as_bytes())?;
+                writer.write_all(line.as_bytes())?;
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn is_empty(&self) -> bool {
         self.rope.len_bytes() == 0
     }
@@ -266,16 +350,136 @@ impl fmt::Debug for RawBuffer {
     }
 }
 
+fn invalid_utf8_error() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        "stream did not contain valid UTF-8",
+    )
+}
+
+/// Replaces CRLF with LF in a text given in chunks, and counts line breaks.
+#[derive(Default)]
+struct CrLfNormalizer {
+    counts: LineBreakCounts,
+    /// Whether the last chunk ended with CR, which may be followed by LF in
+    /// the next chunk.
+    pending_cr: bool,
+    normalized: String,
+}
+
+impl CrLfNormalizer {
+    fn push<F: FnMut(&str)>(&mut self, chunk: &str, mut output: F) {
+        if !self.pending_cr && !chunk.contains('\r') {
+            self.counts.lf += chunk.matches('\n').count();
+            output(chunk);
+            return;
+        }
+
+        self.normalized.clear();
+        for ch in chunk.chars() {
+            if self.pending_cr {
+                self.pending_cr = false;
+                if ch == '\n' {
+                    self.counts.crlf += 1;
+                    self.normalized.push('\n');
+                    continue;
+                }
+
+                self.counts.cr += 1;
+                self.normalized.push('\r');
+            }
+
+            match ch {
+                '\r' => self.pending_cr = true,
+                '\n' => {
+                    self.counts.lf += 1;
+                    self.normalized.push('\n');
+                }
+                _ => self.normalized.push(ch),
+            }
+        }
+
+        output(&self.normalized);
+    }
+
+    fn finish<F: FnMut(&str)>(self, mut output: F) -> LineBreakCounts {
+        let mut counts = self.counts;
+        if self.pending_cr {
+            counts.cr += 1;
+            output("\r");
+        }
+
+        counts
+    }
+}
+
 impl From<ropey::Rope> for RawBuffer {
     fn from(rope: ropey::Rope) -> RawBuffer {
-        RawBuffer { rope }
+        RawBuffer { rope, revision: 0 }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::io::Read;
+
     use super::*;
 
+    #[test]
+    fn line_endings() {
+        let (buffer, eol) =
+            RawBuffer::from_reader_with_end_of_line("a\r\nb\r\n".as_bytes()).unwrap();
+        assert_eq!(buffer.text(), "a\nb\n");
+        assert_eq!(eol, Some(EndOfLine::CrLf));
+
+        let mut written = Vec::new();
+        buffer
+            .write_to_with_end_of_line(&mut written, EndOfLine::CrLf)
+            .unwrap();
+        assert_eq!(written, b"a\r\nb\r\n");
+
+        let (buffer, eol) = RawBuffer::from_reader_with_end_of_line("a\rb".as_bytes()).unwrap();
+        assert_eq!(buffer.text(), "a\nb");
+        assert_eq!(eol, Some(EndOfLine::Cr));
+
+        // A stray CR in a LF file is kept as is.
+        let (buffer, eol) =
+            RawBuffer::from_reader_with_end_of_line("a\rb\nc\r\nd\n".as_bytes()).unwrap();
+        assert_eq!(buffer.text(), "a\rb\nc\nd\n");
+        assert_eq!(eol, Some(EndOfLine::Lf));
+
+        let (_, eol) = RawBuffer::from_reader_with_end_of_line("abc".as_bytes()).unwrap();
+        assert_eq!(eol, None);
+
+        let (buffer, eol) = RawBuffer::from_reader_with_end_of_line("a\rb\r".as_bytes()).unwrap();
+        assert_eq!(buffer.text(), "a\nb\n");
+        assert_eq!(eol, Some(EndOfLine::Cr));
+    }
+
+    #[test]
+    fn line_endings_across_chunks() {
+        // Read one byte at a time to split CRLF and characters across reads.
+        struct OneByteReader<'a>(&'a [u8]);
+
+        impl Read for OneByteReader<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let len = buf.len().min(self.0.len()).min(1);
+                buf[..len].copy_from_slice(&self.0[..len]);
+                self.0 = &self.0[len..];
+                Ok(len)
+            }
+        }
+
+        let (buffer, eol) =
+            RawBuffer::from_reader_with_end_of_line(OneByteReader("あ\r\nい\r\n".as_bytes()))
+                .unwrap();
+        assert_eq!(buffer.text(), "あ\nい\n");
+        assert_eq!(eol, Some(EndOfLine::CrLf));
+
+        assert!(RawBuffer::from_reader_with_end_of_line(&b"a\xff"[..]).is_err());
+        assert!(RawBuffer::from_reader_with_end_of_line(&b"a\xe3\x81"[..]).is_err());
+    }
+
     #[test]
     fn test_substr() {
         let buffer = RawBuffer::from_text("...AB...");
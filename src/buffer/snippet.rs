@@ -0,0 +1,514 @@
+//! A parser for the LSP/TextMate snippet syntax:
+//!
+//! ```text
+//! fn ${1:name}(${2}) {
+//!     ${3|todo!(),unimplemented!()|}$0
+//! }
+//! ```
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Element {
+    Text(String),
+    /// `$1`, `${1}`, or `${1:placeholder}`.
+    Tabstop {
+        index: usize,
+        placeholder: Vec<Element>,
+    },
+    /// `${1|one,two,three|}`.
+    Choice {
+        index: usize,
+        choices: Vec<String>,
+    },
+    /// `$NAME`, `${NAME}`, or `${NAME:default}`.
+    Variable {
+        name: String,
+        default: Vec<Element>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snippet {
+    elements: Vec<Element>,
+}
+
+/// A tabstop in an expanded snippet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tabstop {
+    /// The character offsets of the tabstop (and its mirrors) in the text.
+    pub ranges: Vec<std::ops::Range<usize>>,
+    /// The choices if the tabstop is a choice.
+    pub choices: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpandedSnippet {
+    pub text: String,
+    /// Tabstops in the order to be visited: `$1`, `$2`, ..., and `$0`. The
+    /// final one always exists.
+    pub tabstops: Vec<Tabstop>,
+}
+
+impl Snippet {
+    /// Parses a snippet. Invalid syntax is treated as a plain text.
+    pub fn parse(text: &str) -> Snippet {
+        let mut parser = Parser {
+            chars: text.chars().collect(),
+            i: 0,
+        };
+
+        Snippet {
+            elements: parser.parse_elements(false),
+        }
+    }
+
+    /// Expands the snippet into the text to be inserted.
+    ///
+    /// `indent` is inserted after each newline so that lines are aligned to
+    /// the line where the snippet is inserted. `tab` replaces tab characters.
+    /// `resolve_variable` returns the value of a variable, or `None` if it's
+    /// not defined.
+    pub fn expand<F>(&self, indent: &str, tab: &str, resolve_variable: F) -> ExpandedSnippet
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        // Placeholders of tabstops are shared with their mirrors, e.g. `$1` in
+        // `${1:foo} $1`.
+        let mut placeholders = BTreeMap::new();
+        collect_placeholders(&self.elements, &mut placeholders);
+
+        let mut expander = Expander {
+            indent,
+            tab,
+            resolve_variable: &resolve_variable,
+            placeholders: &placeholders,
+            text: String::new(),
+            len_chars: 0,
+            tabstops: BTreeMap::new(),
+            tabstop_stack: Vec::new(),
+        };
+        expander.expand_elements(&self.elements);
+
+        let mut tabstops: Vec<Tabstop> = Vec::new();
+        let mut final_tabstop = None;
+        for (index, tabstop) in expander.tabstops {
+            if index == 0 {
+                final_tabstop = Some(tabstop);
+            } else {
+                tabstops.push(tabstop);
+            }
+        }
+
+        let len_chars = expander.len_chars;
+        tabstops.push(final_tabstop.unwrap_or_else(|| {
+            let mut tabstop = Tabstop {
+                ranges: Vec::new(),
+                choices: Vec::new(),
+            };
+            tabstop.ranges.push(len_chars..len_chars);
+            tabstop
+        }));
+
+        ExpandedSnippet {
+            text: expander.text,
+            tabstops,
+        }
+    }
+}
+
+fn collect_placeholders<'a>(
+    elements: &'a [Element],
+    placeholders: &mut BTreeMap<usize, &'a [Element]>,
+) {
+    for element in elements {
+        match element {
+            Element::Tabstop { index, placeholder } => {
+                if !placeholder.is_empty() {
+                    placeholders.entry(*index).or_insert(placeholder.as_slice());
+                }
+                collect_placeholders(placeholder, placeholders);
+            }
+            Element::Variable { default, .. } => {
+                collect_placeholders(default, placeholders);
+            }
+            Element::Text(_) | Element::Choice { .. } => {}
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    i: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.i).copied()
+    }
+
+    /// Parses elements until EOF or an unescaped `}` if `in_braces` is true.
+    fn parse_elements(&mut self, in_braces: bool) -> Vec<Element> {
+        let mut elements = Vec::new();
+        let mut text = String::new();
+        while let Some(ch) = self.peek() {
+            match ch {
+                '\\' if matches!(self.chars.get(self.i + 1), Some('$' | '}' | '\\')) => {
+                    text.push(self.chars[self.i + 1]);
+                    self.i += 2;
+                }
+                '}' if in_braces => {
+                    self.i += 1;
+                    break;
+                }
+                '$' => match self.parse_dollar() {
+                    Some(element) => {
+                        if !text.is_empty() {
+                            elements.push(Element::Text(std::mem::take(&mut text)));
+                        }
+                        elements.push(element);
+                    }
+                    None => {
+                        text.push('$');
+                        self.i += 1;
+                    }
+                },
+                _ => {
+                    text.push(ch);
+                    self.i += 1;
+                }
+            }
+        }
+
+        if !text.is_empty() {
+            elements.push(Element::Text(text));
+        }
+
+        elements
+    }
+
+    /// Parses an element starting with `$`. Returns `None` without consuming
+    /// anything if it's not a valid one.
+    fn parse_dollar(&mut self) -> Option<Element> {
+        let start = self.i;
+        self.i += 1;
+        let element = if self.peek() == Some('{') {
+            self.i += 1;
+            self.parse_braced()
+        } else if let Some(index) = self.parse_int() {
+            Some(Element::Tabstop {
+                index,
+                placeholder: Vec::new(),
+            })
+        } else {
+            self.parse_var_name().map(|name| Element::Variable {
+                name,
+                default: Vec::new(),
+            })
+        };
+
+        if element.is_none() {
+            self.i = start;
+        }
+
+        element
+    }
+
+    /// Parses the inside of `${...}`.
+    fn parse_braced(&mut self) -> Option<Element> {
+        if let Some(index) = self.parse_int() {
+            match self.peek()? {
+                '}' => {
+                    self.i += 1;
+                    Some(Element::Tabstop {
+                        index,
+                        placeholder: Vec::new(),
+                    })
+                }
+                ':' => {
+                    self.i += 1;
+                    Some(Element::Tabstop {
+                        index,
+                        placeholder: self.parse_elements(true),
+                    })
+                }
+                '|' => {
+                    self.i += 1;
+                    let choices = self.parse_choices()?;
+                    Some(Element::Choice { index, choices })
+                }
+                _ => None,
+            }
+        } else {
+            let name = self.parse_var_name()?;
+            match self.peek()? {
+                '}' => {
+                    self.i += 1;
+                    Some(Element::Variable {
+                        name,
+                        default: Vec::new(),
+                    })
+                }
+                ':' => {
+                    self.i += 1;
+                    Some(Element::Variable {
+                        name,
+                        default: self.parse_elements(true),
+                    })
+                }
+                '/' => {
+                    // Transformations are not supported: use the value as is.
+                    while let Some(ch) = self.peek() {
+                        self.i += 1;
+                        match ch {
+                            '\\' => self.i += 1,
+                            '}' => {
+                                return Some(Element::Variable {
+                                    name,
+                                    default: Vec::new(),
+                                })
+                            }
+                            _ => {}
+                        }
+                    }
+                    None
+                }
+                _ => None,
+            }
+        }
+    }
+
+    /// Parses `one,two|}`.
+    fn parse_choices(&mut self) -> Option<Vec<String>> {
+        let mut choices = Vec::new();
+        let mut choice = String::new();
+        loop {
+            match self.peek()? {
+                '\\' if matches!(
+                    self.chars.get(self.i + 1),
+                    Some('$' | '}' | '\\' | ',' | '|')
+                ) =>
+                {
+                    choice.push(self.chars[self.i + 1]);
+                    self.i += 2;
+                }
+                ',' => {
+                    choices.push(std::mem::take(&mut choice));
+                    self.i += 1;
+                }
+                '|' if self.chars.get(self.i + 1) == Some(&'}') => {
+                    choices.push(choice);
+                    self.i += 2;
+                    return Some(choices);
+                }
+                ch => {
+                    choice.push(ch);
+                    self.i += 1;
+                }
+            }
+        }
+    }
+
+    fn parse_int(&mut self) -> Option<usize> {
+        let start = self.i;
+        while matches!(self.peek(), Some('0'..='9')) {
+            self.i += 1;
+        }
+
+        self.chars[start..self.i]
+            .iter()
+            .collect::<String>()
+            .parse()
+            .ok()
+    }
+
+    fn parse_var_name(&mut self) -> Option<String> {
+        if !matches!(self.peek(), Some('a'..='z' | 'A'..='Z' | '_')) {
+            return None;
+        }
+
+        let start = self.i;
+        while matches!(self.peek(), Some('a'..='z' | 'A'..='Z' | '0'..='9' | '_')) {
+            self.i += 1;
+        }
+
+        Some(self.chars[start..self.i].iter().collect())
+    }
+}
+
+struct Expander<'a> {
+    indent: &'a str,
+    tab: &'a str,
+    resolve_variable: &'a dyn Fn(&str) -> Option<String>,
+    placeholders: &'a BTreeMap<usize, &'a [Element]>,
+    text: String,
+    len_chars: usize,
+    tabstops: BTreeMap<usize, Tabstop>,
+    /// The tabstops being expanded to avoid infinite recursion in mirrors.
+    tabstop_stack: Vec<usize>,
+}
+
+impl<'a> Expander<'a> {
+    fn push_str(&mut self, s: &str) {
+        for ch in s.chars() {
+            match ch {
+                '\n' => {
+                    self.text.push('\n');
+                    self.text.push_str(self.indent);
+                    self.len_chars += 1 + self.indent.chars().count();
+                }
+                '\t' => {
+                    self.text.push_str(self.tab);
+                    self.len_chars += self.tab.chars().count();
+                }
+                _ => {
+                    self.text.push(ch);
+                    self.len_chars += 1;
+                }
+            }
+        }
+    }
+
+    fn add_tabstop(&mut self, index: usize, range: std::ops::Range<usize>, choices: &[String]) {
+        let tabstop = self.tabstops.entry(index).or_insert_with(|| Tabstop {
+            ranges: Vec::new(),
+            choices: Vec::new(),
+        });
+
+        tabstop.ranges.push(range);
+        if tabstop.choices.is_empty() {
+            tabstop.choices = choices.to_vec();
+        }
+    }
+
+    fn expand_elements(&mut self, elements: &[Element]) {
+        for element in elements {
+            match element {
+                Element::Text(text) => {
+                    self.push_str(text);
+                }
+                Element::Tabstop { index, placeholder } => {
+                    let start = self.len_chars;
+                    if !self.tabstop_stack.contains(index) {
+                        let placeholder = if placeholder.is_empty() {
+                            self.placeholders.get(index).copied().unwrap_or_default()
+                        } else {
+                            placeholder.as_slice()
+                        };
+
+                        self.tabstop_stack.push(*index);
+                        self.expand_elements(placeholder);
+                        self.tabstop_stack.pop();
+                    }
+
+                    self.add_tabstop(*index, start..self.len_chars, &[]);
+                }
+                Element::Choice { index, choices } => {
+                    let start = self.len_chars;
+                    self.push_str(choices.first().map(String::as_str).unwrap_or(""));
+                    self.add_tabstop(*index, start..self.len_chars, choices);
+                }
+                Element::Variable { name, default } => match (self.resolve_variable)(name) {
+                    Some(value) => self.push_str(&value),
+                    None => self.expand_elements(default),
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn expand(snippet: &str) -> ExpandedSnippet {
+        Snippet::parse(snippet).expand("  ", "\t", |name| match name {
+            "TM_FILENAME" => Some("main.rs".to_owned()),
+            _ => None,
+        })
+    }
+
+    fn tabstop(ranges: &[(usize, usize)]) -> Tabstop {
+        Tabstop {
+            ranges: ranges.iter().map(|(start, end)| *start..*end).collect(),
+            choices: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn plain_text() {
+        assert_eq!(
+            expand("abc"),
+            ExpandedSnippet {
+                text: "abc".to_owned(),
+                tabstops: vec![tabstop(&[(3, 3)])],
+            }
+        );
+
+        // Invalid or escaped syntax.
+        assert_eq!(expand("$ ${ \\$1 \\} ${1").text, "$ ${ $1 } ${1");
+    }
+
+    #[test]
+    fn tabstops_and_placeholders() {
+        assert_eq!(
+            expand("fn ${1:name}($2) {$0}"),
+            ExpandedSnippet {
+                text: "fn name() {}".to_owned(),
+                tabstops: vec![tabstop(&[(3, 7)]), tabstop(&[(8, 8)]), tabstop(&[(11, 11)])],
+            }
+        );
+
+        // Nested placeholders and mirrors.
+        assert_eq!(
+            expand("${1:a ${2:b}} $1 ${2}"),
+            ExpandedSnippet {
+                text: "a b a b b".to_owned(),
+                tabstops: vec![
+                    tabstop(&[(0, 3), (4, 7)]),
+                    tabstop(&[(2, 3), (6, 7), (8, 9)]),
+                    tabstop(&[(9, 9)]),
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn choices() {
+        assert_eq!(
+            expand("${1|one,t\\,wo|}"),
+            ExpandedSnippet {
+                text: "one".to_owned(),
+                tabstops: vec![
+                    Tabstop {
+                        choices: vec!["one".to_owned(), "t,wo".to_owned()],
+                        ..tabstop(&[(0, 3)])
+                    },
+                    tabstop(&[(3, 3)]),
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn variables() {
+        assert_eq!(
+            expand("$TM_FILENAME ${TM_FILENAME}").text,
+            "main.rs main.rs"
+        );
+        assert_eq!(expand("${UNKNOWN:${1:default}}x").text, "defaultx");
+        assert_eq!(expand("${TM_FILENAME/(.*)/$1/}").text, "main.rs");
+    }
+
+    #[test]
+    fn indentation() {
+        assert_eq!(
+            expand("{\n\t$0\n}"),
+            ExpandedSnippet {
+                text: "{\n  \t\n  }".to_owned(),
+                tabstops: vec![tabstop(&[(5, 5)])],
+            }
+        );
+    }
+}
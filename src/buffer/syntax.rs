@@ -1,4 +1,4 @@
-use std::ops::ControlFlow;
+use std::{collections::HashMap, ops::ControlFlow};
 
 use crate::{
     cursor::{Position, Range},
@@ -8,10 +8,10 @@ use crate::{
 
 use noa_languages::{
     tree_sitter::{
-        self, get_highlights_query, get_tree_sitter_parser, InputEdit, Node, QueryCursor,
-        TextProvider,
+        self, get_folds_query, get_highlights_query, get_indents_query, get_injections_query,
+        get_textobjects_query, get_tree_sitter_parser, InputEdit, Node, QueryCursor, TextProvider,
     },
-    Language,
+    Language, LANGUAGES,
 };
 
 struct RopeByteChunks<'a>(ropey::iter::Chunks<'a>);
@@ -95,6 +95,76 @@ impl Query {
             }
         }
     }
+
+    /// Queries embedded languages from `@language` and `@content` captures
+    /// (or `@injection.language` and `@injection.content`). The language can
+    /// also be specified by `#set! injection.language` or the capture name
+    /// itself (e.g. `@regex`).
+    ///
+    /// The callback receives the content ranges of each match, and whether
+    /// they should be parsed together with other sites of the same language
+    /// (`#set! injection.combined`).
+    pub fn injections<F>(&self, tree: &tree_sitter::Tree, buffer: &RawBuffer, mut callback: F)
+    where
+        F: FnMut(&'static Language, Vec<tree_sitter::Range>, bool),
+    {
+        let mut cursor = QueryCursor::new();
+        let matches = cursor.matches(&self.raw_query, tree.root_node(), RopeTextProvider(buffer));
+        for m in matches {
+            let mut lang_name = self
+                .raw_query
+                .property_settings(m.pattern_index)
+                .iter()
+                .find(|prop| &*prop.key == "injection.language" || &*prop.key == "language")
+                .and_then(|prop| prop.value.as_deref())
+                .map(|value| value.to_owned());
+            let combined = self
+                .raw_query
+                .property_settings(m.pattern_index)
+                .iter()
+                .any(|prop| &*prop.key == "injection.combined");
+
+            let mut contents = Vec::new();
+            for cap in m.captures {
+                match self.raw_query.capture_names()[cap.index as usize].as_str() {
+                    "language" | "injection.language" => {
+                        lang_name = Some(buffer.substr(cap.node.buffer_range()));
+                    }
+                    "content" | "injection.content" => {
+                        contents.push(cap.node.range());
+                    }
+                    name if find_injected_language(name).is_some() => {
+                        lang_name = Some(name.to_owned());
+                        contents.push(cap.node.range());
+                    }
+                    _ => {}
+                }
+            }
+
+            if contents.is_empty() {
+                continue;
+            }
+
+            if let Some(lang) = lang_name.and_then(|name| find_injected_language(name.trim())) {
+                callback(lang, contents, combined);
+            }
+        }
+    }
+}
+
+/// Looks for a language by its name or file extension (e.g. `js` in a fenced
+/// code block in Markdown).
+fn find_injected_language(name: &str) -> Option<&'static Language> {
+    let name = name.to_ascii_lowercase();
+    LANGUAGES
+        .iter()
+        .find(|lang| lang.name == name)
+        .or_else(|| {
+            LANGUAGES
+                .iter()
+                .find(|lang| lang.extensions.contains(&name.as_str()))
+        })
+        .filter(|lang| lang.tree_sitter.is_some())
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -105,14 +175,88 @@ pub enum ParserError {
     ParseError,
 }
 
+/// A syntax tree of a language embedded in another one, e.g. JavaScript in
+/// `<script>` in HTML.
+#[derive(Clone)]
+pub struct InjectedTree {
+    pub lang: &'static Language,
+    pub tree: tree_sitter::Tree,
+}
+
+/// The syntax trees parsed by [`SyntaxParser`].
+#[derive(Clone)]
+pub struct SyntaxTrees {
+    pub tree: tree_sitter::Tree,
+    pub injected_trees: Vec<InjectedTree>,
+    /// The revision of the buffer the trees are parsed from.
+    pub revision: Option<u64>,
+}
+
+/// Parses an injected language. Each injection site is parsed as its own tree
+/// unless the query asks to combine all sites of the language into one.
+struct InjectionLayer {
+    lang: &'static Language,
+    combined: bool,
+    /// The start of the first range `tree` is parsed from, adjusted for edits.
+    /// Used to find the layer of the same site after the buffer is modified.
+    start_byte: usize,
+    parser: tree_sitter::Parser,
+    tree: Option<tree_sitter::Tree>,
+    injections_query: Option<Query>,
+}
+
+impl InjectionLayer {
+    fn new(lang: &'static Language, combined: bool) -> Option<InjectionLayer> {
+        let ts_lang = get_tree_sitter_parser(lang.name)?;
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(ts_lang).ok()?;
+        Some(InjectionLayer {
+            lang,
+            combined,
+            start_byte: 0,
+            parser,
+            tree: None,
+            injections_query: load_optional_query(lang, ts_lang, get_injections_query(lang.name)),
+        })
+    }
+
+    /// Whether this layer can be reused (and updated incrementally) for
+    /// `injection`.
+    fn is_reusable_for(&self, injection: &PendingInjection) -> bool {
+        self.lang.name == injection.lang.name
+            && self.combined == injection.combined
+            && (self.combined || self.start_byte == injection.ranges[0].start_byte)
+    }
+
+    fn edit(&mut self, edit: &InputEdit) {
+        if let Some(tree) = self.tree.as_mut() {
+            tree.edit(edit);
+        }
+
+        if self.start_byte >= edit.old_end_byte {
+            self.start_byte = self.start_byte - edit.old_end_byte + edit.new_end_byte;
+        } else if self.start_byte > edit.new_end_byte {
+            self.start_byte = edit.new_end_byte;
+        }
+    }
+}
+
+/// How deep injected languages are searched for (e.g. a regex in JavaScript in
+/// HTML).
+const INJECTION_DEPTH_MAX: usize = 3;
+
 pub struct SyntaxParser {
     parser: tree_sitter::Parser,
     ts_lang: tree_sitter::Language,
     tree: tree_sitter::Tree,
+    injections_query: Option<Query>,
+    injection_layers: Vec<InjectionLayer>,
+    /// The revision of the buffer parsed last time.
+    revision: Option<u64>,
 }
 
 impl SyntaxParser {
-    pub fn new(lang: &Language) -> Result<SyntaxParser, ParserError> {
+    pub fn new(lang: &'static Language) -> Result<SyntaxParser, ParserError> {
         let mut parser = tree_sitter::Parser::new();
         let ts_lang = get_tree_sitter_parser(lang.name).ok_or(ParserError::NotSupportedLanguage)?;
         parser
@@ -123,6 +267,9 @@ impl SyntaxParser {
             tree: parser.parse("", None).ok_or(ParserError::ParseError)?,
             ts_lang,
             parser,
+            injections_query: load_optional_query(lang, ts_lang, get_injections_query(lang.name)),
+            injection_layers: Vec::new(),
+            revision: None,
         })
     }
 
@@ -130,6 +277,24 @@ impl SyntaxParser {
         &self.tree
     }
 
+    /// Returns the syntax tree and the ones of injected languages.
+    pub fn trees(&self) -> SyntaxTrees {
+        SyntaxTrees {
+            tree: self.tree.clone(),
+            injected_trees: self
+                .injection_layers
+                .iter()
+                .filter_map(|layer| {
+                    layer.tree.as_ref().map(|tree| InjectedTree {
+                        lang: layer.lang,
+                        tree: tree.clone(),
+                    })
+                })
+                .collect(),
+            revision: self.revision,
+        }
+    }
+
     pub fn parse_fully(&mut self, buffer: &RawBuffer) {
         self.parse(buffer, None);
     }
@@ -139,43 +304,186 @@ impl SyntaxParser {
     }
 
     fn parse(&mut self, buffer: &RawBuffer, changes: Option<&[Change]>) {
-        let rope = buffer.rope();
-        let mut callback = |i, _| {
-            if i > rope.len_bytes() {
-                return &[] as &[u8];
-            }
-
-            let (chunk, start, _, _) = rope.chunk_at_byte(i);
-            chunk[i - start..].as_bytes()
-        };
-
         let old_tree = if let Some(changes) = changes {
             // Tell tree-sitter about the changes we made since the last parsing.
             for change in changes {
-                self.tree.edit(&InputEdit {
+                let edit = InputEdit {
                     start_byte: change.byte_range.start,
                     old_end_byte: change.byte_range.end,
                     new_end_byte: change.byte_range.start + change.insert_text.len(),
                     start_position: change.range.front().into(),
                     old_end_position: change.range.back().into(),
                     new_end_position: change.new_pos.into(),
-                });
+                };
+
+                self.tree.edit(&edit);
+                for layer in &mut self.injection_layers {
+                    layer.edit(&edit);
+                }
             }
 
             Some(&self.tree)
         } else {
+            for layer in &mut self.injection_layers {
+                layer.tree = None;
+            }
+
             None
         };
 
-        if let Some(new_tree) = self.parser.parse_with(&mut callback, old_tree) {
+        if let Some(new_tree) = parse_rope(&mut self.parser, buffer, old_tree) {
             self.tree = new_tree;
         }
+
+        self.parse_injections(buffer);
+        self.revision = Some(buffer.revision());
+    }
+
+    /// Parses the ranges of injected languages in the current syntax tree
+    /// (and ones injected in them).
+    fn parse_injections(&mut self, buffer: &RawBuffer) {
+        let mut injections = Vec::new();
+        if let Some(query) = self.injections_query.as_ref() {
+            query.injections(&self.tree, buffer, |lang, ranges, combined| {
+                add_injection(&mut injections, 0, lang, ranges, combined);
+            });
+        }
+
+        // Layers not reused for the current injections are dropped.
+        let mut old_layers = std::mem::take(&mut self.injection_layers);
+        let mut i = 0;
+        while i < injections.len() {
+            let mut injection = injections[i].clone();
+            i += 1;
+
+            normalize_included_ranges(&mut injection.ranges);
+            let mut layer = match old_layers
+                .iter()
+                .position(|layer| layer.is_reusable_for(&injection))
+            {
+                Some(index) => old_layers.swap_remove(index),
+                None => match InjectionLayer::new(injection.lang, injection.combined) {
+                    Some(layer) => layer,
+                    None => continue,
+                },
+            };
+
+            if layer.parser.set_included_ranges(&injection.ranges).is_err() {
+                warn!("invalid injection ranges for {}", injection.lang.name);
+                continue;
+            }
+
+            layer.start_byte = injection.ranges[0].start_byte;
+            layer.tree = parse_rope(&mut layer.parser, buffer, layer.tree.as_ref());
+            if let (Some(query), Some(tree)) = (layer.injections_query.as_ref(), &layer.tree) {
+                query.injections(tree, buffer, |lang, ranges, combined| {
+                    add_injection(&mut injections, injection.depth + 1, lang, ranges, combined);
+                });
+            }
+
+            self.injection_layers.push(layer);
+        }
+    }
+}
+
+#[derive(Clone)]
+struct PendingInjection {
+    lang: &'static Language,
+    ranges: Vec<tree_sitter::Range>,
+    combined: bool,
+    depth: usize,
+}
+
+/// Adds injected ranges found at `depth`. Injections nested too deeply are
+/// ignored to avoid an infinite loop (e.g. Rust in Rust macros).
+fn add_injection(
+    injections: &mut Vec<PendingInjection>,
+    depth: usize,
+    lang: &'static Language,
+    ranges: Vec<tree_sitter::Range>,
+    combined: bool,
+) {
+    if depth >= INJECTION_DEPTH_MAX {
+        return;
     }
+
+    if combined {
+        if let Some(injection) = injections.iter_mut().find(|injection| {
+            injection.combined && injection.depth == depth && injection.lang.name == lang.name
+        }) {
+            injection.ranges.extend(ranges);
+            return;
+        }
+    }
+
+    injections.push(PendingInjection {
+        lang,
+        ranges,
+        combined,
+        depth,
+    });
+}
+
+/// Sorts ranges and merges overlapping ones since included ranges must be
+/// sorted and must not overlap. The outer range is kept if ranges are nested.
+fn normalize_included_ranges(ranges: &mut Vec<tree_sitter::Range>) {
+    ranges.sort_by_key(|range| (range.start_byte, std::cmp::Reverse(range.end_byte)));
+    ranges.dedup_by(|next, prev| {
+        if next.start_byte >= prev.end_byte {
+            return false;
+        }
+
+        if next.end_byte > prev.end_byte {
+            prev.end_byte = next.end_byte;
+            prev.end_point = next.end_point;
+        }
+        true
+    });
+}
+
+fn parse_rope(
+    parser: &mut tree_sitter::Parser,
+    buffer: &RawBuffer,
+    old_tree: Option<&tree_sitter::Tree>,
+) -> Option<tree_sitter::Tree> {
+    let rope = buffer.rope();
+    let mut callback = |i, _| {
+        if i > rope.len_bytes() {
+            return &[] as &[u8];
+        }
+
+        let (chunk, start, _, _) = rope.chunk_at_byte(i);
+        chunk[i - start..].as_bytes()
+    };
+
+    parser.parse_with(&mut callback, old_tree)
 }
 
 pub struct Syntax {
     tree: tree_sitter::Tree,
+    /// The revision of the buffer `tree` is parsed from. `None` if unknown.
+    revision: Option<u64>,
     highlight_query: Query,
+    indents_query: Option<Query>,
+    textobjects_query: Option<Query>,
+    folds_query: Option<Query>,
+    injected_trees: Vec<InjectedTree>,
+    /// Highlight queries of injected languages. `None` if failed to load.
+    injected_highlight_queries: HashMap<&'static str, Option<Query>>,
+}
+
+/// Loads a query which is not mandatory for editing, that is, errors are
+/// only logged.
+fn load_optional_query(
+    lang: &'static Language,
+    ts_lang: tree_sitter::Language,
+    query_str: Option<&str>,
+) -> Option<Query> {
+    query_str.and_then(|query_str| {
+        Query::new(ts_lang, query_str)
+            .map_err(|err| warn!("failed to load a query for {}: {:?}", lang.name, err))
+            .ok()
+    })
 }
 
 impl Syntax {
@@ -186,10 +494,20 @@ impl Syntax {
             get_highlights_query(lang.name).unwrap_or(""),
         )
         .map_err(ParserError::QueryError)?;
+        let indents_query = load_optional_query(lang, parser.ts_lang, get_indents_query(lang.name));
+        let textobjects_query =
+            load_optional_query(lang, parser.ts_lang, get_textobjects_query(lang.name));
+        let folds_query = load_optional_query(lang, parser.ts_lang, get_folds_query(lang.name));
 
         Ok(Syntax {
             tree: parser.tree,
+            revision: None,
             highlight_query,
+            indents_query,
+            textobjects_query,
+            folds_query,
+            injected_trees: Vec::new(),
+            injected_highlight_queries: HashMap::new(),
         })
     }
 
@@ -197,8 +515,32 @@ impl Syntax {
         &self.tree
     }
 
-    pub fn set_tree(&mut self, tree: tree_sitter::Tree) {
+    /// Replaces the syntax tree. `revision` is the one of the buffer the tree
+    /// is parsed from.
+    pub fn set_tree(&mut self, tree: tree_sitter::Tree, revision: Option<u64>) {
         self.tree = tree;
+        self.revision = revision;
+    }
+
+    /// Returns `true` if the syntax tree is parsed from `buffer` as it is,
+    /// that is, positions in the tree match the buffer.
+    pub fn is_up_to_date(&self, buffer: &RawBuffer) -> bool {
+        self.revision == Some(buffer.revision())
+    }
+
+    pub fn set_injected_trees(&mut self, injected_trees: Vec<InjectedTree>) {
+        for injected in &injected_trees {
+            let lang = injected.lang;
+            self.injected_highlight_queries
+                .entry(lang.name)
+                .or_insert_with(|| {
+                    get_tree_sitter_parser(lang.name).and_then(|ts_lang| {
+                        load_optional_query(lang, ts_lang, get_highlights_query(lang.name))
+                    })
+                });
+        }
+
+        self.injected_trees = injected_trees;
     }
 
     pub fn query_highlight<F>(&self, buffer: &RawBuffer, range: Range, mut callback: F)
@@ -207,6 +549,125 @@ impl Syntax {
     {
         self.highlight_query
             .query(self.tree(), buffer, Some(range), &mut callback);
+
+        // Highlight injected languages after the host language so that their
+        // spans take precedence.
+        for injected in &self.injected_trees {
+            if let Some(Some(query)) = self.injected_highlight_queries.get(injected.lang.name) {
+                query.query(&injected.tree, buffer, Some(range), &mut callback);
+            }
+        }
+    }
+
+    /// Queries text objects like `@function.outer` in the whole buffer. Does
+    /// nothing if the language has no textobjects query.
+    pub fn query_textobjects<F>(&self, buffer: &RawBuffer, mut callback: F)
+    where
+        F: FnMut(Range, &str),
+    {
+        if let Some(query) = self.textobjects_query.as_ref() {
+            query.query(self.tree(), buffer, None, &mut callback);
+        }
+    }
+
+    /// Queries `@fold` nodes in the whole buffer. Returns `false` if the
+    /// language has no folds query.
+    pub fn query_folds<F>(&self, buffer: &RawBuffer, mut callback: F) -> bool
+    where
+        F: FnMut(Range),
+    {
+        match self.folds_query.as_ref() {
+            Some(query) => {
+                query.query(self.tree(), buffer, None, |range, capture| {
+                    if capture == "fold" {
+                        callback(range);
+                    }
+                });
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Computes how many levels the line after a line break at `break_pos`
+    /// should be indented compared to the line of `break_pos` from `@indent`
+    /// and `@outdent` captures. `first_pos` is the position of the first
+    /// non-whitespace character in the new line.
+    ///
+    /// Returns `None` if the language has no indents query or the syntax tree
+    /// does not cover the position yet.
+    pub fn indent_level_change(
+        &self,
+        buffer: &RawBuffer,
+        break_pos: Position,
+        first_pos: Position,
+    ) -> Option<isize> {
+        let query = self.indents_query.as_ref()?;
+        let root = self.tree.root_node();
+        if root.buffer_range().back() < break_pos {
+            return None;
+        }
+
+        let mut indent = false;
+        let mut outdent = false;
+        let mut cursor = QueryCursor::new();
+        cursor.set_point_range(Range::new(break_pos.y, 0, first_pos.y, first_pos.x + 1).into());
+        for m in cursor.matches(&query.raw_query, root, RopeTextProvider(buffer)) {
+            for cap in m.captures {
+                let range = cap.node.buffer_range();
+                match query.raw_query.capture_names()[cap.index as usize].as_str() {
+                    "indent" | "indent.begin" => {
+                        // An incomplete node (e.g. `if foo {` without `}`) ends
+                        // at the line break.
+                        indent |= range.front().y == break_pos.y
+                            && range.front() < break_pos
+                            && (range.back() > break_pos
+                                || (range.back() == break_pos && cap.node.has_error()));
+                    }
+                    "outdent" | "branch" | "indent.branch" | "indent_end" | "indent.end" => {
+                        outdent |= range.front() == first_pos;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Some(indent as isize - outdent as isize)
+    }
+
+    /// Returns the line where the innermost `@indent` node containing `pos`
+    /// begins, or `Some(None)` if there is no such node.
+    ///
+    /// Returns `None` if the language has no indents query or the syntax tree
+    /// does not cover the position yet.
+    pub fn enclosing_indent_line(
+        &self,
+        buffer: &RawBuffer,
+        pos: Position,
+    ) -> Option<Option<usize>> {
+        let query = self.indents_query.as_ref()?;
+        let root = self.tree.root_node();
+        if root.buffer_range().back() < pos {
+            return None;
+        }
+
+        let mut innermost: Option<Range> = None;
+        query.query(
+            &self.tree,
+            buffer,
+            Some(Range::new(pos.y, pos.x, pos.y, pos.x + 1)),
+            |range, capture| {
+                if (capture == "indent" || capture == "indent.begin")
+                    && range.front().y < pos.y
+                    && range.back() >= pos
+                    && !matches!(innermost, Some(inner) if inner.front() >= range.front())
+                {
+                    innermost = Some(range);
+                }
+            },
+        );
+
+        Some(innermost.map(|range| range.front().y))
     }
 
     pub fn words<F>(&self, mut callback: F)
@@ -300,3 +761,146 @@ impl<'tree> TsNodeExt for tree_sitter::Node<'tree> {
         Range::from_positions(start_pos, end_pos)
     }
 }
+
+/// Returns a buffer in the language `lang` with the syntax tree parsed
+/// synchronously.
+#[cfg(test)]
+pub(crate) fn parsed_buffer(lang: &str, text: &str) -> crate::buffer::Buffer {
+    let lang = noa_languages::get_language_by_name(lang).unwrap();
+    let mut buffer = crate::buffer::Buffer::from_text(text);
+    buffer.set_language(lang).unwrap();
+    let mut parser = SyntaxParser::new(lang).unwrap();
+    parser.parse_fully(buffer.raw_buffer());
+    buffer.set_syntax_tree(parser.tree().clone());
+    buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use noa_languages::get_language_by_name;
+    use pretty_assertions::assert_eq;
+
+    use crate::mut_raw_buffer::MutRawBuffer;
+
+    use super::*;
+
+    fn rust_parser_with_injections(query: &str) -> SyntaxParser {
+        let lang = get_language_by_name("rust").unwrap();
+        let mut parser = SyntaxParser::new(lang).unwrap();
+        parser.injections_query = Some(Query::new(parser.ts_lang, query).unwrap());
+        parser
+    }
+
+    fn node_kind_at(trees: &SyntaxTrees, index: usize, range: Range) -> String {
+        trees.injected_trees[index]
+            .tree
+            .root_node()
+            .named_descendant_for_point_range(range.front().into(), range.back().into())
+            .unwrap()
+            .kind()
+            .to_owned()
+    }
+
+    #[test]
+    fn parse_injections() {
+        let mut parser = rust_parser_with_injections(
+            r#"((macro_invocation (token_tree) @content) (#set! injection.language "rust"))"#,
+        );
+
+        let mut buf = MutRawBuffer::from_text("m!(1);\nfn f() {}\nm!(2);");
+        parser.parse_fully(&buf);
+        let trees = parser.trees();
+        assert_eq!(trees.injected_trees.len(), 2);
+        assert_eq!(trees.injected_trees[0].lang.name, "rust");
+        assert_eq!(
+            node_kind_at(&trees, 0, Range::new(0, 3, 0, 4)),
+            "integer_literal"
+        );
+        assert_eq!(
+            node_kind_at(&trees, 1, Range::new(2, 3, 2, 4)),
+            "integer_literal"
+        );
+
+        // Update the injected trees incrementally.
+        buf.edit(Range::new(0, 3, 0, 4), "abc");
+        let changes = buf.clear_changes();
+        parser.parse_incrementally(&buf, &changes);
+        let trees = parser.trees();
+        assert_eq!(trees.injected_trees.len(), 2);
+        assert_eq!(
+            node_kind_at(&trees, 0, Range::new(0, 3, 0, 6)),
+            "identifier"
+        );
+        assert_eq!(
+            node_kind_at(&trees, 1, Range::new(2, 3, 2, 4)),
+            "integer_literal"
+        );
+
+        // No injections anymore.
+        buf.edit(Range::new(0, 0, 2, 6), "");
+        let changes = buf.clear_changes();
+        parser.parse_incrementally(&buf, &changes);
+        assert!(parser.trees().injected_trees.is_empty());
+    }
+
+    #[test]
+    fn parse_combined_injections() {
+        let mut parser = rust_parser_with_injections(
+            r#"((macro_invocation (token_tree) @content)
+                (#set! injection.language "rust")
+                (#set! injection.combined))"#,
+        );
+
+        let buf = MutRawBuffer::from_text("m!(1);\nfn f() {}\nm!(2);");
+        parser.parse_fully(&buf);
+        let trees = parser.trees();
+        assert_eq!(trees.injected_trees.len(), 1);
+        assert_eq!(
+            node_kind_at(&trees, 0, Range::new(2, 3, 2, 4)),
+            "integer_literal"
+        );
+    }
+
+    fn ts_range(start: usize, end: usize) -> tree_sitter::Range {
+        tree_sitter::Range {
+            start_byte: start,
+            end_byte: end,
+            start_point: tree_sitter::Point::new(0, start),
+            end_point: tree_sitter::Point::new(0, end),
+        }
+    }
+
+    #[test]
+    fn normalize_included_ranges() {
+        let mut ranges = vec![
+            ts_range(5, 8),
+            ts_range(0, 4),
+            ts_range(1, 2),
+            ts_range(6, 10),
+        ];
+        super::normalize_included_ranges(&mut ranges);
+        assert_eq!(ranges, vec![ts_range(0, 4), ts_range(5, 10)]);
+    }
+
+    #[test]
+    fn add_nested_injections() {
+        let lang = get_language_by_name("rust").unwrap();
+        let range = ts_range(0, 1);
+
+        // The same language injected again at a deeper level is parsed too,
+        // up to INJECTION_DEPTH_MAX levels.
+        let mut injections = Vec::new();
+        for depth in 0..=INJECTION_DEPTH_MAX {
+            add_injection(&mut injections, depth, lang, vec![range], false);
+        }
+        assert_eq!(injections.len(), INJECTION_DEPTH_MAX);
+
+        // Combined injections are merged only at the same level.
+        let mut injections = Vec::new();
+        add_injection(&mut injections, 0, lang, vec![range], true);
+        add_injection(&mut injections, 0, lang, vec![range], true);
+        add_injection(&mut injections, 1, lang, vec![range], true);
+        assert_eq!(injections.len(), 2);
+        assert_eq!(injections[0].ranges.len(), 2);
+    }
+}
@@ -0,0 +1,698 @@
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cursor::{CursorSet, Range},
+    mut_raw_buffer::{Change, MutRawBuffer, ReversibleChange},
+    raw_buffer::RawBuffer,
+};
+
+/// The default upper bound of the memory used by the undo history.
+const DEFAULT_MAX_BYTES: usize = 64 * 1024 * 1024;
+
+/// Consecutive typing saved within this duration is merged into a single
+/// undo step.
+const COALESCE_WINDOW: Duration = Duration::from_secs(1);
+
+struct UndoNode {
+    /// Changes from the parent state to this state. Empty in the root node.
+    changes: Vec<ReversibleChange>,
+    cursors: CursorSet,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    /// The child visited most recently. `redo` follows this one.
+    last_child: Option<usize>,
+    created_at: SystemTime,
+    updated_at: SystemTime,
+}
+
+impl UndoNode {
+    fn size(&self) -> usize {
+        self.changes
+            .iter()
+            .map(|c| {
+                2 * std::mem::size_of::<Change>()
+                    + c.redo.insert_text.len()
+                    + c.undo.insert_text.len()
+            })
+            .sum()
+    }
+}
+
+/// A serializable form of the undo history.
+#[derive(Serialize, Deserialize)]
+pub struct UndoHistory {
+    nodes: Vec<UndoHistoryNode>,
+    current: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct UndoHistoryNode {
+    changes: Vec<ReversibleChange>,
+    /// The main cursor comes first.
+    selections: Vec<Range>,
+    parent: Option<usize>,
+    last_child: Option<usize>,
+    created_at: SystemTime,
+    updated_at: SystemTime,
+}
+
+/// Applies `changes` to `buf` in order. Returns `None` if any of them is out
+/// of the text.
+fn apply_changes<'a, I>(buf: &RawBuffer, changes: I) -> Option<RawBuffer>
+where
+    I: Iterator<Item = &'a Change>,
+{
+    let mut buf = MutRawBuffer::from_raw_buffer(buf.clone());
+    for change in changes {
+        if !buf.is_valid_range(change.range) {
+            return None;
+        }
+        buf.edit(change.range, &change.insert_text);
+    }
+    Some(buf.raw_buffer().clone())
+}
+
+/// Changes to be applied to move from a state to another one.
+pub(crate) struct UndoPath<'a> {
+    /// Changes to be applied in order.
+    pub changes: Vec<&'a Change>,
+    /// Cursors in the destination state.
+    pub cursors: &'a CursorSet,
+}
+
+/// A branching undo history.
+///
+/// Each node holds the changes from its parent instead of a whole snapshot,
+/// so moving between nodes replays changes along the path in the tree.
+///
+/// Nodes are stored in the order they were created, so the index of a node is
+/// also its position in the timeline. The root node is always at index 0.
+pub(crate) struct UndoTree {
+    nodes: Vec<UndoNode>,
+    current: Option<usize>,
+    num_bytes: usize,
+    max_bytes: usize,
+}
+
+impl UndoTree {
+    pub fn new() -> UndoTree {
+        UndoTree::with_max_bytes(DEFAULT_MAX_BYTES)
+    }
+
+    pub fn with_max_bytes(max_bytes: usize) -> UndoTree {
+        UndoTree {
+            nodes: Vec::new(),
+            current: None,
+            num_bytes: 0,
+            max_bytes,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn to_history(&self) -> Option<UndoHistory> {
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|node| UndoHistoryNode {
+                changes: node.changes.clone(),
+                selections: node.cursors.selections(),
+                parent: node.parent,
+                last_child: node.last_child,
+                created_at: node.created_at,
+                updated_at: node.updated_at,
+            })
+            .collect();
+
+        Some(UndoHistory {
+            nodes,
+            current: self.current?,
+        })
+    }
+
+    /// `buf` is the text at the current state. Returns `None` if the history
+    /// is malformed or any change or cursor in it does not fit in the text.
+    pub fn from_history(history: UndoHistory, buf: &RawBuffer) -> Option<UndoTree> {
+        if history.current >= history.nodes.len() {
+            return None;
+        }
+
+        let mut tree = UndoTree::new();
+        for (i, node) in history.nodes.into_iter().enumerate() {
+            match node.parent {
+                None if i == 0 => {}
+                Some(parent) if parent < i => {
+                    tree.nodes[parent].children.push(i);
+                }
+                _ => return None,
+            }
+
+            if node.selections.is_empty() {
+                return None;
+            }
+
+            let node = UndoNode {
+                changes: node.changes,
+                cursors: CursorSet::from_selections(&node.selections),
+                parent: node.parent,
+                children: Vec::new(),
+                last_child: node.last_child,
+                created_at: node.created_at,
+                updated_at: node.updated_at,
+            };
+
+            tree.num_bytes += node.size();
+            tree.nodes.push(node);
+        }
+
+        for node in &tree.nodes {
+            if let Some(last_child) = node.last_child {
+                if !node.children.contains(&last_child) {
+                    return None;
+                }
+            }
+        }
+
+        tree.current = Some(history.current);
+        if !tree.is_consistent_with(buf) {
+            return None;
+        }
+
+        tree.prune();
+        Some(tree)
+    }
+
+    /// Walks through all states from the current one and checks that every
+    /// change (in both directions) and cursor is valid in the text of the
+    /// state it applies to.
+    fn is_consistent_with(&self, buf: &RawBuffer) -> bool {
+        let current = match self.current {
+            Some(current) => current,
+            None => return true,
+        };
+
+        // (node, the text at the node, the node we came from)
+        let mut stack = vec![(current, buf.clone(), None)];
+        while let Some((index, text, from)) = stack.pop() {
+            let node = &self.nodes[index];
+            if !node
+                .cursors
+                .as_slice()
+                .iter()
+                .all(|c| text.is_valid_range(c.selection()))
+            {
+                return false;
+            }
+
+            if let Some(parent) = node.parent {
+                if from != Some(parent) {
+                    let parent_text =
+                        match apply_changes(&text, node.changes.iter().rev().map(|c| &c.undo)) {
+                            Some(parent_text) => parent_text,
+                            None => return false,
+                        };
+                    if apply_changes(&parent_text, node.changes.iter().map(|c| &c.redo)).is_none() {
+                        return false;
+                    }
+                    stack.push((parent, parent_text, Some(index)));
+                }
+            }
+
+            for &child in &node.children {
+                if from == Some(child) {
+                    continue;
+                }
+
+                let changes = &self.nodes[child].changes;
+                let child_text = match apply_changes(&text, changes.iter().map(|c| &c.redo)) {
+                    Some(child_text) => child_text,
+                    None => return false,
+                };
+                if apply_changes(&child_text, changes.iter().rev().map(|c| &c.undo)).is_none() {
+                    return false;
+                }
+                stack.push((child, child_text, Some(index)));
+            }
+        }
+
+        true
+    }
+
+    /// Adds a new state as a child of the current node and moves to it. The
+    /// first state becomes the root and `changes` are ignored.
+    ///
+    /// Consecutive typing is merged into the current node instead.
+    pub fn push(&mut self, changes: Vec<ReversibleChange>, cursors: CursorSet) {
+        self.push_at(changes, cursors, SystemTime::now());
+    }
+
+    fn push_at(&mut self, changes: Vec<ReversibleChange>, cursors: CursorSet, now: SystemTime) {
+        let parent = match self.current {
+            Some(current) if self.can_coalesce(current, &changes, now) => {
+                let node = &mut self.nodes[current];
+                self.num_bytes -= node.size();
+                node.changes.extend(changes);
+                node.cursors = cursors;
+                node.updated_at = now;
+                self.num_bytes += node.size();
+                self.prune();
+                return;
+            }
+            Some(current) => Some(current),
+            None => {
+                self.nodes.clear();
+                None
+            }
+        };
+
+        let index = self.nodes.len();
+        if let Some(parent) = parent {
+            self.nodes[parent].children.push(index);
+            self.nodes[parent].last_child = Some(index);
+        }
+
+        let node = UndoNode {
+            changes: if parent.is_some() {
+                changes
+            } else {
+                Vec::new()
+            },
+            cursors,
+            parent,
+            children: Vec::new(),
+            last_child: None,
+            created_at: now,
+            updated_at: now,
+        };
+
+        self.num_bytes += node.size();
+        self.nodes.push(node);
+        self.current = Some(index);
+        self.prune();
+    }
+
+    /// Returns true if `changes` continue the typing in the current node.
+    fn can_coalesce(&self, current: usize, changes: &[ReversibleChange], now: SystemTime) -> bool {
+        fn is_typing(change: &Change) -> bool {
+            change.range.is_empty()
+                && !change.insert_text.is_empty()
+                && !change.insert_text.contains('\n')
+        }
+
+        let node = &self.nodes[current];
+        if node.parent.is_none() || !node.children.is_empty() {
+            return false;
+        }
+
+        match now.duration_since(node.updated_at) {
+            Ok(elapsed) if elapsed < COALESCE_WINDOW => {}
+            _ => return false,
+        }
+
+        let mut prev = match node.changes.last() {
+            Some(change) if node.changes.iter().all(|c| is_typing(&c.redo)) => &change.redo,
+            _ => return false,
+        };
+
+        for change in changes {
+            if !is_typing(&change.redo) || change.redo.range.front() != prev.new_pos {
+                return false;
+            }
+
+            prev = &change.redo;
+        }
+
+        !changes.is_empty()
+    }
+
+    /// Drops the oldest nodes until the history fits in the memory limit. The
+    /// path from the root to the current node is kept as long as possible.
+    fn prune(&mut self) {
+        while self.num_bytes > self.max_bytes {
+            let current = match self.current {
+                Some(current) => current,
+                None => return,
+            };
+
+            // Find the child of the root which leads to the current node.
+            let mut new_root = current;
+            loop {
+                match self.nodes[new_root].parent {
+                    Some(0) => break,
+                    Some(parent) => new_root = parent,
+                    None => return,
+                }
+            }
+
+            self.reroot(new_root);
+        }
+    }
+
+    /// Makes `new_root` the root node and drops nodes which are not its
+    /// descendants.
+    fn reroot(&mut self, new_root: usize) {
+        // A parent is always older than its children.
+        let mut new_indices = vec![None; self.nodes.len()];
+        let mut next_index = 0;
+        for i in new_root..self.nodes.len() {
+            let keep = i == new_root
+                || self.nodes[i]
+                    .parent
+                    .map(|parent| new_indices[parent].is_some())
+                    .unwrap_or(false);
+            if keep {
+                new_indices[i] = Some(next_index);
+                next_index += 1;
+            }
+        }
+
+        let old_nodes = std::mem::take(&mut self.nodes);
+        for (i, mut node) in old_nodes.into_iter().enumerate() {
+            if new_indices[i].is_none() {
+                continue;
+            }
+
+            if i == new_root {
+                node.parent = None;
+                node.changes = Vec::new();
+            } else {
+                node.parent = node.parent.and_then(|parent| new_indices[parent]);
+            }
+
+            node.children = node
+                .children
+                .iter()
+                .filter_map(|child| new_indices[*child])
+                .collect();
+            node.last_child = node.last_child.and_then(|child| new_indices[child]);
+            self.nodes.push(node);
+        }
+
+        self.current = self.current.and_then(|current| new_indices[current]);
+        self.num_bytes = self.nodes.iter().map(|node| node.size()).sum();
+    }
+
+    /// Moves to the parent node.
+    pub fn undo(&mut self) -> Option<UndoPath<'_>> {
+        let parent = self.nodes[self.current?].parent?;
+        self.jump_to(parent)
+    }
+
+    /// Moves to the most recently visited child node.
+    pub fn redo(&mut self) -> Option<UndoPath<'_>> {
+        let child = self.nodes[self.current?].last_child?;
+        self.jump_to(child)
+    }
+
+    /// Moves to the next (or previous) sibling of the current node, that is,
+    /// another branch forked from the same state.
+    pub fn switch_branch(&mut self, forward: bool) -> Option<UndoPath<'_>> {
+        let current = self.current?;
+        let siblings = &self.nodes[self.nodes[current].parent?].children;
+        if siblings.len() < 2 {
+            return None;
+        }
+
+        let i = siblings.iter().position(|&index| index == current).unwrap();
+        let next = if forward {
+            siblings[(i + 1) % siblings.len()]
+        } else {
+            siblings[(i + siblings.len() - 1) % siblings.len()]
+        };
+
+        self.jump_to(next)
+    }
+
+    /// Moves to the node created just before the current one regardless of
+    /// the branch it belongs to.
+    pub fn earlier(&mut self) -> Option<UndoPath<'_>> {
+        let index = self.current?.checked_sub(1)?;
+        self.jump_to(index)
+    }
+
+    /// Moves to the node created just after the current one regardless of
+    /// the branch it belongs to.
+    pub fn later(&mut self) -> Option<UndoPath<'_>> {
+        let index = self.current? + 1;
+        if index >= self.nodes.len() {
+            return None;
+        }
+
+        self.jump_to(index)
+    }
+
+    /// Moves to the latest node created at or before `time`. If all nodes are
+    /// newer than that, moves to the oldest one.
+    pub fn travel_to(&mut self, time: SystemTime) -> Option<UndoPath<'_>> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let index = self
+            .nodes
+            .partition_point(|node| node.created_at <= time)
+            .saturating_sub(1);
+        self.jump_to(index)
+    }
+
+    fn jump_to(&mut self, index: usize) -> Option<UndoPath<'_>> {
+        let current = self.current?;
+        if current == index {
+            return None;
+        }
+
+        // Nodes from the destination up to the root.
+        let mut dest_ancestors = vec![index];
+        while let Some(parent) = self.nodes[*dest_ancestors.last().unwrap()].parent {
+            dest_ancestors.push(parent);
+        }
+
+        // Go up from the current node to the common ancestor...
+        let mut ups = Vec::new();
+        let mut common = current;
+        while !dest_ancestors.contains(&common) {
+            ups.push(common);
+            common = self.nodes[common].parent.unwrap();
+        }
+
+        // ... and then go down to the destination.
+        let common_pos = dest_ancestors.iter().position(|&i| i == common).unwrap();
+        let downs: Vec<usize> = dest_ancestors[..common_pos].iter().rev().copied().collect();
+
+        // Update the redo path so that redoing from ancestors leads to here.
+        for &i in &dest_ancestors[..dest_ancestors.len() - 1] {
+            let parent = self.nodes[i].parent.unwrap();
+            self.nodes[parent].last_child = Some(i);
+        }
+
+        self.current = Some(index);
+
+        let mut changes = Vec::new();
+        for i in ups {
+            changes.extend(self.nodes[i].changes.iter().rev().map(|c| &c.undo));
+        }
+        for i in downs {
+            changes.extend(self.nodes[i].changes.iter().map(|c| &c.redo));
+        }
+
+        Some(UndoPath {
+            changes,
+            cursors: &self.nodes[index].cursors,
+        })
+    }
+}
+
+impl Default for UndoTree {
+    fn default() -> UndoTree {
+        UndoTree::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{cursor::Range, mut_raw_buffer::MutRawBuffer};
+
+    struct Harness {
+        buf: MutRawBuffer,
+        tree: UndoTree,
+        now: SystemTime,
+    }
+
+    impl Harness {
+        fn new(max_bytes: usize) -> Harness {
+            let mut h = Harness {
+                buf: MutRawBuffer::new(),
+                tree: UndoTree::with_max_bytes(max_bytes),
+                now: SystemTime::now(),
+            };
+            h.save();
+            h
+        }
+
+        fn edit(&mut self, range: Range, text: &str) {
+            self.buf.edit(range, text);
+        }
+
+        fn save(&mut self) {
+            // Don't coalesce unless a test explicitly wants to.
+            self.now += COALESCE_WINDOW;
+            self.save_now();
+        }
+
+        fn save_now(&mut self) {
+            let changes = self.buf.clear_undo_log();
+            self.tree.push_at(changes, CursorSet::new(), self.now);
+        }
+
+        fn apply(&mut self, f: fn(&mut UndoTree) -> Option<UndoPath<'_>>) -> Option<String> {
+            let changes: Vec<Change> = f(&mut self.tree)?.changes.into_iter().cloned().collect();
+            for change in changes {
+                self.buf.edit(change.range, &change.insert_text);
+            }
+            self.buf.clear_undo_log();
+            Some(self.buf.text())
+        }
+    }
+
+    fn some(s: &str) -> Option<String> {
+        Some(s.to_owned())
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut h = Harness::new(DEFAULT_MAX_BYTES);
+        assert_eq!(h.apply(UndoTree::undo), None);
+        assert_eq!(h.apply(UndoTree::redo), None);
+
+        h.edit(Range::new(0, 0, 0, 0), "a");
+        h.save();
+        h.edit(Range::new(0, 1, 0, 1), "bc");
+        h.save();
+        h.edit(Range::new(0, 0, 0, 2), "X\nY");
+        h.save();
+        assert_eq!(h.buf.text(), "X\nYc");
+
+        assert_eq!(h.apply(UndoTree::undo), some("abc"));
+        assert_eq!(h.apply(UndoTree::undo), some("a"));
+        assert_eq!(h.apply(UndoTree::undo), some(""));
+        assert_eq!(h.apply(UndoTree::undo), None);
+        assert_eq!(h.apply(UndoTree::redo), some("a"));
+        assert_eq!(h.apply(UndoTree::redo), some("abc"));
+        assert_eq!(h.apply(UndoTree::redo), some("X\nYc"));
+        assert_eq!(h.apply(UndoTree::redo), None);
+    }
+
+    #[test]
+    fn branches() {
+        // "" -> a -> ab
+        //         -> ax
+        let mut h = Harness::new(DEFAULT_MAX_BYTES);
+        h.edit(Range::new(0, 0, 0, 0), "a");
+        h.save();
+        h.edit(Range::new(0, 1, 0, 1), "b");
+        h.save();
+        h.apply(UndoTree::undo);
+        h.edit(Range::new(0, 1, 0, 1), "x");
+        h.save();
+
+        // Redo follows the most recently visited branch.
+        assert_eq!(h.apply(UndoTree::undo), some("a"));
+        assert_eq!(h.apply(UndoTree::redo), some("ax"));
+
+        assert_eq!(h.apply(|t| t.switch_branch(true)), some("ab"));
+        assert_eq!(h.apply(|t| t.switch_branch(false)), some("ax"));
+        assert_eq!(h.apply(UndoTree::undo), some("a"));
+        assert_eq!(h.apply(|t| t.switch_branch(true)), None);
+
+        // Chronological moves jump across branches.
+        assert_eq!(h.apply(UndoTree::later), some("ab"));
+        assert_eq!(h.apply(UndoTree::later), some("ax"));
+        assert_eq!(h.apply(UndoTree::earlier), some("ab"));
+    }
+
+    #[test]
+    fn travel_to() {
+        let mut h = Harness::new(DEFAULT_MAX_BYTES);
+        let t0 = h.now;
+        h.edit(Range::new(0, 0, 0, 0), "a");
+        h.save();
+        h.apply(UndoTree::undo);
+        h.edit(Range::new(0, 0, 0, 0), "b");
+        h.save();
+
+        let t1 = t0 + COALESCE_WINDOW;
+        assert_eq!(h.tree.travel_to(t1).map(|p| p.changes.len()), Some(2));
+        h.tree.travel_to(t0);
+        assert!(h.tree.travel_to(t0 - COALESCE_WINDOW).is_none());
+        assert_eq!(
+            h.tree.redo().map(|p| p.changes[0].insert_text.clone()),
+            some("a")
+        );
+    }
+
+    #[test]
+    fn coalesce_typing() {
+        let mut h = Harness::new(DEFAULT_MAX_BYTES);
+        h.edit(Range::new(0, 0, 0, 0), "a");
+        h.save();
+        h.edit(Range::new(0, 1, 0, 1), "b");
+        h.save_now();
+        h.edit(Range::new(0, 2, 0, 2), "c");
+        h.save_now();
+        // Not a continuation.
+        h.edit(Range::new(0, 0, 0, 0), "d");
+        h.save_now();
+
+        assert_eq!(h.apply(UndoTree::undo), some("abc"));
+        assert_eq!(h.apply(UndoTree::undo), some(""));
+    }
+
+    #[test]
+    fn memory_limit() {
+        let mut h = Harness::new(3 * (2 * std::mem::size_of::<Change>() + 100));
+        for i in 0..5 {
+            h.edit(Range::new(i, 0, i, 0), &format!("{}\n", "x".repeat(49)));
+            h.save();
+        }
+
+        assert!(h.tree.num_bytes <= h.tree.max_bytes);
+        assert_eq!(h.tree.nodes[0].parent, None);
+        assert_eq!(h.apply(UndoTree::undo).map(|t| t.lines().count()), Some(4));
+        assert_eq!(h.apply(UndoTree::undo).map(|t| t.lines().count()), Some(3));
+        assert_eq!(h.apply(UndoTree::undo).map(|t| t.lines().count()), Some(2));
+        assert_eq!(h.apply(UndoTree::undo), None);
+    }
+
+    #[test]
+    fn history() {
+        let mut h = Harness::new(DEFAULT_MAX_BYTES);
+        h.edit(Range::new(0, 0, 0, 0), "a");
+        h.save();
+        h.apply(UndoTree::undo);
+        h.edit(Range::new(0, 0, 0, 0), "b");
+        h.save();
+
+        h.tree = UndoTree::from_history(h.tree.to_history().unwrap(), &h.buf).unwrap();
+        assert_eq!(h.apply(UndoTree::undo), some(""));
+        assert_eq!(h.apply(UndoTree::redo), some("b"));
+        assert_eq!(h.apply(UndoTree::earlier), some("a"));
+
+        let mut history = h.tree.to_history().unwrap();
+        history.nodes[1].parent = Some(2);
+        assert!(UndoTree::from_history(history, &h.buf).is_none());
+
+        // The history does not match the text.
+        let history = h.tree.to_history().unwrap();
+        assert!(UndoTree::from_history(history, &RawBuffer::from_text("")).is_none());
+        let mut history = h.tree.to_history().unwrap();
+        history.nodes[history.current].selections[0] = Range::new(5, 0, 5, 0);
+        assert!(UndoTree::from_history(history, &h.buf).is_none());
+    }
+}
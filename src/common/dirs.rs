@@ -21,6 +21,11 @@ pub fn noa_dir() -> PathBuf {
     dir
 }
 
+/// Returns `~/.config/noa`, or `None` if the home directory is unknown.
+pub fn user_config_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".config/noa"))
+}
+
 pub fn noa_workdir(workdir: &Path) -> PathBuf {
     let workdir = workdir
         .canonicalize()
@@ -58,6 +63,12 @@ pub fn backup_dir() -> PathBuf {
     backup_dir
 }
 
+pub fn undo_dir() -> PathBuf {
+    let undo_dir = noa_dir().join("undo");
+    create_dir_all(&undo_dir).expect("failed to create dir");
+    undo_dir
+}
+
 pub fn noa_bin_args() -> &'static [&'static str] {
     if cfg!(debug_assertions) {
         &["cargo", "run", "--bin", "noa", "--"]
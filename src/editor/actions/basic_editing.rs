@@ -1,11 +1,17 @@
+use std::time::{Duration, SystemTime};
+
 use anyhow::{Context, Result};
-use noa_buffer::cursor::{Position, Range};
+use noa_buffer::{
+    cursor::{Position, Range},
+    find::{FindOptions, FindQuery},
+};
 use noa_compositor::Compositor;
 
 use crate::{
     clipboard::{ClipboardData, SystemClipboardData},
     editor::Editor,
     finder::{open_buffer_switcher, open_finder},
+    ui::prompt_view::PromptView,
 };
 
 use super::Action;
@@ -186,7 +192,11 @@ impl Action for FindCurrentWord {
 
         if let Some(word_range) = word_range {
             let text = buffer.substr(word_range);
-            editor.find_query.set_text(&text);
+            if editor.find_options.regex {
+                editor.find_query.set_text(&regex::escape(&text));
+            } else {
+                editor.find_query.set_text(&text);
+            }
         }
         Ok(())
     }
@@ -212,7 +222,15 @@ impl Action for FindCurrentWordGlobally {
 
         if let Some(word_range) = word_range {
             let text = buffer.substr(word_range);
-            open_finder(editor, compositor, Some(&format!("/{}", text)));
+            let options = editor.find_options;
+            let flags = if options.case_insensitive { "i" } else { "" };
+            let query = if options.whole_word {
+                format!(r"{}//\b{}\b", flags, regex::escape(&text))
+            } else {
+                format!("{}/{}", flags, text)
+            };
+
+            open_finder(editor, compositor, Some(&query));
         }
         Ok(())
     }
@@ -239,7 +257,14 @@ impl Action for SelectAllCurrentWord {
 
         if let Some(word_range) = word_range {
             let text = buffer.substr(word_range);
-            let selections: Vec<Range> = buffer.find_iter(&text, Position::new(0, 0)).collect();
+            let options = FindOptions {
+                regex: false,
+                ..editor.find_options
+            };
+            let query = FindQuery::new(&text, options)?;
+            let selections: Vec<Range> = query
+                .find_iter(buffer.raw_buffer(), Position::new(0, 0))
+                .collect();
             for selection in selections {
                 buffer.add_cursor(selection);
             }
@@ -301,6 +326,49 @@ impl Action for MoveLinesDown {
     }
 }
 
+pub struct AddNextOccurrence;
+
+impl Action for AddNextOccurrence {
+    fn name(&self) -> &'static str {
+        "add_next_occurrence"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        if !editor.current_buffer_mut().add_next_occurrence() {
+            notify_warn!("no more occurrences");
+        }
+        Ok(())
+    }
+}
+
+pub struct SkipOccurrence;
+
+impl Action for SkipOccurrence {
+    fn name(&self) -> &'static str {
+        "skip_occurrence"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        if !editor.current_buffer_mut().skip_occurrence() {
+            notify_warn!("no more occurrences");
+        }
+        Ok(())
+    }
+}
+
+pub struct UndoLastOccurrence;
+
+impl Action for UndoLastOccurrence {
+    fn name(&self) -> &'static str {
+        "undo_last_occurrence"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor.current_buffer_mut().undo_last_occurrence();
+        Ok(())
+    }
+}
+
 pub struct AddCursorsUp;
 
 impl Action for AddCursorsUp {
@@ -327,6 +395,36 @@ impl Action for AddCursorsDown {
     }
 }
 
+pub struct SwapWithNextSibling;
+
+impl Action for SwapWithNextSibling {
+    fn name(&self) -> &'static str {
+        "swap_with_next_sibling"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        if !editor.current_buffer_mut().swap_with_next_sibling() {
+            notify_warn!("syntax tree is not ready");
+        }
+        Ok(())
+    }
+}
+
+pub struct SwapWithPrevSibling;
+
+impl Action for SwapWithPrevSibling {
+    fn name(&self) -> &'static str {
+        "swap_with_prev_sibling"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        if !editor.current_buffer_mut().swap_with_prev_sibling() {
+            notify_warn!("syntax tree is not ready");
+        }
+        Ok(())
+    }
+}
+
 pub struct DuplicateLinesUp;
 
 impl Action for DuplicateLinesUp {
@@ -489,6 +587,120 @@ impl Action for Redo {
     }
 }
 
+pub struct SwitchToNextUndoBranch;
+
+impl Action for SwitchToNextUndoBranch {
+    fn name(&self) -> &'static str {
+        "switch_to_next_undo_branch"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        if !editor.current_buffer_mut().switch_undo_branch(true) {
+            notify_warn!("no other undo branches");
+        }
+        Ok(())
+    }
+}
+
+pub struct SwitchToPrevUndoBranch;
+
+impl Action for SwitchToPrevUndoBranch {
+    fn name(&self) -> &'static str {
+        "switch_to_prev_undo_branch"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        if !editor.current_buffer_mut().switch_undo_branch(false) {
+            notify_warn!("no other undo branches");
+        }
+        Ok(())
+    }
+}
+
+pub struct UndoChronologically;
+
+impl Action for UndoChronologically {
+    fn name(&self) -> &'static str {
+        "undo_chronologically"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        if !editor.current_buffer_mut().undo_chronologically() {
+            notify_warn!("no more undo");
+        }
+        Ok(())
+    }
+}
+
+pub struct RedoChronologically;
+
+impl Action for RedoChronologically {
+    fn name(&self) -> &'static str {
+        "redo_chronologically"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        if !editor.current_buffer_mut().redo_chronologically() {
+            notify_warn!("no more redo");
+        }
+        Ok(())
+    }
+}
+
+pub struct UndoToTime;
+
+impl Action for UndoToTime {
+    fn name(&self) -> &'static str {
+        "undo_to_time"
+    }
+
+    fn run(&self, _editor: &mut Editor, compositor: &mut Compositor<Editor>) -> Result<()> {
+        let prompt = compositor.get_mut_surface_by_name::<PromptView>("prompt");
+        prompt.open(
+            "Undo To (e.g. 30s, 5m, 1h ago)",
+            Box::new(|editor, _, prompt, entered| {
+                if entered {
+                    let time = match parse_duration(prompt.text().trim())
+                        .and_then(|ago| SystemTime::now().checked_sub(ago))
+                    {
+                        Some(time) => time,
+                        None => {
+                            notify_error!("invalid duration");
+                            return;
+                        }
+                    };
+
+                    if !editor.current_buffer_mut().undo_to_time(time) {
+                        notify_warn!("no undo history at the time");
+                    }
+
+                    prompt.close();
+                }
+            }),
+        );
+        Ok(())
+    }
+}
+
+/// Parses durations like `30s`, `5m`, and `1h`. A number without a unit is
+/// in minutes.
+fn parse_duration(s: &str) -> Option<Duration> {
+    let (num, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, "m"),
+    };
+
+    let num = num.parse::<u64>().ok()?;
+    let secs = match unit.trim() {
+        "s" | "sec" | "secs" => num,
+        "m" | "min" | "mins" => num.checked_mul(60)?,
+        "h" | "hour" | "hours" => num.checked_mul(60 * 60)?,
+        _ => return None,
+    };
+
+    Some(Duration::from_secs(secs))
+}
+
 pub struct SoftWrap;
 
 impl Action for SoftWrap {
@@ -515,6 +727,19 @@ impl Action for CommentOut {
     }
 }
 
+pub struct ToggleBlockComment;
+
+impl Action for ToggleBlockComment {
+    fn name(&self) -> &'static str {
+        "toggle_block_comment"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor.current_buffer_mut().toggle_block_comment();
+        Ok(())
+    }
+}
+
 pub struct ExpandSelection;
 
 impl Action for ExpandSelection {
@@ -527,3 +752,172 @@ impl Action for ExpandSelection {
         Ok(())
     }
 }
+
+pub struct ShrinkSelection;
+
+impl Action for ShrinkSelection {
+    fn name(&self) -> &'static str {
+        "shrink_selection"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor.current_buffer_mut().shrink_selections();
+        Ok(())
+    }
+}
+
+pub struct SelectInsidePair;
+
+impl Action for SelectInsidePair {
+    fn name(&self) -> &'static str {
+        "select_inside_pair"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor.current_buffer_mut().select_inside_pairs();
+        Ok(())
+    }
+}
+
+pub struct SelectAroundPair;
+
+impl Action for SelectAroundPair {
+    fn name(&self) -> &'static str {
+        "select_around_pair"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor.current_buffer_mut().select_around_pairs();
+        Ok(())
+    }
+}
+
+pub struct SortLinesNaturally;
+
+impl Action for SortLinesNaturally {
+    fn name(&self) -> &'static str {
+        "sort_lines_naturally"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor.current_buffer_mut().sort_lines_naturally();
+        Ok(())
+    }
+}
+
+pub struct SortLinesCaseInsensitively;
+
+impl Action for SortLinesCaseInsensitively {
+    fn name(&self) -> &'static str {
+        "sort_lines_case_insensitively"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor.current_buffer_mut().sort_lines_case_insensitively();
+        Ok(())
+    }
+}
+
+pub struct UniqueLines;
+
+impl Action for UniqueLines {
+    fn name(&self) -> &'static str {
+        "unique_lines"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor.current_buffer_mut().unique_lines();
+        Ok(())
+    }
+}
+
+pub struct ReverseLines;
+
+impl Action for ReverseLines {
+    fn name(&self) -> &'static str {
+        "reverse_lines"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor.current_buffer_mut().reverse_lines();
+        Ok(())
+    }
+}
+
+pub struct ShuffleLines;
+
+impl Action for ShuffleLines {
+    fn name(&self) -> &'static str {
+        "shuffle_lines"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor.current_buffer_mut().shuffle_lines();
+        Ok(())
+    }
+}
+
+pub struct JoinLines;
+
+impl Action for JoinLines {
+    fn name(&self) -> &'static str {
+        "join_lines"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor.current_buffer_mut().join_lines();
+        Ok(())
+    }
+}
+
+pub struct IncrementNumber;
+
+impl Action for IncrementNumber {
+    fn name(&self) -> &'static str {
+        "increment_number"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor.current_buffer_mut().increment_numbers(1);
+        Ok(())
+    }
+}
+
+pub struct DecrementNumber;
+
+impl Action for DecrementNumber {
+    fn name(&self) -> &'static str {
+        "decrement_number"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor.current_buffer_mut().increment_numbers(-1);
+        Ok(())
+    }
+}
+
+pub struct IncrementNumbersInSequence;
+
+impl Action for IncrementNumbersInSequence {
+    fn name(&self) -> &'static str {
+        "increment_numbers_in_sequence"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor.current_buffer_mut().increment_numbers_in_sequence(1);
+        Ok(())
+    }
+}
+
+pub struct AlignCursors;
+
+impl Action for AlignCursors {
+    fn name(&self) -> &'static str {
+        "align_cursors"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor.current_buffer_mut().align_cursors();
+        Ok(())
+    }
+}
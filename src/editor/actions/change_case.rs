@@ -5,6 +5,100 @@ use crate::editor::Editor;
 
 use super::Action;
 
+/// Splits an identifier into words, e.g. `HTTPServer_v2` into `HTTP`,
+/// `Server`, and `v2`.
+fn split_words(s: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    for chunk in s.split(|c: char| !c.is_alphanumeric()) {
+        let chars: Vec<char> = chunk.chars().collect();
+        let mut word = String::new();
+        for (i, ch) in chars.iter().enumerate() {
+            if i > 0 && ch.is_uppercase() {
+                let prev = chars[i - 1];
+                let next_is_lower = matches!(chars.get(i + 1), Some(next) if next.is_lowercase());
+                // `fooBar`, `utf8String`, or `HTTPServer`.
+                if !prev.is_uppercase() || next_is_lower {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+
+            word.push(*ch);
+        }
+
+        if !word.is_empty() {
+            words.push(word);
+        }
+    }
+
+    words
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => String::new(),
+    }
+}
+
+/// Converts an identifier by joining its words with `f`. Leading and trailing
+/// separators like `_` in `__init__` are kept.
+fn convert_case<F>(s: &str, f: F) -> String
+where
+    F: FnOnce(Vec<String>) -> String,
+{
+    let start = s.find(char::is_alphanumeric).unwrap_or(s.len());
+    let end = s
+        .rfind(char::is_alphanumeric)
+        .map_or(start, |i| i + s[i..].chars().next().unwrap().len_utf8());
+
+    let words = split_words(&s[start..end]);
+    format!("{}{}{}", &s[..start], f(words), &s[end..])
+}
+
+fn to_snake_case(s: &str) -> String {
+    convert_case(s, |words| {
+        let words: Vec<String> = words.iter().map(|w| w.to_lowercase()).collect();
+        words.join("_")
+    })
+}
+
+fn to_camel_case(s: &str) -> String {
+    convert_case(s, |words| {
+        let mut words = words.iter();
+        let first = words.next().map(|w| w.to_lowercase()).unwrap_or_default();
+        words.fold(first, |acc, w| acc + &capitalize(w))
+    })
+}
+
+fn to_pascal_case(s: &str) -> String {
+    convert_case(s, |words| words.iter().map(|w| capitalize(w)).collect())
+}
+
+fn to_kebab_case(s: &str) -> String {
+    convert_case(s, |words| {
+        let words: Vec<String> = words.iter().map(|w| w.to_lowercase()).collect();
+        words.join("-")
+    })
+}
+
+fn to_screaming_snake_case(s: &str) -> String {
+    convert_case(s, |words| {
+        let words: Vec<String> = words.iter().map(|w| w.to_uppercase()).collect();
+        words.join("_")
+    })
+}
+
+fn to_title_case(s: &str) -> String {
+    convert_case(s, |words| {
+        let words: Vec<String> = words.iter().map(|w| capitalize(w)).collect();
+        words.join(" ")
+    })
+}
+
 pub struct ToUpperCase;
 
 impl Action for ToUpperCase {
@@ -40,3 +134,151 @@ impl Action for ToLowerCase {
         Ok(())
     }
 }
+
+pub struct ToSnakeCase;
+
+impl Action for ToSnakeCase {
+    fn name(&self) -> &'static str {
+        "to_snake_case"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor
+            .documents
+            .current_mut()
+            .buffer_mut()
+            .edit_selection_current_word(to_snake_case);
+
+        Ok(())
+    }
+}
+
+pub struct ToCamelCase;
+
+impl Action for ToCamelCase {
+    fn name(&self) -> &'static str {
+        "to_camel_case"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor
+            .documents
+            .current_mut()
+            .buffer_mut()
+            .edit_selection_current_word(to_camel_case);
+
+        Ok(())
+    }
+}
+
+pub struct ToPascalCase;
+
+impl Action for ToPascalCase {
+    fn name(&self) -> &'static str {
+        "to_pascal_case"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor
+            .documents
+            .current_mut()
+            .buffer_mut()
+            .edit_selection_current_word(to_pascal_case);
+
+        Ok(())
+    }
+}
+
+pub struct ToKebabCase;
+
+impl Action for ToKebabCase {
+    fn name(&self) -> &'static str {
+        "to_kebab_case"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor
+            .documents
+            .current_mut()
+            .buffer_mut()
+            .edit_selection_current_word(to_kebab_case);
+
+        Ok(())
+    }
+}
+
+pub struct ToScreamingSnakeCase;
+
+impl Action for ToScreamingSnakeCase {
+    fn name(&self) -> &'static str {
+        "to_screaming_snake_case"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor
+            .documents
+            .current_mut()
+            .buffer_mut()
+            .edit_selection_current_word(to_screaming_snake_case);
+
+        Ok(())
+    }
+}
+
+pub struct ToTitleCase;
+
+impl Action for ToTitleCase {
+    fn name(&self) -> &'static str {
+        "to_title_case"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor
+            .documents
+            .current_mut()
+            .buffer_mut()
+            .edit_selection_current_word(to_title_case);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_split_words() {
+        assert_eq!(split_words("fooBar"), vec!["foo", "Bar"]);
+        assert_eq!(split_words("HTTPServer"), vec!["HTTP", "Server"]);
+        assert_eq!(
+            split_words("getHTTPResponse2"),
+            vec!["get", "HTTP", "Response2"]
+        );
+        assert_eq!(split_words("utf8String"), vec!["utf8", "String"]);
+        assert_eq!(
+            split_words("foo_bar-baz qux"),
+            vec!["foo", "bar", "baz", "qux"]
+        );
+        assert_eq!(split_words("ÉtéÀPlage"), vec!["Été", "À", "Plage"]);
+        assert_eq!(split_words("__"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_case_conversions() {
+        assert_eq!(to_snake_case("HTTPServer"), "http_server");
+        assert_eq!(to_snake_case("fooBarBaz"), "foo_bar_baz");
+        assert_eq!(to_snake_case("__privateField__"), "__private_field__");
+        assert_eq!(to_camel_case("foo_bar_baz"), "fooBarBaz");
+        assert_eq!(to_camel_case("HTTP_SERVER"), "httpServer");
+        assert_eq!(to_pascal_case("foo-bar"), "FooBar");
+        assert_eq!(to_pascal_case("straße_öl"), "StraßeÖl");
+        assert_eq!(to_kebab_case("FooBar"), "foo-bar");
+        assert_eq!(to_screaming_snake_case("maxValue"), "MAX_VALUE");
+        assert_eq!(to_title_case("hello_world"), "Hello World");
+        assert_eq!(to_snake_case("Hello World"), "hello_world");
+        assert_eq!(to_snake_case(""), "");
+    }
+}
@@ -0,0 +1,49 @@
+use anyhow::Result;
+use noa_buffer::encoding::Encoding;
+use noa_compositor::Compositor;
+
+use crate::{editor::Editor, ui::prompt_view::PromptView};
+
+use super::Action;
+
+pub struct ReopenWithEncoding;
+
+impl Action for ReopenWithEncoding {
+    fn name(&self) -> &'static str {
+        "reopen_with_encoding"
+    }
+
+    fn run(&self, _editor: &mut Editor, compositor: &mut Compositor<Editor>) -> Result<()> {
+        let prompt = compositor.get_mut_surface_by_name::<PromptView>("prompt");
+        prompt.open(
+            "Reopen with Encoding (e.g. shift_jis, latin1, utf-16le-bom)",
+            Box::new(|editor, _, prompt, entered| {
+                if entered {
+                    let encoding = match Encoding::from_label(prompt.text().trim()) {
+                        Some(encoding) => encoding,
+                        None => {
+                            notify_error!("unknown encoding");
+                            return;
+                        }
+                    };
+
+                    match editor
+                        .documents
+                        .current_mut()
+                        .reopen_with_encoding(encoding)
+                    {
+                        Ok(()) => {
+                            notify_info!("reopened with {}", encoding);
+                        }
+                        Err(err) => {
+                            notify_error!("failed to reopen: {}", err);
+                        }
+                    }
+
+                    prompt.close();
+                }
+            }),
+        );
+        Ok(())
+    }
+}
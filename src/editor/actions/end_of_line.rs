@@ -0,0 +1,57 @@
+use anyhow::Result;
+use noa_compositor::Compositor;
+use noa_editorconfig::EndOfLine;
+
+use crate::editor::Editor;
+
+use super::Action;
+
+fn convert_end_of_line(editor: &mut Editor, eol: EndOfLine) {
+    let buffer = editor.current_buffer_mut();
+    if buffer.end_of_line() == eol {
+        notify_info!("already in {}", eol);
+        return;
+    }
+
+    buffer.set_end_of_line(eol);
+    notify_info!("converted to {} (applied on save)", eol);
+}
+
+pub struct ConvertToLf;
+
+impl Action for ConvertToLf {
+    fn name(&self) -> &'static str {
+        "convert_to_lf"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        convert_end_of_line(editor, EndOfLine::Lf);
+        Ok(())
+    }
+}
+
+pub struct ConvertToCrLf;
+
+impl Action for ConvertToCrLf {
+    fn name(&self) -> &'static str {
+        "convert_to_crlf"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        convert_end_of_line(editor, EndOfLine::CrLf);
+        Ok(())
+    }
+}
+
+pub struct ConvertToCr;
+
+impl Action for ConvertToCr {
+    fn name(&self) -> &'static str {
+        "convert_to_cr"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        convert_end_of_line(editor, EndOfLine::Cr);
+        Ok(())
+    }
+}
@@ -0,0 +1,59 @@
+use anyhow::Result;
+
+use noa_compositor::Compositor;
+
+use crate::editor::Editor;
+
+use super::Action;
+
+pub struct Fold;
+
+impl Action for Fold {
+    fn name(&self) -> &'static str {
+        "fold"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor.current_buffer_mut().fold();
+        Ok(())
+    }
+}
+
+pub struct Unfold;
+
+impl Action for Unfold {
+    fn name(&self) -> &'static str {
+        "unfold"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor.current_buffer_mut().unfold();
+        Ok(())
+    }
+}
+
+pub struct FoldAll;
+
+impl Action for FoldAll {
+    fn name(&self) -> &'static str {
+        "fold_all"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor.current_buffer_mut().fold_all();
+        Ok(())
+    }
+}
+
+pub struct UnfoldAll;
+
+impl Action for UnfoldAll {
+    fn name(&self) -> &'static str {
+        "unfold_all"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor.current_buffer_mut().unfold_all();
+        Ok(())
+    }
+}
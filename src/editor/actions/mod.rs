@@ -9,9 +9,16 @@ use crate::editor::Editor;
 
 mod basic_editing;
 mod change_case;
+mod encoding;
+mod end_of_line;
+mod folding;
 mod goto;
 mod linemap;
+mod reflow;
+mod replace;
 mod scrolling;
+mod surround;
+mod textobjects;
 
 pub const ACTIONS: &[&dyn Action] = &[
     &basic_editing::Save,
@@ -33,8 +40,13 @@ pub const ACTIONS: &[&dyn Action] = &[
     &basic_editing::SelectNextWord,
     &basic_editing::MoveLineUp,
     &basic_editing::MoveLinesDown,
+    &basic_editing::AddNextOccurrence,
+    &basic_editing::SkipOccurrence,
+    &basic_editing::UndoLastOccurrence,
     &basic_editing::AddCursorsUp,
     &basic_editing::AddCursorsDown,
+    &basic_editing::SwapWithNextSibling,
+    &basic_editing::SwapWithPrevSibling,
     &basic_editing::DuplicateLinesUp,
     &basic_editing::DuplicateLinesDown,
     &basic_editing::SelectUntilBeginningOfLine,
@@ -45,17 +57,69 @@ pub const ACTIONS: &[&dyn Action] = &[
     &basic_editing::Undo,
     &basic_editing::UndoCursors,
     &basic_editing::Redo,
+    &basic_editing::SwitchToNextUndoBranch,
+    &basic_editing::SwitchToPrevUndoBranch,
+    &basic_editing::UndoChronologically,
+    &basic_editing::RedoChronologically,
+    &basic_editing::UndoToTime,
     &basic_editing::SoftWrap,
     &basic_editing::CommentOut,
+    &basic_editing::ToggleBlockComment,
     &basic_editing::ExpandSelection,
+    &basic_editing::ShrinkSelection,
+    &basic_editing::SelectInsidePair,
+    &basic_editing::SelectAroundPair,
+    &basic_editing::SortLinesNaturally,
+    &basic_editing::SortLinesCaseInsensitively,
+    &basic_editing::UniqueLines,
+    &basic_editing::ReverseLines,
+    &basic_editing::ShuffleLines,
+    &basic_editing::JoinLines,
+    &basic_editing::IncrementNumber,
+    &basic_editing::DecrementNumber,
+    &basic_editing::IncrementNumbersInSequence,
+    &basic_editing::AlignCursors,
     &change_case::ToUpperCase,
     &change_case::ToLowerCase,
+    &change_case::ToSnakeCase,
+    &change_case::ToCamelCase,
+    &change_case::ToPascalCase,
+    &change_case::ToKebabCase,
+    &change_case::ToScreamingSnakeCase,
+    &change_case::ToTitleCase,
+    &reflow::Reflow,
+    &encoding::ReopenWithEncoding,
+    &end_of_line::ConvertToLf,
+    &end_of_line::ConvertToCrLf,
+    &end_of_line::ConvertToCr,
+    &folding::Fold,
+    &folding::Unfold,
+    &folding::FoldAll,
+    &folding::UnfoldAll,
     &linemap::MoveToNextDiff,
     &linemap::MoveToPrevDiff,
+    &replace::ReplaceNext,
+    &replace::ReplaceAll,
+    &replace::ReplaceInSelection,
     &scrolling::PageUp,
     &scrolling::PageDown,
     &scrolling::Centering,
+    &surround::Surround,
+    &surround::ChangeSurround,
+    &surround::DeleteSurround,
     &goto::GoToLine,
+    &textobjects::SelectFunction,
+    &textobjects::MoveToNextFunction,
+    &textobjects::MoveToPrevFunction,
+    &textobjects::SelectClass,
+    &textobjects::MoveToNextClass,
+    &textobjects::MoveToPrevClass,
+    &textobjects::SelectParameter,
+    &textobjects::MoveToNextParameter,
+    &textobjects::MoveToPrevParameter,
+    &textobjects::SelectComment,
+    &textobjects::MoveToNextComment,
+    &textobjects::MoveToPrevComment,
 ];
 
 pub trait Action: Any + Send + Sync {
@@ -0,0 +1,380 @@
+use anyhow::Result;
+use noa_buffer::{
+    buffer::{Buffer, RangeEdit},
+    cursor::Range,
+};
+use noa_compositor::Compositor;
+use noa_editorconfig::MaxLineLength;
+use textwrap::{Options, WordSeparator, WordSplitter, WrapAlgorithm};
+
+use crate::{config::max_line_length, editor::Editor};
+
+use super::Action;
+
+/// A line split into its prefix and the text.
+struct Line<'a> {
+    /// The indentation and the comment leader (e.g. `/// `) if it's a line
+    /// comment.
+    leader: &'a str,
+    /// The Markdown list marker (e.g. `- ` or `1. `).
+    marker: &'a str,
+    body: &'a str,
+}
+
+fn list_marker_len(s: &str) -> usize {
+    let digits = s.chars().take_while(char::is_ascii_digit).count();
+    let bullet_len = match (digits, s[digits..].chars().next()) {
+        (0, Some('-' | '*' | '+')) => 1,
+        (0, _) => return 0,
+        (_, Some('.' | ')')) => digits + 1,
+        (_, _) => return 0,
+    };
+
+    let rest = &s[bullet_len..];
+    let num_spaces = rest.len() - rest.trim_start_matches([' ', '\t']).len();
+    if num_spaces == 0 {
+        0
+    } else {
+        bullet_len + num_spaces
+    }
+}
+
+fn split_line<'a>(line: &'a str, line_comment: Option<&str>) -> Line<'a> {
+    let mut leader_len = line.len() - line.trim_start().len();
+    if let Some(prefix) = line_comment {
+        if line[leader_len..].starts_with(prefix) {
+            // Include repeated characters like `///` and `//!`.
+            let last = prefix.chars().last().unwrap();
+            leader_len += prefix.len();
+            leader_len += line[leader_len..]
+                .chars()
+                .take_while(|&ch| ch == last || ch == '!')
+                .map(char::len_utf8)
+                .sum::<usize>();
+
+            let rest = &line[leader_len..];
+            leader_len += rest.len() - rest.trim_start().len();
+        }
+    }
+
+    let rest = &line[leader_len..];
+    let marker_len = list_marker_len(rest);
+    Line {
+        leader: &line[..leader_len],
+        marker: &rest[..marker_len],
+        body: &rest[marker_len..],
+    }
+}
+
+/// Returns true if `body` is a Markdown block which is not a part of
+/// paragraphs: an ATX heading, a block quote, or a table row.
+fn is_block_start(body: &str) -> bool {
+    let num_hashes = body.chars().take_while(|&ch| ch == '#').count();
+    let is_heading = (1..=6).contains(&num_hashes)
+        && matches!(body[num_hashes..].chars().next(), None | Some(' ' | '\t'));
+    is_heading || body.starts_with('>') || body.starts_with('|')
+}
+
+/// Returns whether each line is in a fenced code block. Fences themselves are
+/// included.
+fn fenced_code_lines(buffer: &Buffer, line_comment: Option<&str>) -> Vec<bool> {
+    // The character and the length of the opening fence.
+    let mut opening: Option<(char, usize)> = None;
+    (0..buffer.num_lines())
+        .map(|y| {
+            let line = buffer.line_text(y);
+            let body = split_line(&line, line_comment).body;
+            let fence_char = match body.chars().next() {
+                Some(ch @ ('`' | '~')) => ch,
+                _ => return opening.is_some(),
+            };
+            let fence_len = body.chars().take_while(|&ch| ch == fence_char).count();
+            if fence_len < 3 {
+                return opening.is_some();
+            }
+
+            match opening {
+                Some((ch, len)) => {
+                    if ch == fence_char && fence_len >= len && body[fence_len..].trim().is_empty() {
+                        opening = None;
+                    }
+                }
+                None => {
+                    opening = Some((fence_char, fence_len));
+                }
+            }
+
+            true
+        })
+        .collect()
+}
+
+/// Returns true if `lower` continues the paragraph in `upper`.
+fn is_continuation(upper: &str, lower: &str, line_comment: Option<&str>) -> bool {
+    let upper = split_line(upper, line_comment);
+    let lower = split_line(lower, line_comment);
+    upper.leader.trim() == lower.leader.trim()
+        && lower.marker.is_empty()
+        && !upper.body.trim().is_empty()
+        && !lower.body.trim().is_empty()
+        && !is_block_start(upper.body)
+        && !is_block_start(lower.body)
+}
+
+/// Returns the lines of the paragraph (or the comment block) at `y`. If
+/// `comments_only` is true, only comment blocks are returned so that code is
+/// never reflowed. Lines in `fenced_code_lines` are never reflowed either.
+fn paragraph_at(
+    buffer: &Buffer,
+    y: usize,
+    line_comment: Option<&str>,
+    comments_only: bool,
+    fenced_code_lines: &[bool],
+) -> Option<std::ops::Range<usize>> {
+    let line = buffer.line_text(y);
+    let first = split_line(&line, line_comment);
+    if fenced_code_lines[y] || first.body.trim().is_empty() || is_block_start(first.body) {
+        return None;
+    }
+
+    // Continuation lines have the same leader so they're comments too.
+    let is_comment =
+        matches!(line_comment, Some(prefix) if first.leader.trim().starts_with(prefix));
+    if comments_only && !is_comment {
+        return None;
+    }
+
+    let mut start = y;
+    while start > 0
+        && !fenced_code_lines[start - 1]
+        && is_continuation(
+            &buffer.line_text(start - 1),
+            &buffer.line_text(start),
+            line_comment,
+        )
+    {
+        start -= 1;
+    }
+
+    let mut end = y + 1;
+    while end < buffer.num_lines()
+        && !fenced_code_lines[end]
+        && is_continuation(
+            &buffer.line_text(end - 1),
+            &buffer.line_text(end),
+            line_comment,
+        )
+    {
+        end += 1;
+    }
+
+    Some(start..end)
+}
+
+/// Rewraps a paragraph to `width` columns keeping the prefix of its first
+/// line.
+fn reflow_paragraph(text: &str, width: usize, line_comment: Option<&str>) -> String {
+    let mut lines = text.lines();
+    let first = match lines.next() {
+        Some(line) => split_line(line, line_comment),
+        None => return String::new(),
+    };
+
+    let mut words = first.body.trim().to_owned();
+    for line in lines {
+        words.push(' ');
+        words.push_str(split_line(line, line_comment).body.trim());
+    }
+
+    let initial_indent = format!("{}{}", first.leader, first.marker);
+    let subsequent_indent = format!("{}{}", first.leader, " ".repeat(first.marker.len()));
+    let options = Options::new(width)
+        .initial_indent(&initial_indent)
+        .subsequent_indent(&subsequent_indent)
+        .break_words(false)
+        .word_separator(WordSeparator::AsciiSpace)
+        .word_splitter(WordSplitter::NoHyphenation)
+        .wrap_algorithm(WrapAlgorithm::FirstFit);
+
+    textwrap::fill(&words, options)
+}
+
+pub struct Reflow;
+
+impl Action for Reflow {
+    fn name(&self) -> &'static str {
+        "reflow"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        let buffer = editor.current_buffer_mut();
+        let width = match buffer.editorconfig().max_line_length {
+            Some(MaxLineLength::Columns(width)) => width,
+            Some(MaxLineLength::Off) => {
+                notify_warn!("max_line_length is off");
+                return Ok(());
+            }
+            None => max_line_length(),
+        };
+        let line_comment = buffer.language().line_comment;
+        // Reflow only comments in source code.
+        let comments_only =
+            line_comment.is_some() && !matches!(buffer.language().name, "plain" | "markdown");
+        let fenced_code_lines = fenced_code_lines(buffer, line_comment);
+
+        let mut paragraphs: Vec<Range> = Vec::new();
+        for c in buffer.cursors() {
+            let selection = c.selection();
+            let mut y = selection.front().y;
+            while y <= selection.back().y {
+                match paragraph_at(buffer, y, line_comment, comments_only, &fenced_code_lines) {
+                    Some(ys) => {
+                        let last_y = ys.end - 1;
+                        let range = Range::new(ys.start, 0, last_y, buffer.line_len(last_y));
+                        if !paragraphs.contains(&range) {
+                            paragraphs.push(range);
+                        }
+                        y = ys.end;
+                    }
+                    None => {
+                        y += 1;
+                    }
+                }
+            }
+        }
+
+        let edits = paragraphs
+            .into_iter()
+            .map(|range| RangeEdit {
+                range,
+                new_text: reflow_paragraph(&buffer.substr(range), width, line_comment),
+                // Keep selections of paragraphs selecting them.
+                stick_left: true,
+            })
+            .collect();
+        buffer.edit_ranges(edits);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_paragraph_at() {
+        let buffer = Buffer::from_text("a\nb\n\n// c\n// d\ne\n- f\n  g\n- h");
+        let fenced = fenced_code_lines(&buffer, Some("//"));
+        assert_eq!(
+            paragraph_at(&buffer, 1, Some("//"), false, &fenced),
+            Some(0..2)
+        );
+        assert_eq!(paragraph_at(&buffer, 2, Some("//"), false, &fenced), None);
+        assert_eq!(
+            paragraph_at(&buffer, 3, Some("//"), false, &fenced),
+            Some(3..5)
+        );
+        assert_eq!(
+            paragraph_at(&buffer, 5, Some("//"), false, &fenced),
+            Some(5..6)
+        );
+        assert_eq!(
+            paragraph_at(&buffer, 7, Some("//"), false, &fenced),
+            Some(6..8)
+        );
+        assert_eq!(
+            paragraph_at(&buffer, 8, Some("//"), false, &fenced),
+            Some(8..9)
+        );
+
+        // Only comments in source code.
+        assert_eq!(paragraph_at(&buffer, 1, Some("//"), true, &fenced), None);
+        assert_eq!(
+            paragraph_at(&buffer, 4, Some("//"), true, &fenced),
+            Some(3..5)
+        );
+        assert_eq!(paragraph_at(&buffer, 5, Some("//"), true, &fenced), None);
+    }
+
+    #[test]
+    fn stop_paragraphs_at_markdown_blocks() {
+        let paragraphs = |text: &str| {
+            let buffer = Buffer::from_text(text);
+            let fenced = fenced_code_lines(&buffer, None);
+            (0..buffer.num_lines())
+                .map(|y| paragraph_at(&buffer, y, None, false, &fenced))
+                .collect::<Vec<_>>()
+        };
+
+        // ATX headings.
+        assert_eq!(
+            paragraphs("# Title\nSome\ntext\n## Section"),
+            vec![None, Some(1..3), Some(1..3), None]
+        );
+        assert_eq!(paragraphs("#hashtag\ntext"), vec![Some(0..2), Some(0..2)]);
+
+        // Block quotes.
+        assert_eq!(
+            paragraphs("text\n> quote\n> quote"),
+            vec![Some(0..1), None, None]
+        );
+
+        // Tables.
+        assert_eq!(
+            paragraphs("text\n| a | b |\n|---|---|"),
+            vec![Some(0..1), None, None]
+        );
+
+        // Fenced code blocks.
+        assert_eq!(
+            paragraphs("text\n```\na\n\n# b\n```\ntext"),
+            vec![Some(0..1), None, None, None, None, None, Some(6..7)]
+        );
+        assert_eq!(
+            paragraphs("~~~~\na\n```\n~~~\nb\n~~~~\nc"),
+            vec![None, None, None, None, None, None, Some(6..7)]
+        );
+    }
+
+    #[test]
+    fn fenced_code_in_comments() {
+        let buffer = Buffer::from_text("/// a\n/// ```\n/// b\n/// ```\n/// c");
+        let fenced = fenced_code_lines(&buffer, Some("//"));
+        assert_eq!(fenced, vec![false, true, true, true, false]);
+        assert_eq!(
+            paragraph_at(&buffer, 0, Some("//"), true, &fenced),
+            Some(0..1)
+        );
+        assert_eq!(paragraph_at(&buffer, 2, Some("//"), true, &fenced), None);
+        assert_eq!(
+            paragraph_at(&buffer, 4, Some("//"), true, &fenced),
+            Some(4..5)
+        );
+    }
+
+    #[test]
+    fn test_reflow_paragraph() {
+        assert_eq!(
+            reflow_paragraph("aaa bbb\nccc ddd eee", 8, None),
+            "aaa bbb\nccc ddd\neee"
+        );
+        assert_eq!(
+            reflow_paragraph("    /// aaa bbb ccc\n    /// ddd", 16, Some("//")),
+            "    /// aaa bbb\n    /// ccc ddd"
+        );
+        assert_eq!(
+            reflow_paragraph("  - aaa bbb\n    ccc ddd", 11, None),
+            "  - aaa bbb\n    ccc ddd"
+        );
+        assert_eq!(
+            reflow_paragraph("1. aaa\n   bbb ccc", 20, None),
+            "1. aaa bbb ccc"
+        );
+        assert_eq!(
+            reflow_paragraph("# aaa-bbb https://example.com/a-b", 10, Some("#")),
+            "# aaa-bbb\n# https://example.com/a-b"
+        );
+    }
+}
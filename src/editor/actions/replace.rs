@@ -0,0 +1,102 @@
+use anyhow::Result;
+use noa_buffer::find::FindQuery;
+use noa_compositor::Compositor;
+
+use crate::{editor::Editor, ui::prompt_view::PromptView};
+
+use super::Action;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ReplaceMode {
+    Next,
+    All,
+    InSelection,
+}
+
+fn open_replace_prompt(editor: &Editor, compositor: &mut Compositor<Editor>, mode: ReplaceMode) {
+    if editor.find_query.is_empty() {
+        notify_warn!("search something first (Ctrl+L)");
+        return;
+    }
+
+    let title = match mode {
+        ReplaceMode::Next => "Replace Next With",
+        ReplaceMode::All => "Replace All With",
+        ReplaceMode::InSelection => "Replace in Selection With",
+    };
+
+    let prompt = compositor.get_mut_surface_by_name::<PromptView>("prompt");
+    prompt.open(
+        title,
+        Box::new(move |editor, _, prompt, entered| {
+            if !entered {
+                return;
+            }
+
+            let query = match FindQuery::new(&editor.find_query.text(), editor.find_options) {
+                Ok(query) => query,
+                Err(err) => {
+                    notify_error!("invalid regex: {}", err);
+                    return;
+                }
+            };
+
+            let replacement = prompt.text();
+            let buffer = editor.current_buffer_mut();
+            match mode {
+                ReplaceMode::Next => {
+                    // Keep the prompt open to replace the next one by Enter.
+                    if !buffer.replace_next(&query, &replacement) {
+                        notify_warn!("no matches");
+                        prompt.close();
+                    }
+                }
+                ReplaceMode::All | ReplaceMode::InSelection => {
+                    let n =
+                        buffer.replace_all(&query, &replacement, mode == ReplaceMode::InSelection);
+                    notify_info!("replaced {} occurrences", n);
+                    prompt.close();
+                }
+            }
+        }),
+    );
+}
+
+pub struct ReplaceNext;
+
+impl Action for ReplaceNext {
+    fn name(&self) -> &'static str {
+        "replace_next"
+    }
+
+    fn run(&self, editor: &mut Editor, compositor: &mut Compositor<Editor>) -> Result<()> {
+        open_replace_prompt(editor, compositor, ReplaceMode::Next);
+        Ok(())
+    }
+}
+
+pub struct ReplaceAll;
+
+impl Action for ReplaceAll {
+    fn name(&self) -> &'static str {
+        "replace_all"
+    }
+
+    fn run(&self, editor: &mut Editor, compositor: &mut Compositor<Editor>) -> Result<()> {
+        open_replace_prompt(editor, compositor, ReplaceMode::All);
+        Ok(())
+    }
+}
+
+pub struct ReplaceInSelection;
+
+impl Action for ReplaceInSelection {
+    fn name(&self) -> &'static str {
+        "replace_in_selection"
+    }
+
+    fn run(&self, editor: &mut Editor, compositor: &mut Compositor<Editor>) -> Result<()> {
+        open_replace_prompt(editor, compositor, ReplaceMode::InSelection);
+        Ok(())
+    }
+}
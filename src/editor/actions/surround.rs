@@ -0,0 +1,85 @@
+use anyhow::Result;
+use noa_buffer::extras::surround::SurroundPair;
+use noa_compositor::Compositor;
+
+use crate::{editor::Editor, ui::prompt_view::PromptView};
+
+use super::Action;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SurroundMode {
+    Add,
+    Change,
+}
+
+fn open_surround_prompt(compositor: &mut Compositor<Editor>, mode: SurroundMode) {
+    let title = match mode {
+        SurroundMode::Add => "Surround With",
+        SurroundMode::Change => "Change Surrounding To",
+    };
+
+    let prompt = compositor.get_mut_surface_by_name::<PromptView>("prompt");
+    prompt.open(
+        title,
+        Box::new(move |editor, _, prompt, entered| {
+            if !entered {
+                return;
+            }
+
+            prompt.close();
+            let pair = match SurroundPair::parse(&prompt.text()) {
+                Some(pair) => pair,
+                None => {
+                    notify_warn!("invalid pair: specify a bracket, a quote, or a tag");
+                    return;
+                }
+            };
+
+            let doc = editor.documents.current_mut();
+            match mode {
+                SurroundMode::Add => doc.buffer_mut().surround_add(&pair),
+                SurroundMode::Change => doc.buffer_mut().surround_change(None, &pair),
+            }
+            doc.post_update_job(editor.repo.as_ref(), &editor.render_request);
+        }),
+    );
+}
+
+pub struct Surround;
+
+impl Action for Surround {
+    fn name(&self) -> &'static str {
+        "surround"
+    }
+
+    fn run(&self, _editor: &mut Editor, compositor: &mut Compositor<Editor>) -> Result<()> {
+        open_surround_prompt(compositor, SurroundMode::Add);
+        Ok(())
+    }
+}
+
+pub struct ChangeSurround;
+
+impl Action for ChangeSurround {
+    fn name(&self) -> &'static str {
+        "change_surround"
+    }
+
+    fn run(&self, _editor: &mut Editor, compositor: &mut Compositor<Editor>) -> Result<()> {
+        open_surround_prompt(compositor, SurroundMode::Change);
+        Ok(())
+    }
+}
+
+pub struct DeleteSurround;
+
+impl Action for DeleteSurround {
+    fn name(&self) -> &'static str {
+        "delete_surround"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor.current_buffer_mut().surround_delete(None);
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+use anyhow::Result;
+use noa_buffer::extras::textobjects::TextObjectKind;
+use noa_compositor::Compositor;
+
+use crate::editor::Editor;
+
+use super::Action;
+
+pub struct SelectFunction;
+
+impl Action for SelectFunction {
+    fn name(&self) -> &'static str {
+        "select_function"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor
+            .current_buffer_mut()
+            .select_textobjects(TextObjectKind::Function);
+        Ok(())
+    }
+}
+
+pub struct MoveToNextFunction;
+
+impl Action for MoveToNextFunction {
+    fn name(&self) -> &'static str {
+        "move_to_next_function"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor
+            .current_buffer_mut()
+            .move_to_next_textobjects(TextObjectKind::Function);
+        Ok(())
+    }
+}
+
+pub struct MoveToPrevFunction;
+
+impl Action for MoveToPrevFunction {
+    fn name(&self) -> &'static str {
+        "move_to_prev_function"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor
+            .current_buffer_mut()
+            .move_to_prev_textobjects(TextObjectKind::Function);
+        Ok(())
+    }
+}
+
+pub struct SelectClass;
+
+impl Action for SelectClass {
+    fn name(&self) -> &'static str {
+        "select_class"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor
+            .current_buffer_mut()
+            .select_textobjects(TextObjectKind::Class);
+        Ok(())
+    }
+}
+
+pub struct MoveToNextClass;
+
+impl Action for MoveToNextClass {
+    fn name(&self) -> &'static str {
+        "move_to_next_class"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor
+            .current_buffer_mut()
+            .move_to_next_textobjects(TextObjectKind::Class);
+        Ok(())
+    }
+}
+
+pub struct MoveToPrevClass;
+
+impl Action for MoveToPrevClass {
+    fn name(&self) -> &'static str {
+        "move_to_prev_class"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor
+            .current_buffer_mut()
+            .move_to_prev_textobjects(TextObjectKind::Class);
+        Ok(())
+    }
+}
+
+pub struct SelectParameter;
+
+impl Action for SelectParameter {
+    fn name(&self) -> &'static str {
+        "select_parameter"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor
+            .current_buffer_mut()
+            .select_textobjects(TextObjectKind::Parameter);
+        Ok(())
+    }
+}
+
+pub struct MoveToNextParameter;
+
+impl Action for MoveToNextParameter {
+    fn name(&self) -> &'static str {
+        "move_to_next_parameter"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor
+            .current_buffer_mut()
+            .move_to_next_textobjects(TextObjectKind::Parameter);
+        Ok(())
+    }
+}
+
+pub struct MoveToPrevParameter;
+
+impl Action for MoveToPrevParameter {
+    fn name(&self) -> &'static str {
+        "move_to_prev_parameter"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor
+            .current_buffer_mut()
+            .move_to_prev_textobjects(TextObjectKind::Parameter);
+        Ok(())
+    }
+}
+
+pub struct SelectComment;
+
+impl Action for SelectComment {
+    fn name(&self) -> &'static str {
+        "select_comment"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor
+            .current_buffer_mut()
+            .select_textobjects(TextObjectKind::Comment);
+        Ok(())
+    }
+}
+
+pub struct MoveToNextComment;
+
+impl Action for MoveToNextComment {
+    fn name(&self) -> &'static str {
+        "move_to_next_comment"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor
+            .current_buffer_mut()
+            .move_to_next_textobjects(TextObjectKind::Comment);
+        Ok(())
+    }
+}
+
+pub struct MoveToPrevComment;
+
+impl Action for MoveToPrevComment {
+    fn name(&self) -> &'static str {
+        "move_to_prev_comment"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor
+            .current_buffer_mut()
+            .move_to_prev_textobjects(TextObjectKind::Comment);
+        Ok(())
+    }
+}
@@ -1,7 +1,12 @@
 use std::path::PathBuf;
 
 use fuzzy_matcher::skim::SkimMatcherV2;
-use noa_buffer::{buffer::TextEdit, cursor::Cursor, raw_buffer::RawBuffer};
+use noa_buffer::{
+    buffer::{Buffer, TextEdit},
+    cursor::Cursor,
+    raw_buffer::RawBuffer,
+    snippet::Snippet,
+};
 
 use noa_compositor::Compositor;
 use noa_languages::Language;
@@ -9,6 +14,7 @@ use noa_languages::Language;
 use crate::{
     document::{Document, Words},
     editor::Editor,
+    snippets::user_snippets_for,
     ui::completion_view::CompletionView,
 };
 
@@ -19,6 +25,8 @@ pub fn build_fuzzy_matcher() -> SkimMatcherV2 {
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum CompletionKind {
     AnyWord,
+    Snippet,
+    SnippetChoice,
 }
 
 #[derive(Clone, Debug)]
@@ -26,13 +34,15 @@ pub struct CompletionItem {
     pub kind: CompletionKind,
     pub label: String,
     pub text_edits: Vec<TextEdit>,
+    /// The snippet to be inserted after applying `text_edits`.
+    pub snippet: Option<Snippet>,
 }
 
 /// This should be called after `Document::post_update_job` because the buffer
 /// needs to be synced with the LSP server before querying the completion.
 pub async fn complete(
     buffer: RawBuffer,
-    _lang: &'static Language,
+    lang: &'static Language,
     _path: PathBuf,
     main_cursor: Cursor,
     words: Words,
@@ -49,9 +59,29 @@ pub async fn complete(
         None => return None,
     };
 
-    // Any word comopletion.
+    // User snippets.
     let mut items = Vec::new();
     let current_word = buffer.substr(current_word_range);
+    items.extend(
+        user_snippets_for(lang)
+            .iter()
+            .filter(|s| s.prefix.starts_with(&current_word))
+            .take(NUM_ITEMS_MAX)
+            .map(|s| CompletionItem {
+                kind: CompletionKind::Snippet,
+                label: match &s.description {
+                    Some(description) => format!("{} - {}", s.prefix, description),
+                    None => s.prefix.clone(),
+                },
+                text_edits: vec![TextEdit {
+                    range: current_word_range,
+                    new_text: String::new(),
+                }],
+                snippet: Some(s.snippet.clone()),
+            }),
+    );
+
+    // Any word comopletion.
     if current_word.len() >= 3 {
         items.extend(
             words
@@ -66,6 +96,7 @@ pub async fn complete(
                         range: current_word_range,
                         new_text: word,
                     }],
+                    snippet: None,
                 }),
         );
     }
@@ -73,7 +104,10 @@ pub async fn complete(
     // Make items unique.
     let mut unique_items: Vec<CompletionItem> = Vec::with_capacity(items.len());
     for item in items {
-        if unique_items.iter().all(|i| i.text_edits != item.text_edits) {
+        if unique_items
+            .iter()
+            .all(|i| i.text_edits != item.text_edits || i.snippet != item.snippet)
+        {
             unique_items.push(item);
         }
     }
@@ -81,6 +115,39 @@ pub async fn complete(
     Some(unique_items)
 }
 
+/// Returns the choices of the current snippet tabstop as completion items.
+pub fn snippet_choice_items(buffer: &Buffer) -> Option<Vec<CompletionItem>> {
+    let choices = buffer.current_snippet_choices()?;
+    let items = choices
+        .iter()
+        .map(|choice| CompletionItem {
+            kind: CompletionKind::SnippetChoice,
+            label: choice.clone(),
+            text_edits: buffer
+                .cursors()
+                .iter()
+                .map(|c| TextEdit {
+                    range: c.selection(),
+                    new_text: choice.clone(),
+                })
+                .collect(),
+            snippet: None,
+        })
+        .collect();
+
+    Some(items)
+}
+
+/// Shows the choices if the current snippet tabstop has them.
+pub fn show_snippet_choices(compositor: &mut Compositor<Editor>, doc: &mut Document) {
+    if let Some(items) = snippet_choice_items(doc.buffer()) {
+        doc.set_completion_items(items);
+        compositor
+            .get_mut_surface_by_name::<CompletionView>("completion")
+            .set_active(true);
+    }
+}
+
 pub fn clear_completion(compositor: &mut Compositor<Editor>, doc: &mut Document) {
     compositor
         .get_mut_surface_by_name::<CompletionView>("completion")
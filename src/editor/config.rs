@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 
 use anyhow::{Context, Result};
-use noa_common::warn_once;
+use noa_common::{dirs::user_config_dir, warn_once};
 use noa_compositor::{
     canvas::{Color, Style},
     terminal::{KeyCode, KeyModifiers},
@@ -61,6 +61,10 @@ struct ConfigFile {
     key_bindings: Vec<KeyBinding>,
     theme: HashMap<String, ThemeItem>,
     colors: HashMap<String, String>,
+    /// Files larger than this (in bytes) are opened in the large file mode.
+    large_file_threshold: Option<u64>,
+    /// The line length used by reflowing if it's not specified in .editorconfig.
+    max_line_length: Option<usize>,
 }
 
 fn parse_keybindings(
@@ -123,7 +127,7 @@ static DEFAULT_CONFIG_FILE: Lazy<ConfigFile> = Lazy::new(|| {
 static USER_CONFIG_FILE: Lazy<ConfigFile> = Lazy::new(|| {
     let paths = &[
         dirs::home_dir().unwrap().join(".noa.toml"),
-        dirs::home_dir().unwrap().join(".config/noa/config.toml"),
+        user_config_dir().unwrap().join("config.toml"),
     ];
 
     for path in paths {
@@ -185,6 +189,20 @@ static THEME: Lazy<HashMap<String, Style>> = Lazy::new(|| {
     styles
 });
 
+pub fn large_file_threshold() -> u64 {
+    USER_CONFIG_FILE
+        .large_file_threshold
+        .or(DEFAULT_CONFIG_FILE.large_file_threshold)
+        .unwrap()
+}
+
+pub fn max_line_length() -> usize {
+    USER_CONFIG_FILE
+        .max_line_length
+        .or(DEFAULT_CONFIG_FILE.max_line_length)
+        .unwrap()
+}
+
 pub fn get_keybinding_for(
     scope: KeyBindingScope,
     keycode: KeyCode,
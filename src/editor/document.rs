@@ -1,8 +1,8 @@
 use std::{
     collections::{HashMap, HashSet},
     env::current_dir,
-    fs::{create_dir_all, OpenOptions},
-    io::ErrorKind,
+    fs::{create_dir_all, File},
+    io::{BufReader, ErrorKind, Read, Seek, SeekFrom},
     num::NonZeroUsize,
     path::{Path, PathBuf},
     sync::{
@@ -12,7 +12,7 @@ use std::{
     time::SystemTime,
 };
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 
 use arc_swap::ArcSwap;
 
@@ -20,9 +20,11 @@ use fuzzy_matcher::FuzzyMatcher;
 use noa_buffer::{
     buffer::Buffer,
     cursor::Position,
+    encoding::Encoding,
+    find::FindQuery,
     mut_raw_buffer::Change,
     raw_buffer::RawBuffer,
-    syntax::{ParserError, SyntaxParser},
+    syntax::{ParserError, SyntaxParser, SyntaxTrees},
 };
 use noa_common::{
     dirs::{backup_dir, noa_dir},
@@ -30,8 +32,8 @@ use noa_common::{
     prioritized_vec::PrioritizedVec,
 };
 
-use noa_editorconfig::EditorConfig;
-use noa_languages::{guess_language, tree_sitter, Language};
+use noa_editorconfig::{EditorConfig, EndOfLine};
+use noa_languages::{guess_language, Language};
 use tokio::sync::{
     mpsc::{self, UnboundedSender},
     Notify,
@@ -39,10 +41,12 @@ use tokio::sync::{
 
 use crate::{
     completion::{build_fuzzy_matcher, CompletionItem},
+    config::large_file_threshold,
     flash::FlashManager,
     git::{self, Repo},
     linemap::LineMap,
     movement::{Movement, MovementState},
+    undo_history::{load_undo_history, save_undo_history},
     view::View,
 };
 
@@ -78,21 +82,31 @@ pub struct Document {
     name: String,
     buffer: Buffer,
     saved_buffer: RawBuffer,
+    /// The line ending in the file. The document is dirty if it's converted
+    /// to another one.
+    saved_end_of_line: EndOfLine,
+    /// The encoding of the file. Used when saving the buffer.
+    encoding: Encoding,
+    /// If it's true, heavy features like syntax highlighting are disabled.
+    large_file: bool,
     view: View,
     movement_state: MovementState,
     completion_items: Vec<CompletionItem>,
     flashes: FlashManager,
     linemap: Arc<ArcSwap<LineMap>>,
     parser_tx: UnboundedSender<(RawBuffer, DocumentVersion, Vec<Change>)>,
-    updated_syntax_tx: UnboundedSender<(DocumentId, DocumentVersion, tree_sitter::Tree)>,
+    updated_syntax_tx: UnboundedSender<(DocumentId, DocumentVersion, SyntaxTrees)>,
 }
 
+/// The number of bytes used to detect the encoding of a large file.
+const LARGE_FILE_HEAD_LEN: u64 = 64 * 1024;
+
 static NEXT_DOCUMENT_ID: AtomicUsize = AtomicUsize::new(1);
 
 impl Document {
     pub fn new(
         path: &Path,
-        updated_syntax_tx: &UnboundedSender<(DocumentId, DocumentVersion, tree_sitter::Tree)>,
+        updated_syntax_tx: &UnboundedSender<(DocumentId, DocumentVersion, SyntaxTrees)>,
         disable_parser_for_test: bool,
     ) -> Result<Document> {
         // Allocate a document ID.
@@ -122,17 +136,52 @@ impl Document {
         }
 
         // Read the file contents.
-        let mut buffer = match OpenOptions::new().read(true).open(&path) {
-            Ok(file) => Buffer::from_reader(file)?,
-            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Buffer::new(),
+        let config = EditorConfig::resolve_or_guess(&path);
+        let preferred_encoding = config.charset.map(Encoding::from);
+        let (mut buffer, encoding, large_file) = match File::open(&path) {
+            Ok(mut file) if file.metadata()?.len() > large_file_threshold() => {
+                // Detect the encoding from the beginning of the file and
+                // decode the whole file into the buffer without keeping
+                // another copy of it.
+                let mut head = Vec::new();
+                (&mut file)
+                    .take(LARGE_FILE_HEAD_LEN)
+                    .read_to_end(&mut head)?;
+                let encoding = Encoding::detect(&head, preferred_encoding);
+                file.seek(SeekFrom::Start(0))?;
+                let buffer = Buffer::from_reader(encoding.decode_reader(BufReader::new(file)))?;
+                (buffer, encoding, true)
+            }
+            Ok(mut file) => {
+                let mut bytes = Vec::new();
+                file.read_to_end(&mut bytes)?;
+                let encoding = Encoding::detect(&bytes, preferred_encoding);
+                let buffer = Buffer::from_reader(encoding.decode(&bytes).as_bytes())?;
+                (buffer, encoding, false)
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                (Buffer::new(), preferred_encoding.unwrap_or_default(), false)
+            }
             Err(err) => {
                 return Err(err.into());
             }
         };
 
-        buffer.set_editorconfig(EditorConfig::resolve_or_guess(&path));
+        // Hashing the whole file to validate the undo history is too heavy
+        // for large files.
+        if !large_file {
+            load_undo_history(&path, &mut buffer);
+        }
+        buffer.set_editorconfig(config);
 
-        if let Some(lang) = guess_language(&path) {
+        // Syntax highlighting is too heavy for large files.
+        let lang = if large_file {
+            None
+        } else {
+            guess_language(&path)
+        };
+
+        if let Some(lang) = lang {
             match buffer.set_language(lang) {
                 Ok(()) => {}
                 Err(ParserError::NotSupportedLanguage) => {}
@@ -142,7 +191,7 @@ impl Document {
             }
         }
 
-        let parser_tx = if disable_parser_for_test {
+        let parser_tx = if disable_parser_for_test || large_file {
             mpsc::unbounded_channel().0
         } else {
             spawn_parser_task(
@@ -165,6 +214,9 @@ impl Document {
             virtual_file: false,
             name,
             saved_buffer: buffer.raw_buffer().clone(),
+            saved_end_of_line: buffer.end_of_line(),
+            encoding,
+            large_file,
             buffer,
             view: View::new(),
             movement_state: MovementState::new(),
@@ -189,7 +241,7 @@ impl Document {
 
     fn do_save_to_file(&mut self) {
         trace!("saving into a file: {}", self.path.display());
-        let with_sudo = match self.buffer.save_to_file(&self.path) {
+        let with_sudo = match self.buffer.save_to_file(&self.path, self.encoding) {
             Ok(()) => {
                 if let Some(backup_path) = &self.backup_path {
                     let _ = std::fs::remove_file(backup_path);
@@ -198,7 +250,10 @@ impl Document {
                 false
             }
             Err(err) if err.kind() == ErrorKind::PermissionDenied => {
-                match self.buffer.save_to_file_with_sudo(&self.path) {
+                match self
+                    .buffer
+                    .save_to_file_with_sudo(&self.path, self.encoding)
+                {
                     Ok(()) => {
                         if let Some(backup_path) = &self.backup_path {
                             let _ = std::fs::remove_file(backup_path);
@@ -219,6 +274,13 @@ impl Document {
         };
 
         self.saved_buffer = self.buffer.raw_buffer().clone();
+        self.saved_end_of_line = self.buffer.end_of_line();
+
+        if !self.virtual_file && !self.large_file {
+            // Saving may have modified the buffer (e.g. the final newline).
+            self.buffer.save_undo();
+            save_undo_history(&self.path, &self.buffer).oops();
+        }
 
         // FIXME: By any chance, the file was modified by another process
         // between saving the file and updating the last saved time here.
@@ -273,8 +335,8 @@ impl Document {
         self.virtual_file = virtual_file;
     }
 
-    pub fn set_syntax_tree(&mut self, version: DocumentVersion, syntax: tree_sitter::Tree) {
-        self.buffer.set_syntax_tree(syntax);
+    pub fn set_syntax_trees(&mut self, version: DocumentVersion, syntax: SyntaxTrees) {
+        self.buffer.set_syntax_trees(syntax);
         self.syntax_version = version;
     }
 
@@ -286,7 +348,8 @@ impl Document {
         let a = self.buffer.raw_buffer();
         let b = &self.saved_buffer;
 
-        a.len_chars() != b.len_chars() && a != b
+        (a.len_chars() != b.len_chars() && a != b)
+            || self.buffer.end_of_line() != self.saved_end_of_line
     }
 
     pub fn path(&self) -> &Path {
@@ -301,6 +364,14 @@ impl Document {
         &self.buffer
     }
 
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    pub fn is_large_file(&self) -> bool {
+        self.large_file
+    }
+
     pub fn raw_buffer(&self) -> &RawBuffer {
         self.buffer.raw_buffer()
     }
@@ -338,7 +409,7 @@ impl Document {
             .movement(&mut self.buffer, &mut self.view)
     }
 
-    pub fn layout_view(&mut self, find_query: &str, height: usize, width: usize) {
+    pub fn layout_view(&mut self, find_query: &FindQuery, height: usize, width: usize) {
         self.view.layout(&self.buffer, height, width);
         self.view.clear_highlights(height);
 
@@ -356,9 +427,8 @@ impl Document {
         }
 
         // Highlight find matches in visible rows.
-        for range in self
-            .buffer
-            .find_iter(find_query, self.view.first_visible_position())
+        for range in
+            find_query.find_iter(self.buffer.raw_buffer(), self.view.first_visible_position())
         {
             if range.front() > self.view.last_visible_position() {
                 break;
@@ -388,10 +458,34 @@ impl Document {
             }
         }
 
-        let file = OpenOptions::new().read(true).open(&self.path)?;
+        let bytes = std::fs::read(&self.path)?;
+        self.encoding = Encoding::detect(&bytes, Some(self.encoding));
         self.buffer.save_undo();
-        self.buffer.set_from_reader(file)?;
+        self.buffer
+            .set_from_reader(self.encoding.decode(&bytes).as_bytes())?;
         self.saved_buffer = self.buffer.raw_buffer().clone();
+        self.saved_end_of_line = self.buffer.end_of_line();
+        self.last_saved_at = Some(std::fs::metadata(&self.path)?.modified()?);
+
+        Ok(())
+    }
+
+    /// Reads the file again, decoding it in `encoding`. The BOM in the file,
+    /// if any, is kept on save.
+    pub fn reopen_with_encoding(&mut self, encoding: Encoding) -> Result<()> {
+        if self.is_dirty() {
+            bail!("unsaved changes");
+        }
+
+        let bytes = std::fs::read(&self.path)?;
+        let encoding = encoding.with_bom_in(&bytes);
+        self.buffer.save_undo();
+        self.buffer
+            .set_from_reader(encoding.decode(&bytes).as_bytes())?;
+        self.buffer.save_undo();
+        self.saved_buffer = self.buffer.raw_buffer().clone();
+        self.saved_end_of_line = self.buffer.end_of_line();
+        self.encoding = encoding;
         self.last_saved_at = Some(std::fs::metadata(&self.path)?.modified()?);
 
         Ok(())
@@ -412,7 +506,9 @@ impl Document {
         self.completion_items.clear();
         self.buffer.clear_undo_and_redo_stacks();
 
-        git::modified_hook(repo, self, render_request);
+        if !self.large_file {
+            git::modified_hook(repo, self, render_request);
+        }
     }
 
     pub fn idle_job(&mut self) {
@@ -434,7 +530,7 @@ fn spawn_parser_task(
     doc_id: DocumentId,
     lang: &'static Language,
     initial_buffer: RawBuffer,
-    updated_syntax_tx: UnboundedSender<(DocumentId, DocumentVersion, tree_sitter::Tree)>,
+    updated_syntax_tx: UnboundedSender<(DocumentId, DocumentVersion, SyntaxTrees)>,
 ) -> UnboundedSender<(RawBuffer, DocumentVersion, Vec<Change>)> {
     let (parser_tx, mut parser_rx) =
         mpsc::unbounded_channel::<(RawBuffer, DocumentVersion, Vec<Change>)>();
@@ -457,12 +553,12 @@ fn spawn_parser_task(
 
         // First, parse the whole buffer.
         parser.parse_fully(&initial_buffer);
-        let _ = updated_syntax_tx.send((doc_id, DocumentVersion::one(), parser.tree().clone()));
+        let _ = updated_syntax_tx.send((doc_id, DocumentVersion::one(), parser.trees()));
 
         // After that, parse the buffer incrementally...
         while let Some((raw_buffer, doc_ver, changes)) = parser_rx.blocking_recv() {
             parser.parse_incrementally(&raw_buffer, &changes);
-            let _ = updated_syntax_tx.send((doc_id, doc_ver, parser.tree().clone()));
+            let _ = updated_syntax_tx.send((doc_id, doc_ver, parser.trees()));
         }
     });
 
@@ -477,7 +573,7 @@ pub struct DocumentManager {
 
 impl DocumentManager {
     pub fn new(
-        updated_syntax_tx: &UnboundedSender<(DocumentId, DocumentVersion, tree_sitter::Tree)>,
+        updated_syntax_tx: &UnboundedSender<(DocumentId, DocumentVersion, SyntaxTrees)>,
         disable_parser_for_test: bool,
     ) -> DocumentManager {
         let mut scratch_doc = Document::new(
@@ -557,6 +653,7 @@ impl DocumentManager {
         let buffers: Vec<RawBuffer> = self
             .documents
             .values()
+            .filter(|doc| !doc.is_large_file())
             .map(|doc| doc.raw_buffer().clone())
             .collect();
 
@@ -8,12 +8,13 @@ use anyhow::Result;
 use noa_buffer::{
     buffer::Buffer,
     cursor::{Position, Range},
+    find::{FindOptions, FindQuery},
+    syntax::SyntaxTrees,
 };
 
 use noa_common::logger::OopsExt;
 use noa_compositor::line_edit::LineEdit;
 
-use noa_languages::tree_sitter;
 use tokio::sync::{
     mpsc::{self, UnboundedSender},
     Notify,
@@ -28,16 +29,27 @@ use crate::{
     search::CancelFlag,
 };
 
+/// `Editor::find_query` compiled with the text and the options it's built
+/// from.
+struct CompiledFindQuery {
+    text: String,
+    options: FindOptions,
+    /// `None` if the text is an invalid regex.
+    query: Option<Arc<FindQuery>>,
+}
+
 pub struct Editor {
     pub workspace_dir: PathBuf,
     pub documents: DocumentManager,
     pub jobs: JobManager,
     pub clipboard: Box<dyn ClipboardProvider>,
     pub find_query: LineEdit,
+    pub find_options: FindOptions,
+    compiled_find_query: Option<CompiledFindQuery>,
     pub repo: Option<Arc<Repo>>,
     pub render_request: Arc<Notify>,
     pub watch_tx: mpsc::UnboundedSender<WatchEvent>,
-    pub updated_syntax_tx: UnboundedSender<(DocumentId, DocumentVersion, tree_sitter::Tree)>,
+    pub updated_syntax_tx: UnboundedSender<(DocumentId, DocumentVersion, SyntaxTrees)>,
     pub finder_cancel_flag: Option<CancelFlag>,
 }
 
@@ -46,7 +58,7 @@ impl Editor {
         workspace_dir: &Path,
         render_request: Arc<Notify>,
         watch_tx: mpsc::UnboundedSender<WatchEvent>,
-        updated_syntax_tx: UnboundedSender<(DocumentId, DocumentVersion, tree_sitter::Tree)>,
+        updated_syntax_tx: UnboundedSender<(DocumentId, DocumentVersion, SyntaxTrees)>,
     ) -> Editor {
         let repo = match Repo::open(workspace_dir) {
             Ok(repo) => Some(Arc::new(repo)),
@@ -62,6 +74,8 @@ impl Editor {
             jobs: JobManager::new(),
             clipboard: clipboard::build_provider(),
             find_query: LineEdit::new(),
+            find_options: FindOptions::default(),
+            compiled_find_query: None,
             repo,
             render_request,
             watch_tx,
@@ -74,6 +88,28 @@ impl Editor {
         self.documents.current_mut().buffer_mut()
     }
 
+    /// Returns `find_query` compiled with `find_options`, or `None` if it's
+    /// an invalid regex. It's compiled again only if the text or the options
+    /// have been changed.
+    pub fn compiled_find_query(&mut self) -> Option<Arc<FindQuery>> {
+        let text = self.find_query.text();
+        let options = self.find_options;
+        let outdated = match &self.compiled_find_query {
+            Some(compiled) => compiled.text != text || compiled.options != options,
+            None => true,
+        };
+
+        if outdated {
+            self.compiled_find_query = Some(CompiledFindQuery {
+                query: FindQuery::new(&text, options).ok().map(Arc::new),
+                text,
+                options,
+            });
+        }
+
+        self.compiled_find_query.as_ref().unwrap().query.clone()
+    }
+
     pub fn open_file(&mut self, path: &Path, cursor_pos: Option<Position>) -> Result<DocumentId> {
         if let Some(doc) = self.documents.get_mut_document_by_path(path) {
             // Already opened. Just move the cursor and return.
@@ -53,7 +53,9 @@ mod job;
 mod linemap;
 mod movement;
 mod search;
+mod snippets;
 mod ui;
+mod undo_history;
 mod view;
 
 #[derive(Parser, Debug)]
@@ -189,9 +191,9 @@ async fn main() {
                     file_watch::watch_event_hook(&mut editor, &ev);
                 }
 
-                Some((doc_id, doc_ver, new_tree)) = updated_syntax_rx.recv() => {
+                Some((doc_id, doc_ver, new_trees)) = updated_syntax_rx.recv() => {
                     if let Some(doc) = editor.documents.get_mut_document_by_id(doc_id) {
-                        doc.set_syntax_tree(doc_ver, new_tree);
+                        doc.set_syntax_trees(doc_ver, new_trees);
                     }
                 }
 
@@ -222,11 +224,11 @@ async fn main() {
         // Interestingly, handling a compositor event and modifying a document
         // is super fast (less than 100 us in total in my machine).
         if editor.documents.current_mut().is_parsing_in_progress() {
-            if let Ok(Some((doc_id, doc_ver, new_tree))) =
+            if let Ok(Some((doc_id, doc_ver, new_trees))) =
                 timeout(Duration::from_millis(5), updated_syntax_rx.recv()).await
             {
                 if let Some(doc) = editor.documents.get_mut_document_by_id(doc_id) {
-                    doc.set_syntax_tree(doc_ver, new_tree);
+                    doc.set_syntax_trees(doc_ver, new_trees);
                 }
             }
         }
@@ -1,20 +1,34 @@
-use std::{cmp::max, collections::HashMap};
+use std::{
+    cmp::{max, min},
+    collections::HashMap,
+};
 
 use noa_buffer::{
     buffer::Buffer,
     cursor::{Cursor, CursorId, Position, Range},
+    extras::block_selection::DisplayPosition,
 };
 
 use crate::view::View;
 
+/// The last block selection. It's kept to extend it.
+struct BlockSelection {
+    anchor: DisplayPosition,
+    corner: DisplayPosition,
+    /// The main cursor selection made by the block selection.
+    main: Range,
+}
+
 pub struct MovementState {
     visual_xs: HashMap<CursorId, usize>,
+    block_selection: Option<BlockSelection>,
 }
 
 impl MovementState {
     pub fn new() -> MovementState {
         MovementState {
             visual_xs: HashMap::new(),
+            block_selection: None,
         }
     }
 
@@ -152,6 +166,56 @@ impl<'a> Movement<'a> {
         });
     }
 
+    pub fn select_block_up(&mut self) {
+        self.extend_block_selection(-1, 0);
+    }
+
+    pub fn select_block_down(&mut self) {
+        self.extend_block_selection(1, 0);
+    }
+
+    pub fn select_block_left(&mut self) {
+        self.extend_block_selection(0, -1);
+    }
+
+    pub fn select_block_right(&mut self) {
+        self.extend_block_selection(0, 1);
+    }
+
+    pub fn select_block(&mut self, anchor: DisplayPosition, corner: DisplayPosition) {
+        self.buffer.select_block(anchor, corner);
+        self.state.block_selection = Some(BlockSelection {
+            anchor,
+            corner,
+            main: self.buffer.main_cursor().selection(),
+        });
+    }
+
+    fn extend_block_selection(&mut self, y_diff: isize, x_diff: isize) {
+        let main = self.buffer.main_cursor();
+        let (anchor, corner) = match &self.state.block_selection {
+            Some(block) if block.main == main.selection() => (block.anchor, block.corner),
+            _ => (
+                self.buffer.display_position(main.fixed_position()),
+                self.buffer.display_position(main.moving_position()),
+            ),
+        };
+
+        let add = |n: usize, diff: isize| {
+            if diff < 0 {
+                n.saturating_sub(diff.abs() as usize)
+            } else {
+                n + diff as usize
+            }
+        };
+
+        let corner = DisplayPosition::new(
+            min(add(corner.y, y_diff), self.buffer.num_lines() - 1),
+            add(corner.column, x_diff),
+        );
+        self.select_block(anchor, corner);
+    }
+
     pub fn select_until_beginning_of_line(&mut self) {
         self.buffer.deselect_cursors();
 
@@ -230,6 +294,20 @@ impl<'a> Movement<'a> {
         self.update_cursors_with(|buffer, _, _, c| {
             let mut new_pos = c.moving_position();
             new_pos.move_by(buffer, 0, 0, left, right);
+
+            // Step over folded lines.
+            while let Some(fold) = buffer.hidden_fold_at(new_pos.y) {
+                if x_diff < 0 {
+                    new_pos = Position::new(fold.start, buffer.line_len(fold.start));
+                } else if fold.end + 1 < buffer.num_lines() {
+                    new_pos = Position::new(fold.end + 1, 0);
+                } else {
+                    // No visible lines after the fold.
+                    new_pos = c.moving_position();
+                    break;
+                }
+            }
+
             f(c, new_pos);
         });
     }
@@ -340,6 +418,31 @@ mod tests {
         assert_eq!(movement.buffer.cursors(), &[Cursor::new(2, 3)]);
     }
 
+    #[test]
+    fn cursor_movement_over_folds() {
+        // a
+        //   b (folded)
+        // c
+        let mut buffer = Buffer::from_text("a\n  b\nc");
+        buffer.set_cursors_for_test(&[Cursor::new(0, 0)]);
+        buffer.fold();
+        let mut view = View::new();
+        view.layout(&buffer, 16, 5);
+        let mut movement_state = MovementState::new();
+        let mut movement = movement_state.movement(&mut buffer, &mut view);
+
+        movement.buffer.set_cursors_for_test(&[Cursor::new(0, 1)]);
+        movement.move_cursors_right();
+        assert_eq!(movement.buffer.cursors(), &[Cursor::new(2, 0)]);
+        movement.move_cursors_left();
+        assert_eq!(movement.buffer.cursors(), &[Cursor::new(0, 1)]);
+
+        movement.move_cursors_down();
+        assert_eq!(movement.buffer.cursors(), &[Cursor::new(2, 1)]);
+        movement.move_cursors_up();
+        assert_eq!(movement.buffer.cursors(), &[Cursor::new(0, 1)]);
+    }
+
     #[test]
     fn cursor_movement_through_empty_text() {
         let mut buffer = Buffer::from_text("");
@@ -0,0 +1,116 @@
+use std::{collections::HashMap, path::Path, sync::Arc, time::SystemTime};
+
+use anyhow::Context;
+use noa_buffer::snippet::Snippet;
+use noa_common::dirs::user_config_dir;
+use noa_languages::Language;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::Deserialize;
+
+/// A snippet defined in `~/.config/noa/snippets/<language>.json`.
+#[derive(Clone, Debug)]
+pub struct UserSnippet {
+    pub prefix: String,
+    pub description: Option<String>,
+    pub snippet: Snippet,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// A snippet definition in the VS Code format.
+#[derive(Deserialize)]
+struct SnippetDef {
+    prefix: OneOrMany,
+    body: OneOrMany,
+    #[serde(default)]
+    description: Option<String>,
+}
+
+/// Snippets loaded from a file.
+struct CachedSnippets {
+    /// The modified time of the file when it was loaded.
+    modified: Option<SystemTime>,
+    snippets: Arc<Vec<UserSnippet>>,
+}
+
+/// Loaded snippets keyed by language names.
+type SnippetCache = HashMap<&'static str, CachedSnippets>;
+
+static USER_SNIPPETS: Lazy<Mutex<SnippetCache>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn parse_snippets_file(path: &Path) -> anyhow::Result<Vec<UserSnippet>> {
+    let defs: HashMap<String, SnippetDef> =
+        serde_json::from_str(&std::fs::read_to_string(path)?)
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+
+    let mut snippets = Vec::new();
+    for def in defs.into_values() {
+        let body = match def.body {
+            OneOrMany::One(body) => body,
+            OneOrMany::Many(lines) => lines.join("\n"),
+        };
+
+        let prefixes = match def.prefix {
+            OneOrMany::One(prefix) => vec![prefix],
+            OneOrMany::Many(prefixes) => prefixes,
+        };
+
+        let snippet = Snippet::parse(&body);
+        for prefix in prefixes {
+            snippets.push(UserSnippet {
+                prefix,
+                description: def.description.clone(),
+                snippet: snippet.clone(),
+            });
+        }
+    }
+
+    snippets.sort_by(|a, b| a.prefix.cmp(&b.prefix));
+    Ok(snippets)
+}
+
+/// Returns the user snippets for the language. They're reloaded when the file
+/// is modified.
+pub fn user_snippets_for(lang: &'static Language) -> Arc<Vec<UserSnippet>> {
+    let path = match user_config_dir() {
+        Some(dir) => dir.join("snippets").join(format!("{}.json", lang.name)),
+        None => return Arc::new(Vec::new()),
+    };
+
+    let modified = std::fs::metadata(&path)
+        .and_then(|meta| meta.modified())
+        .ok();
+    if let Some(cached) = USER_SNIPPETS.lock().get(lang.name) {
+        if cached.modified == modified {
+            return cached.snippets.clone();
+        }
+    }
+
+    // Load the file without holding the lock.
+    let snippets = if modified.is_some() {
+        match parse_snippets_file(&path) {
+            Ok(snippets) => Arc::new(snippets),
+            Err(err) => {
+                warn!("failed to load snippets: {:?}", err);
+                Arc::new(Vec::new())
+            }
+        }
+    } else {
+        Arc::new(Vec::new())
+    };
+
+    USER_SNIPPETS.lock().insert(
+        lang.name,
+        CachedSnippets {
+            modified,
+            snippets: snippets.clone(),
+        },
+    );
+    snippets
+}
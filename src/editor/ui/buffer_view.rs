@@ -20,7 +20,7 @@ use tokio::sync::{mpsc::UnboundedSender, Notify};
 
 use crate::{
     actions::execute_action_or_notify,
-    completion::{clear_completion, complete},
+    completion::{clear_completion, complete, show_snippet_choices},
     config::{get_keybinding_for, theme_for, KeyBindingScope},
     editor::Editor,
     linemap::LineStatus,
@@ -61,7 +61,7 @@ impl BufferView {
 
     pub fn show_completion(&mut self, editor: &mut Editor) {
         let doc = editor.documents.current_mut();
-        if doc.buffer().cursors().len() != 1 {
+        if doc.buffer().cursors().len() != 1 || doc.is_large_file() {
             return;
         }
 
@@ -129,16 +129,18 @@ impl Surface for BufferView {
         let buffer_width;
         let buffer_height;
         {
+            // An invalid regex highlights nothing.
+            let find_query = editor.compiled_find_query().unwrap_or_default();
+
             let doc = editor.documents.current_mut();
             let buffer = doc.buffer();
             lineno_x = 1;
             max_lineno_width = buffer.num_lines().display_width();
             buffer_y = 0;
-            buffer_x = lineno_x + max_lineno_width + 1 /* line status */;
+            buffer_x = lineno_x + max_lineno_width + 1 /* fold marker */ + 1 /* line status */;
             buffer_width = canvas.width() - buffer_x - 1 /* row_end_marker */;
             buffer_height = canvas.height();
-
-            doc.layout_view(&editor.find_query.text(), buffer_height, buffer_width);
+            doc.layout_view(&find_query, buffer_height, buffer_width);
         }
 
         self.buffer_x = buffer_x;
@@ -193,6 +195,16 @@ impl Surface for BufferView {
                 canvas.write_str(canvas_y, lineno_x, &format!("{}", row.lineno));
             }
 
+            // Draw fold marker.
+            if row.folded {
+                canvas.write_char_with_style(
+                    canvas_y,
+                    lineno_x + lineno_width,
+                    '▸',
+                    theme_for("buffer.fold_marker"),
+                );
+            }
+
             // Draw each characters in the row.
             let mut virtual_cursor_x = None;
             let mut canvas_x = buffer_x;
@@ -295,6 +307,7 @@ impl Surface for BufferView {
     ) -> HandledEvent {
         const NONE: KeyModifiers = KeyModifiers::NONE;
         const CTRL: KeyModifiers = KeyModifiers::CONTROL;
+        const ALT: KeyModifiers = KeyModifiers::ALT;
         const SHIFT: KeyModifiers = KeyModifiers::SHIFT;
 
         let doc = editor.documents.current_mut();
@@ -340,14 +353,34 @@ impl Surface for BufferView {
             (KeyCode::Right, SHIFT) => {
                 doc.movement().select_right();
             }
+            (KeyCode::Up, modifiers) if modifiers == ALT | SHIFT => {
+                doc.movement().select_block_up();
+            }
+            (KeyCode::Down, modifiers) if modifiers == ALT | SHIFT => {
+                doc.movement().select_block_down();
+            }
+            (KeyCode::Left, modifiers) if modifiers == ALT | SHIFT => {
+                doc.movement().select_block_left();
+            }
+            (KeyCode::Right, modifiers) if modifiers == ALT | SHIFT => {
+                doc.movement().select_block_right();
+            }
             (KeyCode::Enter, NONE) => {
                 doc.buffer_mut().insert_newline_and_indent();
             }
             (KeyCode::Tab, NONE) => {
-                doc.buffer_mut().indent();
+                if doc.buffer_mut().move_to_next_tabstop() {
+                    show_snippet_choices(compositor, doc);
+                } else {
+                    doc.buffer_mut().indent();
+                }
             }
             (KeyCode::BackTab, _) => {
-                doc.buffer_mut().deindent();
+                if doc.buffer_mut().move_to_prev_tabstop() {
+                    show_snippet_choices(compositor, doc);
+                } else {
+                    doc.buffer_mut().deindent();
+                }
             }
             (KeyCode::Char(ch), NONE) | (KeyCode::Char(ch), SHIFT) => {
                 doc.buffer_mut().smart_insert_char(ch);
@@ -483,6 +516,17 @@ impl Surface for BufferView {
                         }
                         _ => {}
                     },
+                    // Dragging + Alt: block selection.
+                    (MouseEventKind::Drag(MouseButton::Left), ALT) => {
+                        if let Some(start) = self.selection_start {
+                            let anchor = doc.buffer().display_position(start);
+                            let mut corner = doc.buffer().display_position(clicked_pos);
+                            corner.column += doc
+                                .view()
+                                .columns_beyond_row_end(surface_y, surface_x - self.buffer_x);
+                            doc.movement().select_block(anchor, corner);
+                        }
+                    }
                     // Single click + Shift.
                     (MouseEventKind::Up(MouseButton::Left), SHIFT)
                         if self.time_last_clicked.elapsed() > Duration::from_millis(400) =>
@@ -7,7 +7,7 @@ use noa_compositor::{
     Compositor,
 };
 
-use crate::{config::theme_for, editor::Editor};
+use crate::{completion::snippet_choice_items, config::theme_for, editor::Editor};
 
 use super::helpers::truncate_to_width;
 
@@ -99,14 +99,34 @@ impl Surface for CompletionView {
                 self.set_active(false);
             }
             (KeyCode::Tab, NONE) => {
-                if let Some(item) = doc.completion_items().get(self.selected_index).cloned() {
+                let item = doc.completion_items().get(self.selected_index).cloned();
+                doc.clear_completion_items();
+                self.set_active(false);
+
+                if let Some(item) = item {
                     doc.buffer_mut().save_undo();
                     doc.buffer_mut().apply_text_edits(item.text_edits);
+                    if let Some(snippet) = item.snippet {
+                        let path = doc.path().to_owned();
+                        doc.buffer_mut().insert_snippet(&snippet, |name| {
+                            let value = match name {
+                                "TM_FILENAME" => path.file_name()?,
+                                "TM_FILENAME_BASE" => path.file_stem()?,
+                                "TM_DIRECTORY" => path.parent()?.as_os_str(),
+                                "TM_FILEPATH" => path.as_os_str(),
+                                _ => return None,
+                            };
+
+                            Some(value.to_string_lossy().into_owned())
+                        });
+                    }
                     doc.buffer_mut().save_undo();
-                }
 
-                doc.clear_completion_items();
-                self.set_active(false);
+                    if let Some(items) = snippet_choice_items(doc.buffer()) {
+                        doc.set_completion_items(items);
+                        self.set_active(true);
+                    }
+                }
             }
             (KeyCode::Up, NONE) => {
                 // In case the # of items was decreased (I think won't happen though).
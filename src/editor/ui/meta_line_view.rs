@@ -1,4 +1,4 @@
-use noa_buffer::display_width::DisplayWidth;
+use noa_buffer::{display_width::DisplayWidth, encoding::Encoding};
 use noa_compositor::{
     canvas::CanvasViewMut,
     surface::{HandledEvent, KeyEvent, Layout, RectSize, Surface},
@@ -7,6 +7,7 @@ use noa_compositor::{
 };
 
 use crate::{
+    actions::execute_action_or_notify,
     config::theme_for,
     editor::Editor,
     notification::{notification_manager, Notification},
@@ -71,6 +72,7 @@ impl Surface for MetaLineView {
         canvas.clear();
 
         let search_query = editor.find_query.text();
+        let invalid_regex = editor.compiled_find_query().is_none();
         let doc = editor.documents.current();
         let view = doc.view();
         let buffer = doc.buffer();
@@ -80,9 +82,35 @@ impl Surface for MetaLineView {
 
         match self.mode {
             MetaLineMode::Search => {
+                // Search options (toggled by Alt+R, Alt+C, and Alt+W).
+                let options = editor.find_options;
+                let options_text = [
+                    (invalid_regex, "[invalid regex]"),
+                    (options.regex, "[regex]"),
+                    (options.case_insensitive, "[icase]"),
+                    (options.whole_word, "[word]"),
+                ]
+                .iter()
+                .filter(|(enabled, _)| *enabled)
+                .map(|(_, text)| *text)
+                .collect::<Vec<_>>()
+                .join(" ");
+
                 // Search query.
-                let truncated_query = truncate_to_width_suffix(&search_query, canvas.width());
+                let truncated_query = truncate_to_width_suffix(
+                    &search_query,
+                    canvas
+                        .width()
+                        .saturating_sub(options_text.display_width() + 3),
+                );
                 canvas.write_str(0, 1, truncated_query);
+                canvas.write_str(
+                    0,
+                    canvas
+                        .width()
+                        .saturating_sub(1 + options_text.display_width()),
+                    &options_text,
+                );
             }
             MetaLineMode::Normal => {
                 // Cursor position.
@@ -119,7 +147,17 @@ impl Surface for MetaLineView {
                 // Are there any in-progress async jobs?
                 let is_busy = if editor.jobs.is_busy() { "[busy]" } else { "" };
 
-                let left_text = [is_dirty].join(" ");
+                // Show the encoding only if it's not the ordinary UTF-8.
+                let encoding = if doc.encoding() != Encoding::UTF_8 {
+                    format!("[{}]", doc.encoding())
+                } else {
+                    String::new()
+                };
+
+                // Heavy features are disabled for the file.
+                let is_large_file = if doc.is_large_file() { "[large]" } else { "" };
+
+                let left_text = [is_dirty, &encoding, is_large_file].join(" ");
                 let right_text = [is_busy, &cursor_text].join(" ");
 
                 // File name.
@@ -160,12 +198,12 @@ impl Surface for MetaLineView {
     fn handle_key_event(
         &mut self,
         editor: &mut Editor,
-        _compositor: &mut Compositor<Self::Context>,
+        compositor: &mut Compositor<Self::Context>,
         key: KeyEvent,
     ) -> HandledEvent {
         const NONE: KeyModifiers = KeyModifiers::NONE;
         const CTRL: KeyModifiers = KeyModifiers::CONTROL;
-        // const ALT: KeyModifiers = KeyModifiers::ALT;
+        const ALT: KeyModifiers = KeyModifiers::ALT;
         // const SHIFT: KeyModifiers = KeyModifiers::SHIFT;
 
         match self.mode {
@@ -178,6 +216,29 @@ impl Surface for MetaLineView {
                     self.mode = MetaLineMode::Normal;
                     editor.find_query.save_undo();
                 }
+                (KeyCode::Char('r'), CTRL)
+                | (KeyCode::Char('a'), ALT)
+                | (KeyCode::Char('s'), ALT) => {
+                    // Replace the next one (Ctrl+R), all (Alt+A), or ones in
+                    // the selection (Alt+S).
+                    self.mode = MetaLineMode::Normal;
+                    editor.find_query.save_undo();
+                    let action = match key.code {
+                        KeyCode::Char('a') => "replace_all",
+                        KeyCode::Char('s') => "replace_in_selection",
+                        _ => "replace_next",
+                    };
+                    execute_action_or_notify(editor, compositor, action);
+                }
+                (KeyCode::Char('r'), ALT) => {
+                    editor.find_options.regex = !editor.find_options.regex;
+                }
+                (KeyCode::Char('c'), ALT) => {
+                    editor.find_options.case_insensitive = !editor.find_options.case_insensitive;
+                }
+                (KeyCode::Char('w'), ALT) => {
+                    editor.find_options.whole_word = !editor.find_options.whole_word;
+                }
                 _ => {
                     editor.find_query.consume_key_event(key);
                 }
@@ -0,0 +1,116 @@
+use std::{
+    collections::HashMap,
+    fs::OpenOptions,
+    io::{BufReader, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use anyhow::Result;
+use noa_buffer::{buffer::Buffer, raw_buffer::RawBuffer, undo_tree::UndoHistory};
+use noa_common::dirs::{path_into_dotted_str, undo_dir};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct UndoHistoryFile {
+    /// The hash of the file contents when the history was saved.
+    content_hash: u64,
+    history: UndoHistory,
+}
+
+/// The 64-bit FNV-1a hash of the text. Unlike `DefaultHasher`, the algorithm
+/// is fixed so that saved histories stay valid across Rust versions.
+fn content_hash(buf: &RawBuffer) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    let mut hash = OFFSET_BASIS;
+    for chunk in buf.rope().chunks() {
+        for byte in chunk.as_bytes() {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(PRIME);
+        }
+    }
+    hash
+}
+
+/// Incremented on every save to order background writes.
+static SAVE_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// The sequence number of the last history written for each path. An older
+/// history is not written over a newer one.
+static WRITTEN_SEQS: Lazy<Mutex<HashMap<PathBuf, u64>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn undo_history_path(path: &Path) -> PathBuf {
+    undo_dir().join(format!("{}.json", path_into_dotted_str(path)))
+}
+
+/// Saves the undo history of the buffer just written into `path`. The history
+/// is serialized here but written in a background thread since it can be
+/// large.
+pub fn save_undo_history(path: &Path, buffer: &Buffer) -> Result<()> {
+    let history = match buffer.undo_history() {
+        Some(history) => history,
+        None => return Ok(()),
+    };
+
+    let file = UndoHistoryFile {
+        content_hash: content_hash(buffer.raw_buffer()),
+        history,
+    };
+
+    let json = serde_json::to_vec(&file)?;
+    let history_path = undo_history_path(path);
+    let seq = SAVE_SEQ.fetch_add(1, Ordering::SeqCst);
+    std::thread::spawn(move || {
+        let mut written_seqs = WRITTEN_SEQS.lock();
+        if matches!(written_seqs.get(&history_path), Some(written) if *written > seq) {
+            return;
+        }
+
+        let result = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&history_path)
+            .and_then(|mut writer| writer.write_all(&json));
+        if let Err(err) = result {
+            warn!(
+                "failed to save undo history {}: {}",
+                history_path.display(),
+                err
+            );
+        }
+
+        written_seqs.insert(history_path, seq);
+    });
+
+    Ok(())
+}
+
+/// Loads the undo history saved for `path` if the file has not been modified
+/// since then. Stale or corrupt history files are removed.
+pub fn load_undo_history(path: &Path, buffer: &mut Buffer) {
+    let history_path = undo_history_path(path);
+    let reader = match OpenOptions::new().read(true).open(&history_path) {
+        Ok(reader) => reader,
+        Err(_) => return,
+    };
+
+    let restored = match serde_json::from_reader::<_, UndoHistoryFile>(BufReader::new(reader)) {
+        Ok(file) if file.content_hash == content_hash(buffer.raw_buffer()) => {
+            buffer.restore_undo_history(file.history)
+        }
+        Ok(_) => false,
+        Err(err) => {
+            trace!("corrupt undo history {}: {}", history_path.display(), err);
+            false
+        }
+    };
+
+    if !restored {
+        let _ = std::fs::remove_file(&history_path);
+    }
+}
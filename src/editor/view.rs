@@ -1,4 +1,4 @@
-use std::cmp::min;
+use std::cmp::{max, min};
 
 use arrayvec::ArrayString;
 use noa_buffer::{
@@ -27,6 +27,9 @@ pub struct DisplayRow {
     pub graphemes: Vec<Grapheme>,
     /// The positions in the buffer for each grapheme.
     pub positions: Vec<Position>,
+    /// Whether the row is the first one of a line whose following lines are
+    /// folded.
+    pub folded: bool,
 }
 
 impl DisplayRow {
@@ -90,7 +93,14 @@ impl DisplayRow {
 }
 
 pub struct View {
+    /// The display rows of the lines around the viewport. Lines far from the
+    /// viewport are not laid out.
     rows: Vec<DisplayRow>,
+    /// The line index (starts at 0) of the first row in `rows`.
+    first_line: usize,
+    /// The index in `rows` of the first row of each line, i.e.
+    /// `line_rows[0]` is the first row of the `first_line`-th line.
+    line_rows: Vec<usize>,
     scroll_x: usize,
     scroll_y: usize,
     height: usize,
@@ -101,6 +111,8 @@ impl View {
     pub fn new() -> View {
         View {
             rows: Vec::new(),
+            first_line: 0,
+            line_rows: Vec::new(),
             scroll_x: 0,
             scroll_y: 0,
             height: 0,
@@ -205,6 +217,19 @@ impl View {
 
     /// Update characters' styles in the given range.
     pub fn do_highlight(&mut self, range: Range, style: Style) {
+        // Ignore the part which is not laid out.
+        let (first_row, last_row) = match (self.rows.first(), self.rows.last()) {
+            (Some(first_row), Some(last_row)) => (first_row, last_row),
+            _ => return,
+        };
+        let front = max(range.front(), first_row.first_position());
+        let back = min(range.back(), last_row.end_of_row_position());
+        if front > back {
+            return;
+        }
+
+        let range = Range::from_positions(front, back);
+
         // We don't handle out of bounds ranges because if a buffer is rendered
         // before the tree-sitter finishes parsing, tree-sitter may report a
         // highlight ranges of the previous version, which may be out of
@@ -247,7 +272,8 @@ impl View {
         }
     }
 
-    /// Computes the grapheme layout (text wrapping).
+    /// Computes the grapheme layout (text wrapping) of the lines around the
+    /// viewport.
     pub fn layout(&mut self, buffer: &Buffer, height: usize, width: usize) {
         use rayon::prelude::*;
 
@@ -258,19 +284,81 @@ impl View {
             std::usize::MAX
         };
 
+        // Determine the line at the top of the viewport and how many wrapped
+        // rows of it are scrolled out.
+        let num_lines = buffer.num_lines();
+        let main_pos = buffer.main_cursor().moving_position();
+        let (mut top_line, mut top_offset) = match self.rows.get(self.scroll_y) {
+            Some(row) => {
+                let y = row.lineno - 1;
+                (y, self.scroll_y - self.line_rows[y - self.first_line])
+            }
+            None => (0, 0),
+        };
+
+        if top_line >= num_lines || main_pos.y < top_line || main_pos.y > top_line + height {
+            // The viewport is going to jump to the cursor.
+            top_line = min(top_line, num_lines - 1);
+            top_line = top_line.clamp(main_pos.y.saturating_sub(height), main_pos.y);
+            top_offset = 0;
+        }
+
+        // The line may have been folded. The first line is never hidden.
+        while buffer.is_line_hidden(top_line) {
+            top_line -= 1;
+            top_offset = 0;
+        }
+
+        // Lay out visible lines within a screen height from the viewport so
+        // that scrolling and moving cursors by a page work without the
+        // layout. Since each visible line has at least one row, they cover
+        // the viewport.
         self.height = height;
-        self.rows = (0..buffer.num_lines())
+        self.first_line = top_line;
+        let mut num_visible_lines = 0;
+        while self.first_line > 0 && num_visible_lines < height {
+            self.first_line -= 1;
+            if !buffer.is_line_hidden(self.first_line) {
+                num_visible_lines += 1;
+            }
+        }
+
+        let mut end_line = top_line;
+        let mut num_visible_lines = 0;
+        while end_line < num_lines && num_visible_lines < 2 * height + 1 {
+            if !buffer.is_line_hidden(end_line) {
+                num_visible_lines += 1;
+            }
+            end_line += 1;
+        }
+
+        let lines: Vec<Vec<DisplayRow>> = (self.first_line..end_line)
             .into_par_iter()
             .map(|y| {
-                let rows = self.layout_line(buffer, y, layout_width);
+                // Hidden lines have no rows.
+                if buffer.is_line_hidden(y) {
+                    return Vec::new();
+                }
+
+                let mut rows = self.layout_line(buffer, y, layout_width);
                 debug_assert!(!rows.is_empty());
+                rows[0].folded = buffer.is_folded(y);
                 rows
             })
-            .flatten()
             .collect();
 
+        self.rows.clear();
+        self.line_rows.clear();
+        for rows in lines {
+            self.line_rows.push(self.rows.len());
+            self.rows.extend(rows);
+        }
+
+        let top_line_rows = self.line_rows[top_line - self.first_line];
+        let top_line_len = self.rows_in_line(top_line - self.first_line).len();
+        self.scroll_y = top_line_rows + min(top_offset, top_line_len - 1);
+
         // Adjust scroll_y and scroll_x if necessary.
-        let main_pos = buffer.main_cursor().moving_position();
         while main_pos < self.first_visible_position() {
             self.scroll_y -= 1;
         }
@@ -296,6 +384,17 @@ impl View {
         debug_assert!(self.scroll_y < self.rows.len());
     }
 
+    /// Returns the rows of the `i`-th laid out line.
+    fn rows_in_line(&self, i: usize) -> &[DisplayRow] {
+        let start = self.line_rows[i];
+        let end = self
+            .line_rows
+            .get(i + 1)
+            .copied()
+            .unwrap_or(self.rows.len());
+        &self.rows[start..end]
+    }
+
     /// Layouts a single physical (separated by "\n") line.
     fn layout_line(&self, buffer: &Buffer, y: usize, width: usize) -> Vec<DisplayRow> {
         let mut grapheme_iter = buffer.grapheme_iter(Position::new(y, 0));
@@ -393,6 +492,7 @@ impl View {
                 len_chars,
                 graphemes,
                 positions,
+                folded: false,
             });
         }
 
@@ -400,15 +500,24 @@ impl View {
     }
 
     /// Returns the index of the display row and the index within the row.
+    /// Returns `None` if the position is not laid out.
     pub fn locate_row_by_position(&self, pos: Position) -> Option<(usize, usize)> {
-        let i_y = self
-            .rows
-            .partition_point(|row| pos >= row.first_position() || row.range().contains(pos));
+        let i = pos.y.checked_sub(self.first_line)?;
+        if i >= self.line_rows.len() {
+            return None;
+        }
 
+        let rows = self.rows_in_line(i);
+        if rows.is_empty() {
+            // The line is hidden by a fold.
+            return None;
+        }
+
+        let i_y =
+            rows.partition_point(|row| pos >= row.first_position() || row.range().contains(pos));
         debug_assert!(i_y > 0);
-        let row = &self.rows[i_y - 1];
-        let i_x = row.locate_column_by_position(pos)?;
-        Some((i_y - 1, i_x))
+        let i_x = rows[i_y - 1].locate_column_by_position(pos)?;
+        Some((self.line_rows[i] + i_y - 1, i_x))
     }
 
     pub fn get_position_from_screen_yx(&self, y: usize, x: usize) -> Option<Position> {
@@ -419,6 +528,18 @@ impl View {
                 .unwrap_or_else(|| Position::new(row.lineno - 1, row.len_chars()))
         })
     }
+
+    /// Returns how many columns the screen position is beyond the end of the
+    /// row.
+    pub fn columns_beyond_row_end(&self, y: usize, x: usize) -> usize {
+        self.rows
+            .get(self.scroll_y + y)
+            .map(|row| {
+                let row_width: usize = row.graphemes.iter().map(|g| g.width).sum();
+                x.saturating_sub(row_width)
+            })
+            .unwrap_or(0)
+    }
 }
 
 #[cfg(test)]
@@ -482,6 +603,7 @@ mod tests {
                 len_chars: 3,
                 graphemes: vec![g2("A", Red), g2("B", Red), g("C")],
                 positions: vec![p(0, 0), p(0, 1), p(0, 2)],
+                folded: false,
             },]
         );
     }
@@ -516,6 +638,40 @@ mod tests {
         assert_eq!(view.rows[1].positions, vec![p(0, 3), p(0, 4), p(0, 5)]);
     }
 
+    #[test]
+    fn layout_only_around_viewport() {
+        let mut view = View::new();
+        let mut buffer = Buffer::from_text(&"A\n".repeat(1000));
+
+        view.layout(&buffer, 10, 5);
+        assert_eq!(view.rows.len(), 21);
+        assert_eq!(view.first_visible_position(), p(0, 0));
+
+        // Jump to the middle of the buffer.
+        buffer.set_cursors_for_test(&[Cursor::new(500, 0)]);
+        view.layout(&buffer, 10, 5);
+        assert_eq!(view.rows.len(), 31);
+        assert_eq!(view.first_visible_position(), p(491, 0));
+        assert_eq!(view.locate_row_by_position(p(500, 0)), Some((20, 0)));
+        assert_eq!(view.locate_row_by_position(p(0, 0)), None);
+        assert_eq!(view.locate_row_by_position(p(999, 0)), None);
+
+        // The scroll position is kept across layouts.
+        buffer.set_cursors_for_test(&[Cursor::new(495, 0)]);
+        view.layout(&buffer, 10, 5);
+        assert_eq!(view.first_visible_position(), p(491, 0));
+        assert_eq!(view.locate_row_by_position(p(495, 0)), Some((14, 0)));
+
+        // Highlights are clipped to the laid out lines.
+        let style = Style {
+            fg: Color::Red,
+            ..Default::default()
+        };
+        view.do_highlight(Range::new(0, 0, 999, 0), style);
+        assert_eq!(view.rows[0].graphemes[0].style, style);
+        assert_eq!(view.rows.last().unwrap().graphemes[0].style, style);
+    }
+
     #[test]
     fn test_layout_tabs() {
         let mut view = View::new();
@@ -692,6 +848,36 @@ mod tests {
         assert_eq!(view.locate_row_by_position(p(1, 0)), Some((2, 0)));
     }
 
+    #[test]
+    fn columns_beyond_row_end() {
+        let mut view = View::new();
+        view.layout(&Buffer::from_text("aあ\n\tb"), 2, 16);
+        assert_eq!(view.columns_beyond_row_end(0, 2), 0);
+        assert_eq!(view.columns_beyond_row_end(0, 5), 2);
+        assert_eq!(view.columns_beyond_row_end(1, 5), 0);
+        assert_eq!(view.columns_beyond_row_end(1, 11), 2);
+        assert_eq!(view.columns_beyond_row_end(2, 11), 0);
+    }
+
+    #[test]
+    fn layout_with_folds() {
+        let mut buffer = Buffer::from_text("a\n  b\n  c\nd");
+        buffer.set_cursors_for_test(&[Cursor::new(1, 0)]);
+        buffer.fold();
+
+        let mut view = View::new();
+        view.layout(&buffer, 3, 5);
+        assert_eq!(
+            view.rows
+                .iter()
+                .map(|row| (row.lineno, row.folded))
+                .collect::<Vec<_>>(),
+            vec![(1, true), (4, false)]
+        );
+        assert_eq!(view.locate_row_by_position(p(1, 0)), None);
+        assert_eq!(view.locate_row_by_position(p(3, 0)), Some((1, 0)));
+    }
+
     #[bench]
     fn bench_layout_single_line(b: &mut test::Bencher) {
         let (mut view, buffer) = create_view_and_buffer(1);
@@ -0,0 +1,67 @@
+use crate::EndOfLine;
+
+/// The number of each kind of line breaks in a text.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LineBreakCounts {
+    pub lf: usize,
+    pub crlf: usize,
+    pub cr: usize,
+}
+
+impl LineBreakCounts {
+    /// Returns the most common line ending, or `None` if there are no line
+    /// breaks.
+    pub fn most_common(&self) -> Option<EndOfLine> {
+        let LineBreakCounts { lf, crlf, cr } = *self;
+        if lf == 0 && crlf == 0 && cr == 0 {
+            None
+        } else if lf >= crlf && lf >= cr {
+            Some(EndOfLine::Lf)
+        } else if crlf >= cr {
+            Some(EndOfLine::CrLf)
+        } else {
+            Some(EndOfLine::Cr)
+        }
+    }
+}
+
+/// Returns the most common line ending in the text, or `None` if the text has
+/// no line breaks.
+pub fn detect_end_of_line(text: &str) -> Option<EndOfLine> {
+    let mut counts = LineBreakCounts::default();
+    let mut iter = text.bytes().peekable();
+    while let Some(b) = iter.next() {
+        match b {
+            b'\r' if iter.peek() == Some(&b'\n') => {
+                iter.next();
+                counts.crlf += 1;
+            }
+            b'\r' => counts.cr += 1,
+            b'\n' => counts.lf += 1,
+            _ => {}
+        }
+    }
+
+    counts.most_common()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn detect() {
+        assert_eq!(detect_end_of_line(""), None);
+        assert_eq!(detect_end_of_line("abc"), None);
+        assert_eq!(detect_end_of_line("a\nb\n"), Some(EndOfLine::Lf));
+        assert_eq!(detect_end_of_line("a\r\nb\r\n"), Some(EndOfLine::CrLf));
+        assert_eq!(detect_end_of_line("a\rb\r"), Some(EndOfLine::Cr));
+    }
+
+    #[test]
+    fn mixed_line_endings() {
+        assert_eq!(detect_end_of_line("a\r\nb\r\nc\n"), Some(EndOfLine::CrLf));
+        assert_eq!(detect_end_of_line("a\r\nb\nc\n"), Some(EndOfLine::Lf));
+    }
+}
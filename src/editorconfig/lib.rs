@@ -4,11 +4,14 @@ extern crate log;
 use std::{
     env::current_dir,
     error::Error,
+    fmt,
     path::{Path, PathBuf},
 };
 
+mod detect_end_of_line;
 mod detect_indent;
 
+pub use detect_end_of_line::{detect_end_of_line, LineBreakCounts};
 pub use detect_indent::detect_indent_style;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -24,14 +27,56 @@ pub enum EndOfLine {
     CrLf,
 }
 
+impl EndOfLine {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EndOfLine::Cr => "\r",
+            EndOfLine::Lf => "\n",
+            EndOfLine::CrLf => "\r\n",
+        }
+    }
+}
+
+impl fmt::Display for EndOfLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EndOfLine::Cr => write!(f, "CR"),
+            EndOfLine::Lf => write!(f, "LF"),
+            EndOfLine::CrLf => write!(f, "CRLF"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    Latin1,
+    Utf8,
+    Utf8Bom,
+    Utf16Be,
+    Utf16Le,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaxLineLength {
+    /// `off`: lines should not be wrapped.
+    Off,
+    Columns(usize),
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct EditorConfig {
     pub indent_style: IndentStyle,
     pub indent_size: usize,
     pub tab_width: usize,
-    // TODO: Implement `end_of_line`.
-    pub end_of_line: EndOfLine,
+    /// `None` if not specified. The line ending detected in the file is used
+    /// instead.
+    pub end_of_line: Option<EndOfLine>,
+    /// `None` if not specified. The encoding is detected from the file
+    /// contents instead.
+    pub charset: Option<Charset>,
     pub insert_final_newline: bool,
+    /// `None` if not specified.
+    pub max_line_length: Option<MaxLineLength>,
 }
 
 impl EditorConfig {
@@ -62,8 +107,10 @@ impl Default for EditorConfig {
             indent_style: IndentStyle::Space,
             indent_size: 4,
             tab_width: 8,
-            end_of_line: EndOfLine::Lf,
+            end_of_line: None,
+            charset: None,
             insert_final_newline: false,
+            max_line_length: None,
         }
     }
 }
@@ -86,7 +133,9 @@ struct Rule {
     indent_size: Option<usize>,
     tab_width: Option<usize>,
     end_of_line: Option<EndOfLine>,
+    charset: Option<Charset>,
     insert_final_newline: Option<bool>,
+    max_line_length: Option<MaxLineLength>,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -154,6 +203,24 @@ fn parse_config(body: &str) -> ConfigFile {
                         }
                         _ => {}
                     },
+                    "charset" => match value {
+                        "latin1" => {
+                            rule.charset = Some(Charset::Latin1);
+                        }
+                        "utf-8" => {
+                            rule.charset = Some(Charset::Utf8);
+                        }
+                        "utf-8-bom" => {
+                            rule.charset = Some(Charset::Utf8Bom);
+                        }
+                        "utf-16be" => {
+                            rule.charset = Some(Charset::Utf16Be);
+                        }
+                        "utf-16le" => {
+                            rule.charset = Some(Charset::Utf16Le);
+                        }
+                        _ => {}
+                    },
                     "indent_size" => {
                         if let Ok(value) = value.parse::<usize>() {
                             rule.indent_size = Some(value);
@@ -164,6 +231,13 @@ fn parse_config(body: &str) -> ConfigFile {
                             rule.tab_width = Some(value);
                         }
                     }
+                    "max_line_length" => {
+                        if value == "off" {
+                            rule.max_line_length = Some(MaxLineLength::Off);
+                        } else if let Ok(value) = value.parse::<usize>() {
+                            rule.max_line_length = Some(MaxLineLength::Columns(value));
+                        }
+                    }
                     _ => {}
                 }
             }
@@ -285,9 +359,12 @@ fn resolve_config(source_file: &Path) -> Option<EditorConfig> {
                 ret.indent_style = rule.indent_style.unwrap_or(ret.indent_style);
                 ret.indent_size = rule.indent_size.unwrap_or(ret.indent_size);
                 ret.tab_width = rule.tab_width.unwrap_or(ret.tab_width);
+                ret.end_of_line = rule.end_of_line.or(ret.end_of_line);
+                ret.charset = rule.charset.or(ret.charset);
                 ret.insert_final_newline = rule
                     .insert_final_newline
                     .unwrap_or(ret.insert_final_newline);
+                ret.max_line_length = rule.max_line_length.or(ret.max_line_length);
 
                 matched_any = true;
             }
@@ -329,12 +406,15 @@ mod tests {
 
                 [*.rs] # comment
                 indent_size = 4 # comment
+                max_line_length = 100
 
                 [*.md]
                 indent_style = tab
                 tab_width = 8
                 end_of_line = crlf
+                charset = utf-16le
                 insert_final_newline = false
+                max_line_length = off
 
                 [broken]
                 foo =
@@ -350,7 +430,9 @@ mod tests {
                         indent_size: Some(4),
                         tab_width: None,
                         end_of_line: None,
+                        charset: None,
                         insert_final_newline: None,
+                        max_line_length: Some(MaxLineLength::Columns(100)),
                     },
                     Rule {
                         pattern: "*.md".to_owned(),
@@ -358,7 +440,9 @@ mod tests {
                         indent_size: None,
                         tab_width: Some(8),
                         end_of_line: Some(EndOfLine::CrLf),
+                        charset: Some(Charset::Utf16Le),
                         insert_final_newline: Some(false),
+                        max_line_length: Some(MaxLineLength::Off),
                     },
                     Rule {
                         pattern: "broken".to_owned(),
@@ -366,7 +450,9 @@ mod tests {
                         indent_size: None,
                         tab_width: None,
                         end_of_line: None,
+                        charset: None,
                         insert_final_newline: None,
+                        max_line_length: None,
                     }
                 ]
             }
@@ -133,7 +133,7 @@ fn main() {
     mod_rs.push_str("    }\n");
     mod_rs.push_str("}\n\n");
 
-    for scm_name in &["highlights", "indents"] {
+    for scm_name in &["highlights", "indents", "textobjects", "injections", "folds"] {
         mod_rs.push_str(&format!(
             "pub fn get_{}_query(name: &str) -> Option<&str> {{\n",
             scm_name
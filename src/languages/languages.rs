@@ -15,6 +15,8 @@ pub struct Language {
     pub filenames: &'static [&'static str],
     pub extensions: &'static [&'static str],
     pub line_comment: Option<&'static str>,
+    /// The start and end delimiters of a block comment, e.g. `/*` and `*/`.
+    pub block_comment: Option<(&'static str, &'static str)>,
     /// `\1` is replaced with the finder query.
     pub heutristic_search_regex: Option<&'static str>,
     pub tree_sitter: Option<TreeSitter>,
@@ -40,6 +42,7 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &[],
         line_comment: None,
+        block_comment: None,
         heutristic_search_regex: None,
         tree_sitter: None,
     },
@@ -48,6 +51,7 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["rs"],
         line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
         heutristic_search_regex: Some(r"(type|struct|enum|trait|static|const|fn)\s\1"),
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/tree-sitter/tree-sitter-rust",
@@ -60,6 +64,7 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["c", "h"],
         line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
         heutristic_search_regex: None,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/tree-sitter/tree-sitter-c",
@@ -72,6 +77,7 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["cpp", "cxx", "hpp", "hxx"],
         line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
         heutristic_search_regex: None,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/tree-sitter/tree-sitter-cpp",
@@ -84,6 +90,7 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["js"],
         line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
         heutristic_search_regex: None,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/tree-sitter/tree-sitter-javascript",
@@ -96,6 +103,7 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["py"],
         line_comment: Some("#"),
+        block_comment: None,
         heutristic_search_regex: None,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/tree-sitter/tree-sitter-python",
@@ -108,6 +116,7 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["go"],
         line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
         heutristic_search_regex: None,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/tree-sitter/tree-sitter-go",
@@ -120,6 +129,7 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["sh", "bash"],
         line_comment: Some("#"),
+        block_comment: None,
         heutristic_search_regex: None,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/tree-sitter/tree-sitter-bash",
@@ -132,6 +142,7 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["html"],
         line_comment: None,
+        block_comment: Some(("<!--", "-->")),
         heutristic_search_regex: None,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/tree-sitter/tree-sitter-html",
@@ -144,6 +155,7 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["css"],
         line_comment: None,
+        block_comment: Some(("/*", "*/")),
         heutristic_search_regex: None,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/tree-sitter/tree-sitter-css",
@@ -156,6 +168,7 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["scss"],
         line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
         heutristic_search_regex: None,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/serenadeai/tree-sitter-scss",
@@ -168,6 +181,7 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["ts"],
         line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
         heutristic_search_regex: None,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/tree-sitter/tree-sitter-typescript",
@@ -180,6 +194,7 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["tsx"],
         line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
         heutristic_search_regex: None,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/tree-sitter/tree-sitter-typescript",
@@ -192,6 +207,7 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["md"],
         line_comment: None,
+        block_comment: Some(("<!--", "-->")),
         heutristic_search_regex: None,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/MDeiml/tree-sitter-markdown",
@@ -204,6 +220,7 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["toml"],
         line_comment: Some("#"),
+        block_comment: None,
         heutristic_search_regex: None,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/ikatyang/tree-sitter-toml",
@@ -216,6 +233,7 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["json"],
         line_comment: None,
+        block_comment: None,
         heutristic_search_regex: None,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/tree-sitter/tree-sitter-json",
@@ -228,6 +246,7 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["yml", "yaml"],
         line_comment: Some("#"),
+        block_comment: None,
         heutristic_search_regex: None,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/ikatyang/tree-sitter-yaml",
@@ -240,6 +259,7 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &["Makefile"],
         extensions: &["mk", "makefile"],
         line_comment: Some("#"),
+        block_comment: None,
         heutristic_search_regex: None,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/alemuller/tree-sitter-make",
@@ -252,6 +272,7 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &["Dockerfile"],
         extensions: &["dockerfile"],
         line_comment: Some("#"),
+        block_comment: None,
         heutristic_search_regex: None,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/camdencheek/tree-sitter-dockerfile",
@@ -264,6 +285,7 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &[],
         line_comment: None,
+        block_comment: None,
         heutristic_search_regex: None,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/tree-sitter/tree-sitter-regex",
@@ -276,6 +298,7 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &[],
         line_comment: None,
+        block_comment: None,
         heutristic_search_regex: None,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/stsewd/tree-sitter-comment",